@@ -0,0 +1,221 @@
+use crate::context::AppContext;
+use crate::error::Error;
+use crate::ids::{FactorId, GeneId, GeneSetId, TraitGeneSetId, TraitId};
+use crate::s3;
+use crate::s3::FilePath;
+use crate::tsv::{CsvConsumer, Record};
+use log::info;
+
+pub(crate) mod files {
+    pub(crate) const PHENO_GENE: &str = "pheno_gene.csv";
+    pub(crate) const PHENO_GENE_SET: &str = "pheno_geneset.csv";
+    pub(crate) const FACTOR_GENE: &str = "factor_gene.csv";
+    pub(crate) const FACTOR_GENE_SET: &str = "factor_geneset.csv";
+    pub(crate) const FACTOR_PHENOS: &str = "factor_phenos.csv";
+    pub(crate) const GENE_GENE_SET: &str = "gene_geneset.csv";
+    pub(crate) const PHENOTYPE_NAMES: &str = "phenotype_names.csv";
+}
+
+mod fields {
+    pub(crate) const PHENO: &str = "pheno";
+    pub(crate) const GENE: &str = "gene";
+    pub(crate) const GENE_SET: &str = "gene_set";
+    pub(crate) const FACTOR: &str = "factor";
+    pub(crate) const LABEL: &str = "label";
+    pub(crate) const WEIGHT: &str = "weight";
+    pub(crate) const COMBINED: &str = "combined";
+    pub(crate) const PRIOR: &str = "prior";
+    pub(crate) const BETA_UNCORRECTED: &str = "beta_uncorrected";
+    pub(crate) const BETA: &str = "beta";
+    pub(crate) const ANY_RELEVANCE: &str = "any_relevance";
+    pub(crate) const PGS: &str = "pgs";
+    pub(crate) const SOURCE: &str = "source";
+    pub(crate) const PHENOTYPE: &str = "phenotype";
+    pub(crate) const PHENOTYPE_NAME: &str = "phenotype_name";
+}
+
+pub(crate) trait BulkRow: Sized {
+    const FILE: &'static str;
+    fn from_record(record: &Record) -> Result<Self, Error>;
+}
+
+pub(crate) struct PhenoGene {
+    pub(crate) pheno: TraitId,
+    pub(crate) gene: GeneId,
+    pub(crate) combined: f64,
+    pub(crate) prior: f64,
+}
+
+pub(crate) struct PhenoGeneSet {
+    pub(crate) pheno: TraitId,
+    pub(crate) gene_set: GeneSetId,
+    pub(crate) beta_uncorrected: f64,
+    pub(crate) beta: f64,
+}
+
+pub(crate) struct FactorGene {
+    pub(crate) factor: FactorId,
+    pub(crate) gene: GeneId,
+    pub(crate) weight: f64,
+}
+
+pub(crate) struct FactorGeneSet {
+    pub(crate) factor: FactorId,
+    pub(crate) gene_set: GeneSetId,
+    pub(crate) weight: f64,
+}
+
+pub(crate) struct FactorPheno {
+    pub(crate) factor: FactorId,
+    pub(crate) label: String,
+    pub(crate) pheno: TraitId,
+    pub(crate) any_relevance: f64,
+}
+
+pub(crate) struct PhenoPgs {
+    pub(crate) pheno: TraitId,
+    pub(crate) pgs: TraitGeneSetId,
+    pub(crate) source: String,
+    pub(crate) beta_uncorrected: f64,
+    pub(crate) beta: f64,
+}
+
+/// A row of `pheno_geneset.csv`, which links traits to gene sets when written by `bulk` and to
+/// trait gene sets when written by `pgs`.
+pub(crate) enum AnyPhenoGeneSet {
+    GeneSet(PhenoGeneSet),
+    TraitGeneSet(PhenoPgs),
+}
+
+pub(crate) struct GenePgs {
+    pub(crate) gene: GeneId,
+    pub(crate) pgs: TraitGeneSetId,
+    pub(crate) beta: f64,
+}
+
+/// A row of `phenotype_names.csv`, which the upload scripts use as trait labels.
+pub(crate) struct PhenotypeName {
+    pub(crate) phenotype: TraitId,
+    pub(crate) name: String,
+}
+
+impl BulkRow for PhenoGene {
+    const FILE: &'static str = files::PHENO_GENE;
+    fn from_record(record: &Record) -> Result<Self, Error> {
+        Ok(PhenoGene {
+            pheno: record.require(fields::PHENO)?.parse()?,
+            gene: record.require(fields::GENE)?.parse()?,
+            combined: record.weight(fields::COMBINED),
+            prior: record.weight(fields::PRIOR),
+        })
+    }
+}
+
+impl BulkRow for PhenoGeneSet {
+    const FILE: &'static str = files::PHENO_GENE_SET;
+    fn from_record(record: &Record) -> Result<Self, Error> {
+        Ok(PhenoGeneSet {
+            pheno: record.require(fields::PHENO)?.parse()?,
+            gene_set: record.require(fields::GENE_SET)?.parse()?,
+            beta_uncorrected: record.weight(fields::BETA_UNCORRECTED),
+            beta: record.weight(fields::BETA),
+        })
+    }
+}
+
+impl BulkRow for FactorGene {
+    const FILE: &'static str = files::FACTOR_GENE;
+    fn from_record(record: &Record) -> Result<Self, Error> {
+        Ok(FactorGene {
+            factor: record.require(fields::FACTOR)?.parse()?,
+            gene: record.require(fields::GENE)?.parse()?,
+            weight: record.weight(fields::WEIGHT),
+        })
+    }
+}
+
+impl BulkRow for FactorGeneSet {
+    const FILE: &'static str = files::FACTOR_GENE_SET;
+    fn from_record(record: &Record) -> Result<Self, Error> {
+        Ok(FactorGeneSet {
+            factor: record.require(fields::FACTOR)?.parse()?,
+            gene_set: record.require(fields::GENE_SET)?.parse()?,
+            weight: record.weight(fields::WEIGHT),
+        })
+    }
+}
+
+impl BulkRow for FactorPheno {
+    const FILE: &'static str = files::FACTOR_PHENOS;
+    fn from_record(record: &Record) -> Result<Self, Error> {
+        Ok(FactorPheno {
+            factor: record.require(fields::FACTOR)?.parse()?,
+            label: record.require(fields::LABEL)?.to_string(),
+            pheno: record.require(fields::PHENO)?.parse()?,
+            any_relevance: record.weight(fields::ANY_RELEVANCE),
+        })
+    }
+}
+
+impl BulkRow for AnyPhenoGeneSet {
+    const FILE: &'static str = files::PHENO_GENE_SET;
+    fn from_record(record: &Record) -> Result<Self, Error> {
+        match record.get(fields::PGS) {
+            Some(pgs) => Ok(AnyPhenoGeneSet::TraitGeneSet(PhenoPgs {
+                pheno: record.require(fields::PHENO)?.parse()?,
+                pgs: pgs.parse()?,
+                source: record.require(fields::SOURCE)?.to_string(),
+                beta_uncorrected: record.weight(fields::BETA_UNCORRECTED),
+                beta: record.weight(fields::BETA),
+            })),
+            None => Ok(AnyPhenoGeneSet::GeneSet(PhenoGeneSet::from_record(record)?)),
+        }
+    }
+}
+
+impl BulkRow for GenePgs {
+    const FILE: &'static str = files::GENE_GENE_SET;
+    fn from_record(record: &Record) -> Result<Self, Error> {
+        Ok(GenePgs {
+            gene: record.require(fields::GENE)?.parse()?,
+            pgs: record.require(fields::PGS)?.parse()?,
+            beta: record.weight(fields::BETA),
+        })
+    }
+}
+
+impl BulkRow for PhenotypeName {
+    const FILE: &'static str = files::PHENOTYPE_NAMES;
+    fn from_record(record: &Record) -> Result<Self, Error> {
+        Ok(PhenotypeName {
+            phenotype: record.require(fields::PHENOTYPE)?.parse()?,
+            name: record.require(fields::PHENOTYPE_NAME)?.to_string(),
+        })
+    }
+}
+
+pub(crate) fn bulk_file(dir: &str, file: &str) -> String {
+    format!("{}/{}", dir.trim_end_matches('/'), file)
+}
+
+pub(crate) fn for_each_row<R: BulkRow, F: FnMut(R) -> Result<(), Error>>(
+    context: &AppContext, dir: &str, mut consumer: F
+) -> Result<(), Error> {
+    let file = bulk_file(dir, R::FILE);
+    info!("Reading {file}");
+    let mut csv_consumer = CsvConsumer::new(|record| {
+        consumer(R::from_record(&record)?)
+    });
+    let file_path = FilePath::from_path(&file)?;
+    s3::process_file(context, &file_path, &mut csv_consumer)
+        .map_err(|e| Error::wrap(format!("Failed to process {file}"), e))
+}
+
+pub(crate) fn read_rows<R: BulkRow>(context: &AppContext, dir: &str) -> Result<Vec<R>, Error> {
+    let mut rows: Vec<R> = Vec::new();
+    for_each_row(context, dir, |row: R| {
+        rows.push(row);
+        Ok(())
+    })?;
+    Ok(rows)
+}
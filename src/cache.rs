@@ -0,0 +1,424 @@
+use crate::config::{CacheConfig, CacheMode, S3CacheConfig};
+use crate::context::AppContext;
+use crate::error::Error;
+use crate::output;
+use crate::output::RecordWriter;
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+const OBJECTS_DIR: &str = "objects";
+const LISTINGS_DIR: &str = "listings";
+const INDEX_FILE: &str = "index.json";
+const PARTIAL_SUFFIX: &str = ".partial";
+
+static PARTIAL_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// An object as listed, which is all a cached listing needs to answer later.
+#[derive(Clone, Serialize, Deserialize)]
+pub(crate) struct ListedObject {
+    pub(crate) key: String,
+    pub(crate) size: Option<u64>,
+    pub(crate) last_modified: Option<SystemTime>,
+    pub(crate) etag: Option<String>,
+}
+
+/// The common prefixes and objects under a prefix, from all pages.
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub(crate) struct Listing {
+    pub(crate) prefixes: Vec<String>,
+    pub(crate) objects: Vec<ListedObject>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct CachedListing {
+    listed_at: SystemTime,
+    listing: Listing,
+}
+
+#[derive(Serialize, Deserialize)]
+struct CachedObject {
+    bucket: String,
+    key: String,
+    etag: String,
+    size: u64,
+    last_used: SystemTime,
+}
+
+/// The cached objects by file name.
+#[derive(Default, Serialize, Deserialize)]
+struct Index {
+    objects: BTreeMap<String, CachedObject>,
+}
+
+impl Index {
+    fn size(&self) -> u64 {
+        self.objects.values().map(|object| object.size).sum()
+    }
+}
+
+/// S3 objects on disk keyed by bucket, key and ETag, so a changed object is downloaded again,
+/// and listings kept for a limited time. Once objects exceed the size limit, the least recently
+/// used are evicted.
+pub(crate) struct S3Cache {
+    config: S3CacheConfig,
+    index_lock: Mutex<()>,
+}
+
+/// A file name derived from the parts, safe whatever characters keys contain.
+fn file_name(parts: &[&str]) -> String {
+    let mut hasher = Sha256::new();
+    for part in parts {
+        hasher.update(part.as_bytes());
+        hasher.update([0u8]);
+    }
+    format!("{:x}", hasher.finalize())
+}
+
+fn load_index(dir: &Path) -> Index {
+    let index_file = dir.join(INDEX_FILE);
+    if !index_file.exists() {
+        return Index::default();
+    }
+    let index = File::open(&index_file).map_err(Error::from).and_then(|file| {
+        serde_json::from_reader::<_, Index>(BufReader::new(file)).map_err(Error::from)
+    });
+    index.unwrap_or_else(|error| {
+        warn!("Ignoring unreadable cache index {}: {error}", index_file.display());
+        Index::default()
+    })
+}
+
+/// A temporary file for the given file name, unique across processes and threads sharing the
+/// cache.
+fn partial_file_name(name: &str) -> String {
+    let count = PARTIAL_COUNTER.fetch_add(1, Ordering::Relaxed);
+    format!("{name}.{}.{count}{PARTIAL_SUFFIX}", std::process::id())
+}
+
+/// Writes the index to a temporary file first, so an interrupted write loses nothing.
+fn save_index(dir: &Path, index: &Index) -> Result<(), Error> {
+    std::fs::create_dir_all(dir)?;
+    let index_file = dir.join(INDEX_FILE);
+    let partial_file = dir.join(partial_file_name(INDEX_FILE));
+    let mut writer = BufWriter::new(File::create(&partial_file)?);
+    serde_json::to_writer(&mut writer, index)?;
+    writer.flush()?;
+    std::fs::rename(&partial_file, &index_file)?;
+    Ok(())
+}
+
+fn count_files(dir: &Path) -> Result<usize, Error> {
+    if !dir.exists() {
+        return Ok(0);
+    }
+    Ok(std::fs::read_dir(dir)?.count())
+}
+
+impl S3Cache {
+    pub(crate) fn new(config: S3CacheConfig) -> S3Cache {
+        S3Cache { config, index_lock: Mutex::new(()) }
+    }
+    /// The cache directory, unless the cache is off.
+    fn dir(&self) -> Option<&Path> {
+        match self.config.mode {
+            CacheMode::Off => None,
+            CacheMode::Use | CacheMode::Refresh => self.config.dir.as_deref(),
+        }
+    }
+    pub(crate) fn is_enabled(&self) -> bool {
+        self.dir().is_some()
+    }
+    pub(crate) fn fits(&self, size: u64) -> bool {
+        size <= self.config.max_bytes
+    }
+    /// Loads the index, lets the function change it and saves it, all while holding the lock.
+    fn update_index<T>(&self, dir: &Path, update: impl FnOnce(&mut Index) -> T)
+        -> Result<T, Error> {
+        let _guard = self.index_lock.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let mut index = load_index(dir);
+        let result = update(&mut index);
+        save_index(dir, &index)?;
+        Ok(result)
+    }
+    fn listing_file(dir: &Path, bucket: &str, prefix: &str, delimiter: Option<&str>) -> PathBuf {
+        let name = file_name(&[bucket, prefix, delimiter.unwrap_or_default()]);
+        dir.join(LISTINGS_DIR).join(format!("{name}.json"))
+    }
+    /// The cached listing, if there is one younger than the listing TTL and not refreshing.
+    pub(crate) fn listing(&self, bucket: &str, prefix: &str, delimiter: Option<&str>)
+        -> Option<Listing> {
+        let dir = self.dir().filter(|_| self.config.mode == CacheMode::Use)?;
+        let file = File::open(S3Cache::listing_file(dir, bucket, prefix, delimiter)).ok()?;
+        let cached: CachedListing = serde_json::from_reader(BufReader::new(file)).ok()?;
+        let age = cached.listed_at.elapsed().ok()?;
+        if age > self.config.listing_ttl {
+            return None;
+        }
+        info!("Using cached listing of s3://{bucket}/{prefix} from {} ago",
+            humantime::format_duration(std::time::Duration::from_secs(age.as_secs())));
+        Some(cached.listing)
+    }
+    /// Caches the listing if the cache is on, only warning if that fails.
+    pub(crate) fn add_listing(&self, bucket: &str, prefix: &str, delimiter: Option<&str>,
+                              listing: &Listing) {
+        let Some(dir) = self.dir() else {
+            return;
+        };
+        let listing_file = S3Cache::listing_file(dir, bucket, prefix, delimiter);
+        let cached = CachedListing { listed_at: SystemTime::now(), listing: listing.clone() };
+        let result = std::fs::create_dir_all(dir.join(LISTINGS_DIR)).map_err(Error::from)
+            .and_then(|_| Ok(File::create(&listing_file)?))
+            .and_then(|file| Ok(serde_json::to_writer(BufWriter::new(file), &cached)?));
+        if let Err(error) = result {
+            warn!("Could not cache listing of s3://{bucket}/{prefix}: {error}");
+        }
+    }
+    /// The cached file for this version of the object, if there is one and not refreshing.
+    pub(crate) fn object(&self, bucket: &str, key: &str, etag: &str)
+        -> Result<Option<PathBuf>, Error> {
+        let Some(dir) = self.dir().filter(|_| self.config.mode == CacheMode::Use) else {
+            return Ok(None);
+        };
+        let name = file_name(&[bucket, key, etag]);
+        let file = dir.join(OBJECTS_DIR).join(&name);
+        let is_cached = self.update_index(dir, |index| {
+            match index.objects.get_mut(&name) {
+                Some(object) if file.exists() => {
+                    object.last_used = SystemTime::now();
+                    true
+                }
+                _ => false,
+            }
+        })?;
+        Ok(if is_cached { Some(file) } else { None })
+    }
+    /// Where to download the object to before adding it with `add_object`, or removing it
+    /// with `discard_partial` if the download fails.
+    pub(crate) fn partial_file(&self, bucket: &str, key: &str, etag: &str)
+        -> Result<PathBuf, Error> {
+        let dir = self.dir().ok_or_else(|| Error::from("The S3 cache is off."))?;
+        let objects_dir = dir.join(OBJECTS_DIR);
+        std::fs::create_dir_all(&objects_dir)?;
+        Ok(objects_dir.join(partial_file_name(&file_name(&[bucket, key, etag]))))
+    }
+    pub(crate) fn discard_partial(&self, partial_file: &Path) {
+        if let Err(error) = std::fs::remove_file(partial_file)
+            && error.kind() != std::io::ErrorKind::NotFound {
+            warn!("Could not remove {}: {error}", partial_file.display());
+        }
+    }
+    /// Adds the fully downloaded object and evicts the least recently used objects if the
+    /// cache has grown too big.
+    pub(crate) fn add_object(&self, bucket: &str, key: &str, etag: &str, partial_file: &Path)
+        -> Result<PathBuf, Error> {
+        let dir = self.dir().ok_or_else(|| Error::from("The S3 cache is off."))?;
+        let name = file_name(&[bucket, key, etag]);
+        let file = dir.join(OBJECTS_DIR).join(&name);
+        std::fs::rename(partial_file, &file)?;
+        let size = std::fs::metadata(&file)?.len();
+        let max_bytes = self.config.max_bytes;
+        let evicted = self.update_index(dir, |index| {
+            let object = CachedObject {
+                bucket: bucket.to_string(),
+                key: key.to_string(),
+                etag: etag.to_string(),
+                size,
+                last_used: SystemTime::now(),
+            };
+            index.objects.insert(name.clone(), object);
+            let mut evicted: Vec<String> = Vec::new();
+            while index.size() > max_bytes {
+                let least_recently_used = index.objects.iter()
+                    .filter(|(other, _)| **other != name)
+                    .min_by_key(|(_, object)| object.last_used)
+                    .map(|(other, _)| other.clone());
+                let Some(least_recently_used) = least_recently_used else {
+                    break;
+                };
+                index.objects.remove(&least_recently_used);
+                evicted.push(least_recently_used);
+            }
+            evicted
+        })?;
+        for evicted_name in &evicted {
+            let evicted_file = dir.join(OBJECTS_DIR).join(evicted_name);
+            if let Err(error) = std::fs::remove_file(&evicted_file) {
+                warn!("Could not remove {} from the cache: {error}", evicted_file.display());
+            }
+        }
+        if !evicted.is_empty() {
+            info!("Evicted {} objects from the cache to stay within its size limit.",
+                evicted.len());
+        }
+        Ok(file)
+    }
+}
+
+fn mode_name(mode: CacheMode) -> &'static str {
+    match mode {
+        CacheMode::Off => "off",
+        CacheMode::Use => "on",
+        CacheMode::Refresh => "refreshing",
+    }
+}
+
+/// Removes the cached objects, listings and index, but nothing else in the cache directory.
+fn clear(dir: &Path) -> Result<(), Error> {
+    for sub_dir in [OBJECTS_DIR, LISTINGS_DIR] {
+        let sub_dir = dir.join(sub_dir);
+        if sub_dir.exists() {
+            std::fs::remove_dir_all(&sub_dir)?;
+        }
+    }
+    let index_file = dir.join(INDEX_FILE);
+    if index_file.exists() {
+        std::fs::remove_file(&index_file)?;
+    }
+    Ok(())
+}
+
+fn write_objects(index: Index, config: &CacheConfig, writer: &mut dyn Write)
+    -> Result<(), Error> {
+    let headers: Vec<String> = ["bucket", "key", "etag", "size", "last_used"]
+        .iter().map(|header| header.to_string()).collect();
+    let mut record_writer = RecordWriter::new(config.format, headers, writer)?;
+    let mut objects: Vec<CachedObject> = index.objects.into_values().collect();
+    objects.sort_by_key(|object| std::cmp::Reverse(object.last_used));
+    for object in objects {
+        record_writer.write(&[
+            json!(object.bucket),
+            json!(object.key),
+            json!(object.etag),
+            json!(object.size),
+            json!(humantime::format_rfc3339_seconds(object.last_used).to_string()),
+        ])?;
+    }
+    record_writer.finish()
+}
+
+pub(crate) fn cache(context: &AppContext, config: &CacheConfig) -> Result<(), Error> {
+    let cache = context.s3_cache();
+    let dir = cache.config.dir.as_deref()
+        .ok_or_else(|| Error::from("No cache directory (cache/dir) and no app directory."))?;
+    if config.clear {
+        let _guard = cache.index_lock.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        clear(dir)?;
+        info!("Cleared the cache in {}.", dir.display());
+    }
+    let index = load_index(dir);
+    let mut writer = output::open(config.out.as_deref())?;
+    if config.long {
+        return write_objects(index, config, &mut writer);
+    }
+    writeln!(writer, "Cache directory: {}", dir.display())?;
+    writeln!(writer, "Cache: {}", mode_name(cache.config.mode))?;
+    writeln!(writer, "Objects: {} with {} of at most {} bytes", index.objects.len(), index.size(),
+             cache.config.max_bytes)?;
+    writeln!(writer, "Listings: {}, kept for {}", count_files(&dir.join(LISTINGS_DIR))?,
+             humantime::format_duration(cache.config.listing_ttl))?;
+    writer.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{file_name, CachedListing, Listing, S3Cache, INDEX_FILE, LISTINGS_DIR};
+    use crate::config::{CacheMode, S3CacheConfig};
+    use std::path::{Path, PathBuf};
+    use std::time::{Duration, SystemTime};
+
+    const BUCKET: &str = "bucket";
+    const ETAG: &str = "etag";
+
+    fn cache_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("ygramul-cache-{name}-{}", std::process::id()));
+        if dir.exists() {
+            std::fs::remove_dir_all(&dir).unwrap();
+        }
+        dir
+    }
+
+    fn cache(dir: &Path, mode: CacheMode, max_bytes: u64) -> S3Cache {
+        let listing_ttl = Duration::from_secs(60);
+        S3Cache::new(S3CacheConfig { dir: Some(dir.to_path_buf()), mode, max_bytes, listing_ttl })
+    }
+
+    fn add(cache: &S3Cache, key: &str, size: usize) -> PathBuf {
+        let partial_file = cache.partial_file(BUCKET, key, ETAG).unwrap();
+        std::fs::write(&partial_file, vec![b'x'; size]).unwrap();
+        // Keeps the last-used times of consecutive objects apart.
+        std::thread::sleep(Duration::from_millis(2));
+        cache.add_object(BUCKET, key, ETAG, &partial_file).unwrap()
+    }
+
+    fn cached_keys(cache: &S3Cache, keys: &[&str]) -> Vec<String> {
+        keys.iter().filter(|key| cache.object(BUCKET, key, ETAG).unwrap().is_some())
+            .map(|key| key.to_string()).collect()
+    }
+
+    #[test]
+    fn evicts_least_recently_used() {
+        let dir = cache_dir("lru");
+        let cache = cache(&dir, CacheMode::Use, 10);
+        let a = add(&cache, "a", 4);
+        let b = add(&cache, "b", 4);
+        assert!(cache.object(BUCKET, "a", ETAG).unwrap().is_some());
+        std::thread::sleep(Duration::from_millis(2));
+        let c = add(&cache, "c", 4);
+        assert!(a.exists() && !b.exists() && c.exists());
+        assert_eq!(cached_keys(&cache, &["a", "b", "c"]), ["a", "c"]);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn oversized_object_evicts_all_others() {
+        let dir = cache_dir("oversized");
+        let cache = cache(&dir, CacheMode::Use, 10);
+        assert!(cache.fits(10) && !cache.fits(11));
+        add(&cache, "a", 4);
+        add(&cache, "b", 4);
+        let big = add(&cache, "big", 12);
+        assert!(big.exists());
+        assert_eq!(cached_keys(&cache, &["a", "b", "big"]), ["big"]);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn index_persists_across_instances() {
+        let dir = cache_dir("index");
+        add(&cache(&dir, CacheMode::Use, 100), "a", 4);
+        assert_eq!(cached_keys(&cache(&dir, CacheMode::Use, 100), &["a", "b"]), ["a"]);
+        assert!(cached_keys(&cache(&dir, CacheMode::Refresh, 100), &["a"]).is_empty());
+        std::fs::write(dir.join(INDEX_FILE), "not json").unwrap();
+        assert!(cached_keys(&cache(&dir, CacheMode::Use, 100), &["a"]).is_empty());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn listings_expire() {
+        let dir = cache_dir("listings");
+        let cache = cache(&dir, CacheMode::Use, 100);
+        let listing = Listing { prefixes: vec!["prefix/sub/".to_string()], objects: Vec::new() };
+        cache.add_listing(BUCKET, "prefix/", Some("/"), &listing);
+        let cached = cache.listing(BUCKET, "prefix/", Some("/")).unwrap();
+        assert_eq!(cached.prefixes, listing.prefixes);
+        assert!(cache.listing(BUCKET, "prefix/", None).is_none());
+        let listing_file = dir.join(LISTINGS_DIR)
+            .join(format!("{}.json", file_name(&[BUCKET, "prefix/", "/"])));
+        let listed_at = SystemTime::now() - Duration::from_secs(61);
+        let stale = CachedListing { listed_at, listing };
+        std::fs::write(&listing_file, serde_json::to_string(&stale).unwrap()).unwrap();
+        assert!(cache.listing(BUCKET, "prefix/", Some("/")).is_none());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
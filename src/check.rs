@@ -0,0 +1,221 @@
+use crate::config::CheckConfig;
+use crate::context::AppContext;
+use crate::error::Error;
+use crate::neo::{Neo, TypedRowEater};
+use crate::output;
+use crate::output::{OutputFormat, RecordWriter};
+use crate::schema::{labels, rel_types};
+use crate::wipe::confirm;
+use log::{info, warn};
+use neo4rs::query;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::io::Write;
+
+const FIX_BATCH_SIZE: usize = 10000;
+const LEGACY_PHENO_LABEL: &str = "Pheno";
+
+/// An integrity rule: a pattern producing one row per violation, with a readable `sample` for
+/// each, and a statement fixing all violations if that is safe. Fixes that delete nodes need
+/// confirmation.
+struct Rule {
+    name: &'static str,
+    description: &'static str,
+    pattern: String,
+    sample: &'static str,
+    fix: Option<String>,
+    deletes: bool,
+}
+
+struct Outcome {
+    rule: Rule,
+    violations: i64,
+    samples: Vec<String>,
+    fixed: bool,
+}
+
+#[derive(Deserialize)]
+struct Count {
+    n: i64,
+}
+
+#[derive(Deserialize)]
+struct Sample {
+    sample: Option<String>,
+}
+
+/// The weight-like properties of edges that are floats but not finite numbers.
+fn invalid_float(value: &str) -> String {
+    format!("(valueType({value}) STARTS WITH 'FLOAT' AND (isNaN({value}) \
+        OR {value} > 1.7976931348623157e308 OR {value} < -1.7976931348623157e308))")
+}
+
+fn rules() -> Vec<Rule> {
+    let edge_sample = "coalesce(startNode(r).id, elementId(startNode(r))) + ' -[' + type(r) \
+        + ']-> ' + coalesce(endNode(r).id, elementId(endNode(r)))";
+    vec![
+        Rule {
+            name: "orphan-factors",
+            description: "Factors without a trait.",
+            pattern: format!("MATCH (n:{}) WHERE NOT EXISTS {{ (n)<-[:{}]-(:{}) }}",
+                             labels::FACTOR, rel_types::TRAIT_FACTOR, labels::TRAIT),
+            sample: "n.id",
+            fix: Some(format!(
+                "MATCH (n:{}) WHERE NOT EXISTS {{ (n)<-[:{}]-(:{}) }}\n\
+                CALL (n) {{ DETACH DELETE n }} IN TRANSACTIONS OF {FIX_BATCH_SIZE} ROWS",
+                labels::FACTOR, rel_types::TRAIT_FACTOR, labels::TRAIT
+            )),
+            deletes: true,
+        },
+        Rule {
+            name: "isolated-nodes",
+            description: "Nodes without any relationships.",
+            pattern: "MATCH (n) WHERE NOT EXISTS { (n)--() }".to_string(),
+            sample: "head(labels(n)) + ':' + coalesce(n.id, elementId(n))",
+            // Nodes may be isolated on purpose, e.g. a trait without data yet.
+            fix: None,
+            deletes: false,
+        },
+        Rule {
+            name: "invalid-weights",
+            description: "Relationships with NaN or infinite numbers.",
+            pattern: format!("MATCH ()-[r]->() WHERE any(key IN keys(r) WHERE {})",
+                             invalid_float("r[key]")),
+            sample: edge_sample,
+            fix: Some(format!(
+                "MATCH ()-[r]->()\n\
+                UNWIND keys(r) AS key\n\
+                WITH r, key WHERE {}\n\
+                CALL (r, key) {{ SET r[key] = null }} IN TRANSACTIONS OF {FIX_BATCH_SIZE} ROWS",
+                invalid_float("r[key]")
+            )),
+            deletes: false,
+        },
+        Rule {
+            name: "negative-weights",
+            description: "Relationships with a negative weight.",
+            pattern: "MATCH ()-[r]->() WHERE r.weight < 0".to_string(),
+            sample: edge_sample,
+            fix: None,
+            deletes: false,
+        },
+        Rule {
+            name: "case-duplicate-ids",
+            description: "Nodes with the same label and ids that differ only in case.",
+            pattern: "MATCH (n) WHERE n.id IS NOT NULL\n\
+                UNWIND labels(n) AS label\n\
+                WITH label, toLower(n.id) AS key, collect(DISTINCT n.id) AS ids\n\
+                WHERE size(ids) > 1".to_string(),
+            sample: "label + ':' + reduce(joined = head(ids), id IN tail(ids) | \
+                joined + '/' + id)",
+            fix: None,
+            deletes: false,
+        },
+        Rule {
+            name: "mixed-labels",
+            description: "Ids used by nodes with different labels, such as a Pheno and a Trait.",
+            pattern: "MATCH (n) WHERE n.id IS NOT NULL\n\
+                WITH n.id AS id, collect(DISTINCT labels(n)) AS label_sets\n\
+                WHERE size(label_sets) > 1".to_string(),
+            sample: "id + ' (' + trim(reduce(joined = '', label_set IN label_sets | joined + ' ' \
+                + reduce(inner = '', label IN label_set | inner + ':' + label))) + ')'",
+            fix: None,
+            deletes: false,
+        },
+        Rule {
+            name: "legacy-labels",
+            description: "Nodes with the legacy Pheno label instead of Trait.",
+            pattern: format!("MATCH (n:{LEGACY_PHENO_LABEL})"),
+            sample: "coalesce(n.id, elementId(n))",
+            fix: None,
+            deletes: false,
+        },
+        Rule {
+            name: "unlabeled-factors",
+            description: "Factors without a label property.",
+            pattern: format!("MATCH (n:{}) WHERE n.label IS NULL OR trim(n.label) = ''",
+                             labels::FACTOR),
+            sample: "n.id",
+            fix: None,
+            deletes: false,
+        },
+    ]
+}
+
+fn count(neo: &Neo, rule: &Rule) -> Result<i64, Error> {
+    let statement = format!("{}\nRETURN count(*) AS n", rule.pattern);
+    let counts: Vec<Count> = neo.read(query(&statement), &mut TypedRowEater::new())?;
+    Ok(counts.first().map(|count| count.n).unwrap_or(0))
+}
+
+fn find_samples(neo: &Neo, rule: &Rule, limit: usize) -> Result<Vec<String>, Error> {
+    let statement =
+        format!("{}\nRETURN {} AS sample\nLIMIT {limit}", rule.pattern, rule.sample);
+    let samples: Vec<Sample> = neo.read(query(&statement), &mut TypedRowEater::new())?;
+    Ok(samples.into_iter().filter_map(|sample| sample.sample).collect())
+}
+
+fn apply(neo: &Neo, rule: Rule, config: &CheckConfig) -> Result<Outcome, Error> {
+    let mut violations = count(neo, &rule)?;
+    let mut samples = find_samples(neo, &rule, config.limit)?;
+    let mut fixed = false;
+    if violations > 0 && config.fix
+        && let Some(fix) = &rule.fix
+        && (!rule.deletes || config.yes || confirm(&format!(
+            "Fixing {} deletes {violations} nodes. Proceed?", rule.name
+        ))?) {
+        info!("Fixing {violations} violations of {}.", rule.name);
+        let _: Vec<Count> = neo.cypher(query(fix), &mut TypedRowEater::new())?;
+        fixed = true;
+        violations = count(neo, &rule)?;
+        samples = find_samples(neo, &rule, config.limit)?;
+    }
+    Ok(Outcome { rule, violations, samples, fixed })
+}
+
+fn write_outcomes(outcomes: &[Outcome], format: OutputFormat, writer: &mut dyn Write)
+    -> Result<(), Error> {
+    let headers: Vec<String> = ["rule", "violations", "fix", "samples", "description"]
+        .iter().map(|header| header.to_string()).collect();
+    let mut record_writer = RecordWriter::new(format, headers, writer)?;
+    for outcome in outcomes {
+        let fix = match (&outcome.rule.fix, outcome.fixed) {
+            (None, _) => "manual",
+            (Some(_), true) => "fixed",
+            (Some(_), false) => "auto",
+        };
+        let samples = match format {
+            OutputFormat::Json => json!(outcome.samples),
+            _ => Value::String(outcome.samples.join(", ")),
+        };
+        record_writer.write(&[
+            json!(outcome.rule.name),
+            json!(outcome.violations),
+            json!(fix),
+            samples,
+            json!(outcome.rule.description),
+        ])?;
+    }
+    record_writer.finish()
+}
+
+pub(crate) fn check(context: &AppContext, config: &CheckConfig) -> Result<(), Error> {
+    let neo = context.neo(&config.neo4j)?;
+    let mut outcomes: Vec<Outcome> = Vec::new();
+    for rule in rules() {
+        info!("Checking {}", rule.name);
+        outcomes.push(apply(&neo, rule, config)?);
+    }
+    let mut writer = output::open(config.out.as_deref())?;
+    write_outcomes(&outcomes, config.format, &mut writer)?;
+    let failed: Vec<&str> = outcomes.iter()
+        .filter(|outcome| outcome.violations > 0)
+        .map(|outcome| outcome.rule.name)
+        .collect();
+    if !failed.is_empty() {
+        warn!("Violations remain for {}.", failed.join(", "));
+        Err(Error::from(format!("{} of {} integrity rules violated.", failed.len(),
+                                outcomes.len())))?
+    }
+    Ok(())
+}
@@ -1,7 +1,8 @@
 use crate::config;
-use crate::config::{Action, ACTIONS};
+use crate::config::{Action, Backend, ACTIONS};
 use crate::error::Error;
-use clap::{command, Arg, ArgMatches, Command};
+use crate::output::OutputFormat;
+use clap::{command, Arg, ArgAction, ArgMatches, Command};
 use std::path::PathBuf;
 
 pub struct Args {
@@ -11,6 +12,39 @@ pub struct Args {
     pub(crate) password: Option<String>,
     pub(crate) file: Option<String>,
     pub(crate) out: Option<String>,
+    pub(crate) hgnc_file: Option<String>,
+    pub(crate) bulk_dir: Option<String>,
+    pub(crate) traits: Option<Vec<String>>,
+    pub(crate) min_similarity: Option<f64>,
+    pub(crate) backend: Option<Backend>,
+    pub(crate) id: Option<String>,
+    pub(crate) limit: Option<usize>,
+    pub(crate) cypher: Option<String>,
+    pub(crate) params: Vec<String>,
+    pub(crate) format: Option<OutputFormat>,
+    pub(crate) sort: Option<String>,
+    pub(crate) ascending: bool,
+    pub(crate) seeds: Option<Vec<String>>,
+    pub(crate) hops: Option<usize>,
+    pub(crate) rel_types: Option<Vec<String>>,
+    pub(crate) min_weight: Option<f64>,
+    pub(crate) sources: Option<Vec<String>>,
+    pub(crate) min_beta: Option<f64>,
+    pub(crate) address: Option<String>,
+    pub(crate) labels: Option<Vec<String>>,
+    pub(crate) run_id: Option<String>,
+    pub(crate) sub_dir: Option<String>,
+    pub(crate) batch_size: Option<usize>,
+    pub(crate) yes: bool,
+    pub(crate) drop_schema: bool,
+    pub(crate) fix: bool,
+    pub(crate) recursive: bool,
+    pub(crate) long: bool,
+    pub(crate) glob: Option<String>,
+    pub(crate) summary: bool,
+    pub(crate) no_cache: bool,
+    pub(crate) refresh: bool,
+    pub(crate) clear: bool,
 }
 pub struct CliOptions {
     pub(crate) action: Option<Action>,
@@ -23,6 +57,40 @@ mod args {
     pub(crate) const PASSWORD: &str = "password";
     pub(crate) const FILE: &str = "file";
     pub(crate) const OUT: &str = "out";
+    pub(crate) const HGNC: &str = "hgnc";
+    pub(crate) const BULK_DIR: &str = "bulk-dir";
+    pub(crate) const TRAITS: &str = "traits";
+    pub(crate) const MIN_SIMILARITY: &str = "min-similarity";
+    pub(crate) const BACKEND: &str = "backend";
+    pub(crate) const ID: &str = "id";
+    pub(crate) const LIMIT: &str = "limit";
+    pub(crate) const CYPHER: &str = "cypher";
+    pub(crate) const PARAM: &str = "param";
+    pub(crate) const FORMAT: &str = "format";
+    pub(crate) const SORT: &str = "sort";
+    pub(crate) const ASCENDING: &str = "ascending";
+    pub(crate) const NODE_ID: &str = "node-id";
+    pub(crate) const SEEDS: &str = "seeds";
+    pub(crate) const HOPS: &str = "hops";
+    pub(crate) const REL_TYPES: &str = "rel-types";
+    pub(crate) const MIN_WEIGHT: &str = "min-weight";
+    pub(crate) const SOURCES: &str = "sources";
+    pub(crate) const MIN_BETA: &str = "min-beta";
+    pub(crate) const ADDRESS: &str = "address";
+    pub(crate) const LABELS: &str = "labels";
+    pub(crate) const RUN_ID: &str = "run-id";
+    pub(crate) const SUB_DIR: &str = "sub-dir";
+    pub(crate) const BATCH_SIZE: &str = "batch-size";
+    pub(crate) const YES: &str = "yes";
+    pub(crate) const DROP_SCHEMA: &str = "drop-schema";
+    pub(crate) const FIX: &str = "fix";
+    pub(crate) const RECURSIVE: &str = "recursive";
+    pub(crate) const LONG: &str = "long";
+    pub(crate) const GLOB: &str = "glob";
+    pub(crate) const SUMMARY: &str = "summary";
+    pub(crate) const NO_CACHE: &str = "no-cache";
+    pub(crate) const REFRESH: &str = "refresh";
+    pub(crate) const CLEAR: &str = "clear";
 }
 
 mod arg_short {
@@ -32,19 +100,149 @@ mod arg_short {
     pub(crate) const PASSWORD: char = 'p';
     pub(crate) const FILE: char = 'f';
     pub(crate) const OUT: char = 'o';
+    pub(crate) const HGNC: char = 'g';
+    pub(crate) const BULK_DIR: char = 'b';
+    pub(crate) const TRAITS: char = 't';
+    pub(crate) const MIN_SIMILARITY: char = 'm';
+    pub(crate) const BACKEND: char = 'k';
+    pub(crate) const ID: char = 'i';
+    pub(crate) const LIMIT: char = 'n';
+    pub(crate) const CYPHER: char = 'c';
+    pub(crate) const PARAM: char = 'P';
+    pub(crate) const FORMAT: char = 'F';
+    pub(crate) const SORT: char = 's';
+    pub(crate) const ASCENDING: char = 'a';
+    pub(crate) const SEEDS: char = 'S';
+    pub(crate) const HOPS: char = 'H';
+    pub(crate) const REL_TYPES: char = 'R';
+    pub(crate) const MIN_WEIGHT: char = 'w';
+    pub(crate) const SOURCES: char = 'e';
+    pub(crate) const MIN_BETA: char = 'B';
+    pub(crate) const ADDRESS: char = 'A';
+    pub(crate) const LABELS: char = 'L';
+    pub(crate) const RUN_ID: char = 'r';
+    pub(crate) const SUB_DIR: char = 'D';
+    pub(crate) const BATCH_SIZE: char = 'z';
+    pub(crate) const YES: char = 'y';
+    pub(crate) const DROP_SCHEMA: char = 'X';
+    pub(crate) const FIX: char = 'x';
+    pub(crate) const RECURSIVE: char = 'C';
+    pub(crate) const LONG: char = 'W';
+    pub(crate) const GLOB: char = 'G';
+    pub(crate) const SUMMARY: char = 'M';
+    pub(crate) const NO_CACHE: char = 'N';
+    pub(crate) const REFRESH: char = 'E';
+    pub(crate) const CLEAR: char = 'K';
+    pub(crate) const LS_RECURSIVE: char = 'r';
+    pub(crate) const LS_LONG: char = 'l';
 }
 
 mod arg_help {
     pub(crate) const DATA_DIR: &str = "The directory containing the data.";
     pub(crate) const URI: &str = "The URI of the Neo4j server.";
     pub(crate) const USER: &str = "The user name for the Neo4j server.";
-    pub(crate) const PASSWORD: &str = "The password for the Neo4j server.";
+    pub(crate) const PASSWORD: &str =
+        "The password for the Neo4j server. Prefer NEO4J_PASSWORD, which stays out of the shell \
+        history.";
     pub(crate) const FILE: &str = "The input file";
-    pub(crate) const OUT: &str = "The output directory";
+    pub(crate) const OUT: &str = "The output file or directory, depending on the action.";
+    pub(crate) const OUT_FILE: &str = "The output file. Default is standard output.";
+    pub(crate) const OUT_DIR: &str = "The output directory.";
+    pub(crate) const OUT_FORMAT_FILE: &str = "The output file, in the format its extension names.";
+    pub(crate) const OUT_STATS: &str = "The JSON file to write the statistics to.";
+    pub(crate) const HGNC: &str = "The HGNC complete set TSV file used to normalize genes.";
+    pub(crate) const BULK_DIR: &str = "The directory containing the bulk CSV files.";
+    pub(crate) const TRAITS: &str = "Comma-separated list of trait ids.";
+    pub(crate) const MIN_SIMILARITY: &str =
+        "The minimum similarity for factors to be linked or clustered.";
+    pub(crate) const BACKEND: &str =
+        "Where to read the graph from: neo4j (default) or memory (the CSV files in --bulk-dir).";
+    pub(crate) const ID: &str = "The id of a node.";
+    pub(crate) const LIMIT: &str = "The maximum number of results.";
+    pub(crate) const CYPHER: &str = "A Cypher statement, or several separated by semicolons.";
+    pub(crate) const PARAM: &str = "A query parameter as key=value. May be repeated.";
+    pub(crate) const FORMAT: &str = "The output format: table (default), csv, tsv or json.";
+    pub(crate) const SORT: &str = "The column to sort by.";
+    pub(crate) const ASCENDING: &str = "Sort in ascending instead of descending order.";
+    pub(crate) const NODE_ID: &str = "The id of a node, same as --id.";
+    pub(crate) const SEEDS: &str = "Comma-separated list of seed node ids.";
+    pub(crate) const HOPS: &str = "The maximum number of hops from the seed nodes.";
+    pub(crate) const REL_TYPES: &str =
+        "Comma-separated list of relationship types to follow. Default is all.";
+    pub(crate) const MIN_WEIGHT: &str = "The minimum weight of edges with a weight.";
+    pub(crate) const SOURCES: &str = "Comma-separated list of gene set sources.";
+    pub(crate) const MIN_BETA: &str = "The minimum beta of trait gene sets.";
+    pub(crate) const ADDRESS: &str = "The address for the server to listen on, as host:port.";
+    pub(crate) const LABELS: &str = "Comma-separated list of node labels.";
+    pub(crate) const RUN_ID: &str = "The id of the load run, as recorded in the run_id property.";
+    pub(crate) const SUB_DIR: &str = "The data sub-directory, as recorded in the sub_dir property.";
+    pub(crate) const BATCH_SIZE: &str = "The number of rows per transaction.";
+    pub(crate) const YES: &str = "Do not ask for confirmation.";
+    pub(crate) const DROP_SCHEMA: &str = "Also drop the constraints and indexes.";
+    pub(crate) const FIX: &str = "Also fix the violations that are safe to fix.";
+    pub(crate) const RECURSIVE: &str = "List all files below the directory, not just its entries.";
+    pub(crate) const LONG: &str = "List sizes, last-modified times and ETags too.";
+    pub(crate) const GLOB: &str =
+        "Only list paths relative to the directory matching this glob pattern.";
+    pub(crate) const SUMMARY: &str = "Total the number of files and bytes per sub-directory.";
+    pub(crate) const NO_CACHE: &str = "Read S3 directly, without using or filling the cache.";
+    pub(crate) const REFRESH: &str = "Download from S3 again and replace what is cached.";
+    pub(crate) const CLEAR: &str = "Remove everything from the cache.";
+    pub(crate) const CACHE_LONG: &str =
+        "List the cached objects with their ETags, sizes and when they were last used.";
+}
+
+/// The arguments each subcommand accepts. Without a subcommand, the action comes from the config
+/// file, so all arguments are accepted.
+mod action_args {
+    use super::args::*;
+
+    const NEO4J: [&str; 3] = [URI, USER, PASSWORD];
+
+    pub(crate) const HELLO: &[&str] = &[DATA_DIR, URI, USER, PASSWORD];
+    pub(crate) const SURVEY: &[&str] = &[DATA_DIR];
+    pub(crate) const PING: &[&str] = &[DATA_DIR, URI, USER, PASSWORD];
+    pub(crate) const UPLOAD: &[&str] =
+        &[DATA_DIR, URI, USER, PASSWORD, HGNC, RUN_ID, SUB_DIR, NO_CACHE, REFRESH];
+    pub(crate) const WIPE: &[&str] = &[
+        URI, USER, PASSWORD, LABELS, TRAITS, RUN_ID, SUB_DIR, BATCH_SIZE, YES, DROP_SCHEMA
+    ];
+    pub(crate) const CAT: &[&str] = &[FILE, NO_CACHE, REFRESH];
+    pub(crate) const LS: &[&str] =
+        &[FILE, RECURSIVE, LONG, GLOB, SUMMARY, FORMAT, OUT, NO_CACHE, REFRESH];
+    pub(crate) const PIGEAN: &[&str] = &[OUT, HGNC, NO_CACHE, REFRESH];
+    pub(crate) const STATS: &[&str] = &[FILE, OUT, NO_CACHE, REFRESH];
+    pub(crate) const FACTOR_OVERLAP: &[&str] =
+        &[BULK_DIR, TRAITS, OUT, MIN_SIMILARITY, NO_CACHE, REFRESH];
+    pub(crate) const COMPARE_TRAITS: &[&str] =
+        &[URI, USER, PASSWORD, BULK_DIR, BACKEND, TRAITS, OUT, NO_CACHE, REFRESH];
+    pub(crate) const EMBED: &[&str] = &[URI, USER, PASSWORD, BULK_DIR, OUT, NO_CACHE, REFRESH];
+    pub(crate) const VECTOR_INDEX: &[&str] = &NEO4J;
+    pub(crate) const SIMILAR: &[&str] = &[URI, USER, PASSWORD, ID, NODE_ID, LIMIT];
+    pub(crate) const META_FACTORS: &[&str] =
+        &[BULK_DIR, OUT, MIN_SIMILARITY, NO_CACHE, REFRESH];
+    pub(crate) const QUERY: &[&str] = &[URI, USER, PASSWORD, CYPHER, FILE, PARAM, FORMAT, OUT];
+    pub(crate) const LOOKUP: &[&str] = &[
+        URI, USER, PASSWORD, BULK_DIR, BACKEND, ID, NODE_ID, LIMIT, SORT, ASCENDING, FORMAT, OUT,
+        NO_CACHE, REFRESH
+    ];
+    pub(crate) const EXPORT_SUBGRAPH: &[&str] = &[
+        URI, USER, PASSWORD, BULK_DIR, BACKEND, SEEDS, HOPS, REL_TYPES, MIN_WEIGHT, OUT, NO_CACHE,
+        REFRESH
+    ];
+    pub(crate) const EXPORT_TERMS: &[&str] = &[
+        URI, USER, PASSWORD, BULK_DIR, BACKEND, TRAITS, SOURCES, MIN_BETA, OUT, NO_CACHE, REFRESH
+    ];
+    pub(crate) const SERVE: &[&str] =
+        &[URI, USER, PASSWORD, BULK_DIR, BACKEND, ADDRESS, NO_CACHE, REFRESH];
+    pub(crate) const SHELL: &[&str] = &NEO4J;
+    pub(crate) const STATUS: &[&str] = &[URI, USER, PASSWORD, FORMAT, OUT];
+    pub(crate) const CHECK: &[&str] = &[URI, USER, PASSWORD, FIX, YES, LIMIT, FORMAT, OUT];
+    pub(crate) const CACHE: &[&str] = &[CLEAR, LONG, FORMAT, OUT];
 }
 
 pub fn get_cli_options() -> Result<CliOptions, Error> {
-    let matches = add_subcommands(add_args(command!())).get_matches();
+    let matches = add_subcommands(add_args(command!(), None)).get_matches();
     match matches.subcommand() {
         Some((command, sub_matches)) => Action::try_from(command)
             .map_err(|_| Error::from(
@@ -55,47 +253,210 @@ pub fn get_cli_options() -> Result<CliOptions, Error> {
     }
 }
 
-fn new_command(name: &'static str, about: &'static str) -> Command {
-    add_args(Command::new(name).about(about))
+fn new_command(action: Action) -> Command {
+    let names = arg_names(action);
+    let mut command = add_args(Command::new(action.name()).about(action.about()), Some(names));
+    if names.contains(&args::OUT) {
+        command = command.mut_arg(args::OUT, |arg| arg.help(out_help(action)));
+    }
+    match action {
+        // The usual short options for listings.
+        Action::Ls => command
+            .mut_arg(args::RECURSIVE, |arg| arg.short(arg_short::LS_RECURSIVE))
+            .mut_arg(args::LONG, |arg| arg.short(arg_short::LS_LONG)),
+        Action::Cache => command
+            .mut_arg(args::LONG, |arg| arg.short(arg_short::LS_LONG).help(arg_help::CACHE_LONG)),
+        _ => command,
+    }
+}
+
+/// What --out names: a directory for actions writing several files, else a single file.
+fn out_help(action: Action) -> &'static str {
+    match action {
+        Action::Bulk | Action::Factors | Action::TraitGeneSets | Action::FactorOverlap
+        | Action::CompareTraits | Action::MetaFactors => arg_help::OUT_DIR,
+        Action::Embed | Action::ExportSubgraph => arg_help::OUT_FORMAT_FILE,
+        Action::Stats => arg_help::OUT_STATS,
+        _ => arg_help::OUT_FILE,
+    }
+}
+
+fn arg_names(action: Action) -> &'static [&'static str] {
+    match action {
+        Action::Hello => action_args::HELLO,
+        Action::Survey => action_args::SURVEY,
+        Action::Ping => action_args::PING,
+        Action::Upload => action_args::UPLOAD,
+        Action::Wipe => action_args::WIPE,
+        Action::Cat => action_args::CAT,
+        Action::Ls => action_args::LS,
+        Action::Bulk | Action::Factors | Action::TraitGeneSets => action_args::PIGEAN,
+        Action::Stats => action_args::STATS,
+        Action::FactorOverlap => action_args::FACTOR_OVERLAP,
+        Action::CompareTraits => action_args::COMPARE_TRAITS,
+        Action::Embed => action_args::EMBED,
+        Action::VectorIndex => action_args::VECTOR_INDEX,
+        Action::Similar => action_args::SIMILAR,
+        Action::MetaFactors => action_args::META_FACTORS,
+        Action::Query => action_args::QUERY,
+        Action::GenesForTrait | Action::GeneSetsForTrait | Action::FactorsForTrait
+        | Action::FactorsForGene | Action::FactorsForGeneSet => action_args::LOOKUP,
+        Action::ExportSubgraph => action_args::EXPORT_SUBGRAPH,
+        Action::ExportTerms => action_args::EXPORT_TERMS,
+        Action::Serve => action_args::SERVE,
+        Action::Shell => action_args::SHELL,
+        Action::Status => action_args::STATUS,
+        Action::Check => action_args::CHECK,
+        Action::Cache => action_args::CACHE,
+    }
+}
+
+fn add_args(command: Command, names: Option<&[&str]>) -> Command {
+    all_args().into_iter()
+        .filter(|arg| names.is_none_or(|names| names.contains(&arg.get_id().as_str())))
+        .fold(command, |command, arg| command.arg(arg))
 }
 
-fn add_args(command: Command) -> Command {
-    command
-        .arg(
-            new_arg(args::DATA_DIR, arg_short::DATA_DIR, arg_help::DATA_DIR)
-                .value_parser(clap::value_parser!(PathBuf)),
-        )
-        .arg(new_arg(args::URI, arg_short::URI, arg_help::URI))
-        .arg(new_arg(args::USER, arg_short::USER, arg_help::USER))
-        .arg(new_arg(args::PASSWORD, arg_short::PASSWORD, arg_help::PASSWORD))
-        .arg(new_arg(args::FILE, arg_short::FILE, arg_help::FILE))
-        .arg(new_arg(args::OUT, arg_short::OUT, arg_help::OUT))
+fn all_args() -> Vec<Arg> {
+    vec![
+        new_arg(args::DATA_DIR, arg_short::DATA_DIR, arg_help::DATA_DIR)
+            .value_parser(clap::value_parser!(PathBuf)),
+        new_arg(args::URI, arg_short::URI, arg_help::URI),
+        new_arg(args::USER, arg_short::USER, arg_help::USER),
+        new_arg(args::PASSWORD, arg_short::PASSWORD, arg_help::PASSWORD),
+        new_arg(args::FILE, arg_short::FILE, arg_help::FILE),
+        new_arg(args::OUT, arg_short::OUT, arg_help::OUT),
+        new_arg(args::HGNC, arg_short::HGNC, arg_help::HGNC),
+        new_arg(args::BULK_DIR, arg_short::BULK_DIR, arg_help::BULK_DIR),
+        new_arg(args::TRAITS, arg_short::TRAITS, arg_help::TRAITS).value_delimiter(','),
+        new_arg(args::MIN_SIMILARITY, arg_short::MIN_SIMILARITY, arg_help::MIN_SIMILARITY)
+            .value_parser(clap::value_parser!(f64)),
+        new_arg(args::BACKEND, arg_short::BACKEND, arg_help::BACKEND)
+            .value_parser(parse_backend),
+        new_arg(args::ID, arg_short::ID, arg_help::ID),
+        new_arg(args::LIMIT, arg_short::LIMIT, arg_help::LIMIT)
+            .value_parser(clap::value_parser!(usize)),
+        new_arg(args::CYPHER, arg_short::CYPHER, arg_help::CYPHER),
+        new_arg(args::PARAM, arg_short::PARAM, arg_help::PARAM).action(ArgAction::Append),
+        new_arg(args::FORMAT, arg_short::FORMAT, arg_help::FORMAT)
+            .value_parser(parse_format),
+        new_arg(args::SORT, arg_short::SORT, arg_help::SORT),
+        new_arg(args::ASCENDING, arg_short::ASCENDING, arg_help::ASCENDING)
+            .action(ArgAction::SetTrue),
+        new_arg(args::SEEDS, arg_short::SEEDS, arg_help::SEEDS).value_delimiter(','),
+        new_arg(args::HOPS, arg_short::HOPS, arg_help::HOPS)
+            .value_parser(clap::value_parser!(usize)),
+        new_arg(args::REL_TYPES, arg_short::REL_TYPES, arg_help::REL_TYPES)
+            .value_delimiter(','),
+        new_arg(args::MIN_WEIGHT, arg_short::MIN_WEIGHT, arg_help::MIN_WEIGHT)
+            .value_parser(clap::value_parser!(f64)),
+        new_arg(args::SOURCES, arg_short::SOURCES, arg_help::SOURCES).value_delimiter(','),
+        new_arg(args::MIN_BETA, arg_short::MIN_BETA, arg_help::MIN_BETA)
+            .value_parser(clap::value_parser!(f64)),
+        new_arg(args::ADDRESS, arg_short::ADDRESS, arg_help::ADDRESS),
+        new_arg(args::LABELS, arg_short::LABELS, arg_help::LABELS).value_delimiter(','),
+        new_arg(args::RUN_ID, arg_short::RUN_ID, arg_help::RUN_ID),
+        new_arg(args::SUB_DIR, arg_short::SUB_DIR, arg_help::SUB_DIR),
+        new_arg(args::BATCH_SIZE, arg_short::BATCH_SIZE, arg_help::BATCH_SIZE)
+            .value_parser(clap::value_parser!(usize)),
+        new_arg(args::YES, arg_short::YES, arg_help::YES).action(ArgAction::SetTrue),
+        new_arg(args::DROP_SCHEMA, arg_short::DROP_SCHEMA, arg_help::DROP_SCHEMA)
+            .action(ArgAction::SetTrue),
+        new_arg(args::FIX, arg_short::FIX, arg_help::FIX).action(ArgAction::SetTrue),
+        new_arg(args::RECURSIVE, arg_short::RECURSIVE, arg_help::RECURSIVE)
+            .action(ArgAction::SetTrue),
+        new_arg(args::LONG, arg_short::LONG, arg_help::LONG).action(ArgAction::SetTrue),
+        new_arg(args::GLOB, arg_short::GLOB, arg_help::GLOB),
+        new_arg(args::SUMMARY, arg_short::SUMMARY, arg_help::SUMMARY)
+            .action(ArgAction::SetTrue),
+        new_arg(args::NO_CACHE, arg_short::NO_CACHE, arg_help::NO_CACHE)
+            .action(ArgAction::SetTrue),
+        new_arg(args::REFRESH, arg_short::REFRESH, arg_help::REFRESH)
+            .action(ArgAction::SetTrue),
+        new_arg(args::CLEAR, arg_short::CLEAR, arg_help::CLEAR).action(ArgAction::SetTrue),
+        Arg::new(args::NODE_ID).help(arg_help::NODE_ID).conflicts_with(args::ID),
+    ]
 }
 
 fn new_arg(name: &'static str, short: char, help: &'static str) -> Arg {
-    Arg::new(name).short(short).help(help)
+    Arg::new(name).short(short).long(name).help(help)
+}
+fn parse_backend(value: &str) -> Result<Backend, String> {
+    Backend::try_from(value).map_err(|error| error.to_string())
 }
+
+fn parse_format(value: &str) -> Result<OutputFormat, String> {
+    OutputFormat::try_from(value).map_err(|error| error.to_string())
+}
+
 fn known_subcommands() -> String {
     format!("Known subcommands are: {}.", config::all_actions_list())
 }
 
 fn add_subcommands(command: Command) -> Command {
     ACTIONS.into_iter().fold(command, |cmd, subcommand| {
-        cmd.subcommand(new_command(subcommand.name(), subcommand.about()))
+        cmd.subcommand(new_command(subcommand))
     })
 }
 
 fn extract_args(matches: &ArgMatches) -> Args {
     Args {
-        data_dir: matches.get_one::<PathBuf>(args::DATA_DIR).cloned(),
-        uri: matches.get_one::<String>(args::URI).cloned(),
-        user: matches.get_one::<String>(args::USER).cloned(),
-        password: matches.get_one::<String>(args::PASSWORD).cloned(),
-        file: matches.get_one::<String>(args::FILE).cloned(),
-        out: matches.get_one::<String>(args::OUT).cloned(),
+        data_dir: one::<PathBuf>(matches, args::DATA_DIR),
+        uri: one::<String>(matches, args::URI),
+        user: one::<String>(matches, args::USER),
+        password: one::<String>(matches, args::PASSWORD),
+        file: one::<String>(matches, args::FILE),
+        out: one::<String>(matches, args::OUT),
+        hgnc_file: one::<String>(matches, args::HGNC),
+        bulk_dir: one::<String>(matches, args::BULK_DIR),
+        traits: many(matches, args::TRAITS),
+        min_similarity: one::<f64>(matches, args::MIN_SIMILARITY),
+        backend: one::<Backend>(matches, args::BACKEND),
+        id: one::<String>(matches, args::ID)
+            .or_else(|| one::<String>(matches, args::NODE_ID)),
+        limit: one::<usize>(matches, args::LIMIT),
+        cypher: one::<String>(matches, args::CYPHER),
+        params: many(matches, args::PARAM).unwrap_or_default(),
+        format: one::<OutputFormat>(matches, args::FORMAT),
+        sort: one::<String>(matches, args::SORT),
+        ascending: flag(matches, args::ASCENDING),
+        seeds: many(matches, args::SEEDS),
+        hops: one::<usize>(matches, args::HOPS),
+        rel_types: many(matches, args::REL_TYPES),
+        min_weight: one::<f64>(matches, args::MIN_WEIGHT),
+        sources: many(matches, args::SOURCES),
+        min_beta: one::<f64>(matches, args::MIN_BETA),
+        address: one::<String>(matches, args::ADDRESS),
+        labels: many(matches, args::LABELS),
+        run_id: one::<String>(matches, args::RUN_ID),
+        sub_dir: one::<String>(matches, args::SUB_DIR),
+        batch_size: one::<usize>(matches, args::BATCH_SIZE),
+        yes: flag(matches, args::YES),
+        drop_schema: flag(matches, args::DROP_SCHEMA),
+        fix: flag(matches, args::FIX),
+        recursive: flag(matches, args::RECURSIVE),
+        long: flag(matches, args::LONG),
+        glob: one::<String>(matches, args::GLOB),
+        summary: flag(matches, args::SUMMARY),
+        no_cache: flag(matches, args::NO_CACHE),
+        refresh: flag(matches, args::REFRESH),
+        clear: flag(matches, args::CLEAR),
     }
 }
 
+/// The value of an argument, or nothing if the subcommand does not take it.
+fn one<T: Clone + Send + Sync + 'static>(matches: &ArgMatches, id: &str) -> Option<T> {
+    matches.try_get_one::<T>(id).ok().flatten().cloned()
+}
+
+fn many(matches: &ArgMatches, id: &str) -> Option<Vec<String>> {
+    matches.try_get_many::<String>(id).ok().flatten().map(|values| values.cloned().collect())
+}
+
+fn flag(matches: &ArgMatches, id: &str) -> bool {
+    one::<bool>(matches, id).unwrap_or(false)
+}
+
 fn new_options(action: Option<Action>, matches: &ArgMatches) -> CliOptions {
     CliOptions {
         action,
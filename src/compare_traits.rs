@@ -0,0 +1,353 @@
+use crate::config::CompareTraitsConfig;
+use crate::context::AppContext;
+use crate::error::Error;
+use crate::ids::TraitId;
+use crate::store;
+use crate::store::{GraphStore, MemberKind};
+use crate::table::Table;
+use log::{info, warn};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::path::Path;
+
+const N_TOP: usize = 20;
+
+struct Kind {
+    member_kind: MemberKind,
+    name: &'static str,
+    member: &'static str,
+    score: &'static str,
+    secondary: &'static str,
+    file: &'static str,
+}
+
+const GENES: Kind = Kind {
+    member_kind: MemberKind::Genes,
+    name: "genes",
+    member: "gene",
+    score: "prior",
+    secondary: "combined",
+    file: "compare_traits_genes.tsv",
+};
+const GENE_SETS: Kind = Kind {
+    member_kind: MemberKind::GeneSets,
+    name: "gene sets",
+    member: "gene_set",
+    score: "beta",
+    secondary: "beta_uncorrected",
+    file: "compare_traits_gene_sets.tsv",
+};
+const OVERLAP_FILE: &str = "compare_traits_overlap.tsv";
+
+/// The genes or gene sets associated with each trait, with their scores, plus the number of
+/// genes or gene sets associated with any trait, which is the universe for the hypergeometric
+/// test.
+struct Members {
+    kind: &'static Kind,
+    per_trait: Vec<HashMap<String, (f64, f64)>>,
+    n_universe: usize,
+}
+
+struct SharedMember {
+    member: String,
+    scores: Vec<(f64, f64)>,
+    total_score: f64,
+    total_secondary: f64,
+}
+
+struct PairOverlap {
+    trait1: TraitId,
+    trait2: TraitId,
+    n1: usize,
+    n2: usize,
+    n_shared: usize,
+    jaccard: f64,
+    p_value: f64,
+}
+
+fn members(store: &dyn GraphStore, kind: &'static Kind, traits: &[TraitId])
+    -> Result<Members, Error> {
+    let per_trait = traits.iter()
+        .map(|trait_id| store.trait_members(kind.member_kind, trait_id))
+        .collect::<Result<Vec<HashMap<String, (f64, f64)>>, Error>>()?;
+    let n_universe = store.n_members(kind.member_kind)?;
+    Ok(Members { kind, per_trait, n_universe })
+}
+
+fn graph_data(store: &dyn GraphStore, traits: &[TraitId]) -> Result<(Members, Members), Error> {
+    Ok((members(store, &GENES, traits)?, members(store, &GENE_SETS, traits)?))
+}
+
+fn finite_or_zero(value: f64) -> f64 {
+    if value.is_finite() { value } else { 0.0 }
+}
+
+impl Members {
+    fn shared(&self) -> Vec<SharedMember> {
+        let Some((first, others)) = self.per_trait.split_first() else {
+            return Vec::new();
+        };
+        let mut shared: Vec<SharedMember> = first.keys().filter_map(|member| {
+            let scores: Vec<(f64, f64)> =
+                std::iter::once(first).chain(others.iter())
+                    .map(|members| members.get(member).copied())
+                    .collect::<Option<Vec<(f64, f64)>>>()?;
+            let total_score = scores.iter().map(|(score, _)| finite_or_zero(*score)).sum();
+            let total_secondary =
+                scores.iter().map(|(_, secondary)| finite_or_zero(*secondary)).sum();
+            Some(SharedMember { member: member.clone(), scores, total_score, total_secondary })
+        }).collect();
+        shared.sort_by(|shared1, shared2| {
+            shared2.total_score.total_cmp(&shared1.total_score)
+                .then(shared2.total_secondary.total_cmp(&shared1.total_secondary))
+                .then_with(|| shared1.member.cmp(&shared2.member))
+        });
+        shared
+    }
+    fn pair_overlaps(&self, traits: &[TraitId]) -> Vec<PairOverlap> {
+        let ln_factorials = ln_factorials(self.n_universe);
+        let mut overlaps: Vec<PairOverlap> = Vec::new();
+        for (i1, members1) in self.per_trait.iter().enumerate() {
+            for (i2, members2) in self.per_trait.iter().enumerate().skip(i1 + 1) {
+                let n1 = members1.len();
+                let n2 = members2.len();
+                let n_shared =
+                    members1.keys().filter(|member| members2.contains_key(*member)).count();
+                let n_union = n1 + n2 - n_shared;
+                let jaccard =
+                    if n_union == 0 { 0.0 } else { (n_shared as f64) / (n_union as f64) };
+                let p_value = hypergeometric_sf(&ln_factorials, self.n_universe, n1, n2, n_shared);
+                overlaps.push(PairOverlap {
+                    trait1: traits[i1].clone(),
+                    trait2: traits[i2].clone(),
+                    n1,
+                    n2,
+                    n_shared,
+                    jaccard,
+                    p_value,
+                });
+            }
+        }
+        overlaps
+    }
+}
+
+fn ln_factorials(n: usize) -> Vec<f64> {
+    let mut ln_factorials = Vec::with_capacity(n + 1);
+    let mut sum = 0.0;
+    ln_factorials.push(sum);
+    for i in 1..=n {
+        sum += (i as f64).ln();
+        ln_factorials.push(sum);
+    }
+    ln_factorials
+}
+
+fn ln_choose(ln_factorials: &[f64], n: usize, k: usize) -> f64 {
+    ln_factorials[n] - ln_factorials[k] - ln_factorials[n - k]
+}
+
+/// Probability of drawing at least `k` successes in `n_draws` draws from a population of
+/// `n_population` containing `n_successes` successes.
+fn hypergeometric_sf(ln_factorials: &[f64], n_population: usize, n_successes: usize,
+                     n_draws: usize, k: usize) -> f64 {
+    if n_successes > n_population || n_draws > n_population {
+        return f64::NAN;
+    }
+    let ln_total = ln_choose(ln_factorials, n_population, n_draws);
+    let min = k.max((n_successes + n_draws).saturating_sub(n_population));
+    let max = n_successes.min(n_draws);
+    let p: f64 = (min..=max).map(|x| {
+        let ln_p = ln_choose(ln_factorials, n_successes, x)
+            + ln_choose(ln_factorials, n_population - n_successes, n_draws - x)
+            - ln_total;
+        ln_p.exp()
+    }).sum();
+    p.min(1.0)
+}
+
+fn print_overlaps(members: &Members, overlaps: &[PairOverlap]) {
+    println!("Overlap of {} ({} {} in total):", members.kind.name, members.n_universe,
+             members.kind.name);
+    let headers: Vec<String> =
+        ["trait1", "trait2", "n1", "n2", "shared", "jaccard", "p_value"]
+            .iter().map(|header| header.to_string()).collect();
+    let mut table = Table::new(headers);
+    for overlap in overlaps {
+        table.add_row(overlap_record(overlap));
+    }
+    print!("{table}");
+}
+
+fn overlap_record(overlap: &PairOverlap) -> Vec<String> {
+    vec![
+        overlap.trait1.to_string(),
+        overlap.trait2.to_string(),
+        overlap.n1.to_string(),
+        overlap.n2.to_string(),
+        overlap.n_shared.to_string(),
+        format!("{:.4}", overlap.jaccard),
+        format!("{:.3e}", overlap.p_value),
+    ]
+}
+
+fn shared_headers(kind: &Kind, traits: &[TraitId]) -> Vec<String> {
+    let mut headers = vec![kind.member.to_string()];
+    for trait_id in traits {
+        headers.push(format!("{}_{}", kind.score, trait_id));
+        headers.push(format!("{}_{}", kind.secondary, trait_id));
+    }
+    headers.push(format!("{}_sum", kind.score));
+    headers.push(format!("{}_sum", kind.secondary));
+    headers
+}
+
+fn shared_record(shared: &SharedMember) -> Vec<String> {
+    let mut record = vec![shared.member.clone()];
+    for (score, secondary) in &shared.scores {
+        record.push(score.to_string());
+        record.push(secondary.to_string());
+    }
+    record.push(shared.total_score.to_string());
+    record.push(shared.total_secondary.to_string());
+    record
+}
+
+fn print_shared(kind: &Kind, traits: &[TraitId], shared: &[SharedMember]) {
+    println!("{} {} shared by all traits, top {}:", shared.len(), kind.name,
+             N_TOP.min(shared.len()));
+    let mut table = Table::new(shared_headers(kind, traits));
+    for shared in shared.iter().take(N_TOP) {
+        table.add_row(shared_record(shared));
+    }
+    print!("{table}");
+}
+
+fn write_shared(kind: &Kind, traits: &[TraitId], shared: &[SharedMember], out_dir: &Path)
+    -> Result<(), Error> {
+    let out_file = out_dir.join(kind.file);
+    info!("Writing {} shared {} to {}", shared.len(), kind.name, out_file.display());
+    let mut writer = csv::WriterBuilder::new().delimiter(b'\t').from_path(out_file)?;
+    writer.write_record(shared_headers(kind, traits))?;
+    for shared in shared {
+        writer.write_record(shared_record(shared))?;
+    }
+    Ok(())
+}
+
+fn write_overlaps(overlaps: &[(&Members, Vec<PairOverlap>)], out_dir: &Path)
+    -> Result<(), Error> {
+    let out_file = out_dir.join(OVERLAP_FILE);
+    info!("Writing overlap statistics to {}", out_file.display());
+    let mut writer = csv::WriterBuilder::new().delimiter(b'\t').from_path(out_file)?;
+    writer.write_record([
+        "kind", "trait1", "trait2", "n1", "n2", "shared", "jaccard", "p_value", "n_universe"
+    ])?;
+    for (members, overlaps) in overlaps {
+        for overlap in overlaps {
+            let mut record = vec![members.kind.member.to_string()];
+            record.extend(overlap_record(overlap));
+            record.push(members.n_universe.to_string());
+            writer.write_record(record)?;
+        }
+    }
+    Ok(())
+}
+
+fn members_json(members: &Members, traits: &[TraitId], n_top: usize) -> Value {
+    let kind = members.kind;
+    let overlaps: Vec<Value> = members.pair_overlaps(traits).iter().map(|overlap| {
+        json!({
+            "trait1": overlap.trait1.as_str(),
+            "trait2": overlap.trait2.as_str(),
+            "n1": overlap.n1,
+            "n2": overlap.n2,
+            "shared": overlap.n_shared,
+            "jaccard": overlap.jaccard,
+            "p_value": overlap.p_value,
+        })
+    }).collect();
+    let shared = members.shared();
+    let top: Vec<Value> = shared.iter().take(n_top).map(|shared| {
+        let mut object = serde_json::Map::new();
+        object.insert(kind.member.to_string(), json!(shared.member));
+        for (trait_id, (score, secondary)) in traits.iter().zip(&shared.scores) {
+            object.insert(format!("{}_{}", kind.score, trait_id), json!(score));
+            object.insert(format!("{}_{}", kind.secondary, trait_id), json!(secondary));
+        }
+        object.insert(format!("{}_sum", kind.score), json!(shared.total_score));
+        object.insert(format!("{}_sum", kind.secondary), json!(shared.total_secondary));
+        Value::Object(object)
+    }).collect();
+    json!({
+        "n_universe": members.n_universe,
+        "overlaps": overlaps,
+        "n_shared": shared.len(),
+        "shared": top,
+    })
+}
+
+/// The comparison of traits as JSON, with the top shared genes and gene sets.
+pub(crate) fn comparison_json(store: &dyn GraphStore, traits: &[TraitId], n_top: usize)
+    -> Result<Value, Error> {
+    let (genes, gene_sets) = graph_data(store, traits)?;
+    Ok(json!({
+        "traits": traits.iter().map(|trait_id| trait_id.as_str()).collect::<Vec<&str>>(),
+        "genes": members_json(&genes, traits, n_top),
+        "gene_sets": members_json(&gene_sets, traits, n_top),
+    }))
+}
+
+pub(crate) fn compare_traits(context: &AppContext, config: &CompareTraitsConfig)
+    -> Result<(), Error> {
+    let traits = &config.traits;
+    let store = store::open(context, &config.store)?;
+    let (genes, gene_sets) = graph_data(store.as_ref(), traits)?;
+    for (trait_id, members) in traits.iter().zip(genes.per_trait.iter()) {
+        if members.is_empty() {
+            warn!("Trait {trait_id} has no genes.");
+        }
+    }
+    let mut overlaps: Vec<(&Members, Vec<PairOverlap>)> = Vec::new();
+    let mut all_shared: Vec<(&Members, Vec<SharedMember>)> = Vec::new();
+    for members in [&genes, &gene_sets] {
+        let pair_overlaps = members.pair_overlaps(traits);
+        print_overlaps(members, &pair_overlaps);
+        overlaps.push((members, pair_overlaps));
+        let shared = members.shared();
+        print_shared(members.kind, traits, &shared);
+        all_shared.push((members, shared));
+    }
+    match &config.out {
+        Some(out) => {
+            let out_dir = Path::new(out);
+            std::fs::create_dir_all(out_dir)?;
+            write_overlaps(&overlaps, out_dir)?;
+            for (members, shared) in &all_shared {
+                write_shared(members.kind, traits, shared, out_dir)?;
+            }
+        }
+        None => info!("No output directory specified, not writing comparison files."),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{hypergeometric_sf, ln_factorials};
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!((actual - expected).abs() <= 1e-9 * expected.max(1e-12),
+                "{actual} is not {expected}");
+    }
+
+    #[test]
+    fn hypergeometric_tail() {
+        let ln_factorials = ln_factorials(1000);
+        assert_close(hypergeometric_sf(&ln_factorials, 50, 5, 10, 4), 0.004083520549755517);
+        assert_close(hypergeometric_sf(&ln_factorials, 20, 7, 12, 6), 0.10557275541795666);
+        assert_close(hypergeometric_sf(&ln_factorials, 1000, 100, 100, 20), 0.001069633001485991);
+        assert_close(hypergeometric_sf(&ln_factorials, 10, 3, 4, 0), 1.0);
+        assert_eq!(hypergeometric_sf(&ln_factorials, 20, 7, 12, 9), 0.0);
+    }
+}
@@ -1,10 +1,36 @@
 use std::fmt::Display;
 use crate::error::Error;
 use serde::Deserialize;
-use std::path::PathBuf;
+use std::io::IsTerminal;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
 use crate::cli::CliOptions;
+use crate::ids::TraitId;
+use crate::output::OutputFormat;
+use crate::pigean::dedup::DuplicatePolicy;
+use crate::password::{PasswordOptions, PasswordSource, PASSWORD_ENV_VAR};
+use log::{info, warn};
 
 
+const DEFAULT_EMBED_DIMS: usize = 1024;
+const DEFAULT_EMBED_ITERATIONS: usize = 10;
+const DEFAULT_LIMIT: usize = 10;
+const DEFAULT_HOPS: usize = 1;
+const DEFAULT_SERVE_ADDRESS: &str = "127.0.0.1:8080";
+const DEFAULT_SERVE_WORKERS: usize = 4;
+const DEFAULT_CACHE_TTL_SECS: u64 = 300;
+const DEFAULT_CACHE_ENTRIES: usize = 1000;
+const SHELL_HISTORY_FILE: &str = "shell_history";
+const S3_CACHE_DIR: &str = "cache";
+const DEFAULT_S3_CACHE_MAX_SIZE_MB: u64 = 20480;
+const DEFAULT_S3_CACHE_LISTING_TTL_SECS: u64 = 3600;
+const DEFAULT_META_FACTOR_SIMILARITY: f64 = 0.5;
+const DEFAULT_WIPE_BATCH_SIZE: usize = 10000;
+const DEFAULT_NEO4J_CONNECT_TIMEOUT_SECS: u64 = 30;
+const DEFAULT_NEO4J_MAX_RETRIES: u32 = 3;
+const DEFAULT_NEO4J_RETRY_INITIAL_DELAY_MS: u64 = 200;
+const DEFAULT_NEO4J_RETRY_MAX_DELAY_MS: u64 = 5000;
+
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Ord, PartialOrd, Deserialize)]
 #[serde(try_from = "&str")]
 pub enum Action {
@@ -18,9 +44,29 @@ pub enum Action {
     Bulk,
     Factors,
     TraitGeneSets,
+    Stats,
+    FactorOverlap,
+    CompareTraits,
+    Embed,
+    VectorIndex,
+    Similar,
+    MetaFactors,
+    Query,
+    GenesForTrait,
+    GeneSetsForTrait,
+    FactorsForTrait,
+    FactorsForGene,
+    FactorsForGeneSet,
+    ExportSubgraph,
+    ExportTerms,
+    Serve,
+    Shell,
+    Status,
+    Check,
+    Cache,
 }
 
-pub const ACTIONS: [Action; 10] = [
+pub const ACTIONS: [Action; 30] = [
     Action::Hello,
     Action::Survey,
     Action::Ping,
@@ -31,8 +77,40 @@ pub const ACTIONS: [Action; 10] = [
     Action::Bulk,
     Action::Factors,
     Action::TraitGeneSets,
+    Action::Stats,
+    Action::FactorOverlap,
+    Action::CompareTraits,
+    Action::Embed,
+    Action::VectorIndex,
+    Action::Similar,
+    Action::MetaFactors,
+    Action::Query,
+    Action::GenesForTrait,
+    Action::GeneSetsForTrait,
+    Action::FactorsForTrait,
+    Action::FactorsForGene,
+    Action::FactorsForGeneSet,
+    Action::ExportSubgraph,
+    Action::ExportTerms,
+    Action::Serve,
+    Action::Shell,
+    Action::Status,
+    Action::Check,
+    Action::Cache,
 ];
 
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub enum Backend {
+    Neo4j,
+    Memory,
+}
+
+mod backend {
+    pub(crate) const NEO4J: &str = "neo4j";
+    pub(crate) const MEMORY: &str = "memory";
+}
+
 pub(crate) mod action {
     pub(crate) const HELLO: &str = "hello";
     pub(crate) const SURVEY: &str = "survey";
@@ -44,6 +122,26 @@ pub(crate) mod action {
     pub(crate) const BULK: &str = "bulk";
     pub(crate) const FACTORS: &str = "factors";
     pub(crate) const PGS: &str = "pgs";
+    pub(crate) const STATS: &str = "stats";
+    pub(crate) const FACTOR_OVERLAP: &str = "factor-overlap";
+    pub(crate) const COMPARE_TRAITS: &str = "compare-traits";
+    pub(crate) const EMBED: &str = "embed";
+    pub(crate) const VECTOR_INDEX: &str = "vector-index";
+    pub(crate) const SIMILAR: &str = "similar";
+    pub(crate) const META_FACTORS: &str = "meta-factors";
+    pub(crate) const QUERY: &str = "query";
+    pub(crate) const GENES_FOR_TRAIT: &str = "genes-for-trait";
+    pub(crate) const GENE_SETS_FOR_TRAIT: &str = "gene-sets-for-trait";
+    pub(crate) const FACTORS_FOR_TRAIT: &str = "factors-for-trait";
+    pub(crate) const FACTORS_FOR_GENE: &str = "factors-for-gene";
+    pub(crate) const FACTORS_FOR_GENE_SET: &str = "factors-for-gene-set";
+    pub(crate) const EXPORT_SUBGRAPH: &str = "export-subgraph";
+    pub(crate) const EXPORT_TERMS: &str = "export-terms";
+    pub(crate) const SERVE: &str = "serve";
+    pub(crate) const SHELL: &str = "shell";
+    pub(crate) const STATUS: &str = "status";
+    pub(crate) const CHECK: &str = "check";
+    pub(crate) const CACHE: &str = "cache";
 }
 
 mod about {
@@ -51,46 +149,298 @@ mod about {
     pub(crate) const SURVEY: &str = "Surveys the data.";
     pub(crate) const PING: &str = "Pings the Neo4j server.";
     pub(crate) const UPLOAD: &str = "Uploads data to the Neo4j server.";
-    pub(crate) const WIPE: &str = "Deletes all data on the Neo4j server.";
+    pub(crate) const WIPE: &str =
+        "Deletes all data on the Neo4j server, or the nodes in the given scopes.";
     pub(crate) const CAT: &str = "Prints the content of the input file.";
-    pub(crate) const LS: &str = "Lists the content of a directory.";
+    pub(crate) const LS: &str =
+        "Lists the content of a local or S3 directory, optionally with sizes or totals.";
     pub(crate) const BULK: &str = "Creates pheno/genes/gene sets bulk files for PIGEAN.";
     pub(crate) const FACTORS: &str = "Creates factors/genes/gene sets bulk files for PIGEAN.";
     pub(crate) const PGS: &str = "Creates trait-gene sets bulk files for PIGEAN.";
+    pub(crate) const STATS: &str = "Reports statistics of bulk CSV files.";
+    pub(crate) const FACTOR_OVERLAP: &str = "Computes overlap between factors of different traits.";
+    pub(crate) const COMPARE_TRAITS: &str = "Compares genes and gene sets of two or more traits.";
+    pub(crate) const EMBED: &str = "Computes node embeddings from bulk CSV files.";
+    pub(crate) const VECTOR_INDEX: &str = "Creates vector indexes on node embeddings.";
+    pub(crate) const SIMILAR: &str = "Finds the nodes with the most similar embeddings.";
+    pub(crate) const META_FACTORS: &str = "Clusters factors of all traits into meta-factors.";
+    pub(crate) const QUERY: &str = "Runs Cypher statements and prints the results.";
+    pub(crate) const GENES_FOR_TRAIT: &str = "Lists the genes associated with a trait.";
+    pub(crate) const GENE_SETS_FOR_TRAIT: &str = "Lists the gene sets associated with a trait.";
+    pub(crate) const FACTORS_FOR_TRAIT: &str = "Lists the factors of a trait.";
+    pub(crate) const FACTORS_FOR_GENE: &str = "Lists the factors a gene loads on.";
+    pub(crate) const FACTORS_FOR_GENE_SET: &str = "Lists the factors a gene set loads on.";
+    pub(crate) const EXPORT_SUBGRAPH: &str =
+        "Exports the neighborhood of seed nodes as GraphML, GEXF or Cytoscape.js JSON.";
+    pub(crate) const EXPORT_TERMS: &str = "Exports trait gene set terms as TSV for text mining.";
+    pub(crate) const SERVE: &str = "Serves read-only JSON endpoints over the knowledge graph.";
+    pub(crate) const SHELL: &str = "Opens an interactive Cypher shell.";
+    pub(crate) const STATUS: &str =
+        "Reports server, counts, schema and provenance of the database.";
+    pub(crate) const CHECK: &str = "Checks the integrity of the graph and optionally fixes it.";
+    pub(crate) const CACHE: &str = "Shows what is in the S3 cache and optionally clears it.";
 }
 
 pub struct Neo4jConfig {
     pub(crate) uri: String,
     pub(crate) user: String,
-    pub(crate) password: String
+    pub(crate) password: String,
+    pub(crate) password_source: PasswordSource,
+    pub(crate) database: Option<String>,
+    pub(crate) max_connections: Option<usize>,
+    pub(crate) fetch_size: Option<usize>,
+    pub(crate) ca_file: Option<String>,
+    pub(crate) connect_timeout: Duration,
+    pub(crate) query_timeout: Option<Duration>,
+    pub(crate) retry: RetryConfig,
+}
+
+/// How often and how patiently to retry reads failing with transient errors.
+#[derive(Copy, Clone)]
+pub struct RetryConfig {
+    pub(crate) max_retries: u32,
+    pub(crate) initial_delay: Duration,
+    pub(crate) max_delay: Duration,
 }
+
+/// Where and how to reach S3, for all actions reading from S3.
+#[derive(Clone, Default)]
+pub struct S3Config {
+    pub(crate) endpoint_url: Option<String>,
+    pub(crate) region: Option<String>,
+    pub(crate) profile: Option<String>,
+    pub(crate) force_path_style: bool,
+    pub(crate) anonymous: bool,
+    pub(crate) request_payer: bool,
+}
+
+/// Whether S3 reads go through the cache, and whether what is cached may be used.
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub enum CacheMode {
+    Off,
+    Use,
+    Refresh,
+}
+
+/// The on-disk cache of S3 objects and listings, without a directory if there is no app
+/// directory to put it in.
+#[derive(Clone)]
+pub struct S3CacheConfig {
+    pub(crate) dir: Option<PathBuf>,
+    pub(crate) mode: CacheMode,
+    pub(crate) max_bytes: u64,
+    pub(crate) listing_ttl: Duration,
+}
+
 pub enum ActionConfig {
-    Hello(LocalConfig),
+    Hello(HelloConfig),
     Survey(LocalConfig),
     Ping(ClientConfig),
-    Upload(ClientConfig),
-    Wipe(ClientConfig),
+    Upload(UploadConfig),
+    Wipe(WipeConfig),
     Cat(String),
-    Ls(String),
+    Ls(LsConfig),
     Bulk(PigeanConfig),
     Factors(PigeanConfig),
     TraitGeneSets(PigeanConfig),
+    Stats(StatsConfig),
+    FactorOverlap(FactorOverlapConfig),
+    CompareTraits(CompareTraitsConfig),
+    Embed(EmbedConfig),
+    VectorIndex(VectorIndexConfig),
+    Similar(SimilarConfig),
+    MetaFactors(MetaFactorsConfig),
+    Query(QueryConfig),
+    GenesForTrait(LookupConfig),
+    GeneSetsForTrait(LookupConfig),
+    FactorsForTrait(LookupConfig),
+    FactorsForGene(LookupConfig),
+    FactorsForGeneSet(LookupConfig),
+    ExportSubgraph(ExportSubgraphConfig),
+    ExportTerms(ExportTermsConfig),
+    Serve(ServeConfig),
+    Shell(ShellConfig),
+    Status(StatusConfig),
+    Check(CheckConfig),
+    Cache(CacheConfig),
 }
 pub struct LocalConfig {
     pub(crate) data_dir: PathBuf,
 }
 
+pub struct HelloConfig {
+    pub(crate) local_config: LocalConfig,
+    pub(crate) neo4j_uri: Option<String>,
+    pub(crate) neo4j_user: Option<String>,
+    pub(crate) password_source: Option<PasswordSource>,
+}
+
+pub struct StatsConfig {
+    pub(crate) dir: String,
+    pub(crate) out: String,
+}
+
+pub struct FactorOverlapConfig {
+    pub(crate) bulk_dir: String,
+    pub(crate) traits: Vec<TraitId>,
+    pub(crate) out: String,
+    pub(crate) min_similarity: Option<f64>,
+}
+
+pub enum StoreConfig {
+    Neo4j(Box<Neo4jConfig>),
+    Memory { bulk_dir: String },
+}
+
+pub struct CompareTraitsConfig {
+    pub(crate) traits: Vec<TraitId>,
+    pub(crate) store: StoreConfig,
+    pub(crate) out: Option<String>,
+}
+
+pub struct EmbedConfig {
+    pub(crate) bulk_dir: String,
+    pub(crate) out: String,
+    pub(crate) dims: usize,
+    pub(crate) iterations: usize,
+    pub(crate) seed: Option<u64>,
+    pub(crate) neo4j: Option<Neo4jConfig>,
+}
+
+pub struct VectorIndexConfig {
+    pub(crate) neo4j: Neo4jConfig,
+    pub(crate) dims: usize,
+}
+
+pub struct SimilarConfig {
+    pub(crate) neo4j: Neo4jConfig,
+    pub(crate) id: String,
+    pub(crate) limit: usize,
+}
+
+pub struct MetaFactorsConfig {
+    pub(crate) bulk_dir: String,
+    pub(crate) out: String,
+    pub(crate) min_similarity: f64,
+}
+
+pub struct QueryConfig {
+    pub(crate) neo4j: Neo4jConfig,
+    pub(crate) cypher: Option<String>,
+    pub(crate) file: Option<String>,
+    pub(crate) params: Vec<(String, String)>,
+    pub(crate) format: OutputFormat,
+    pub(crate) out: Option<String>,
+}
+
+pub struct LookupConfig {
+    pub(crate) store: StoreConfig,
+    pub(crate) id: String,
+    pub(crate) limit: usize,
+    pub(crate) sort: Option<String>,
+    pub(crate) ascending: bool,
+    pub(crate) format: OutputFormat,
+    pub(crate) out: Option<String>,
+}
+
+pub struct ExportSubgraphConfig {
+    pub(crate) store: StoreConfig,
+    pub(crate) seeds: Vec<String>,
+    pub(crate) hops: usize,
+    pub(crate) rel_types: Option<Vec<String>>,
+    pub(crate) min_weight: Option<f64>,
+    pub(crate) out: String,
+}
+
+pub struct ExportTermsConfig {
+    pub(crate) store: StoreConfig,
+    pub(crate) traits: Vec<TraitId>,
+    pub(crate) sources: Option<Vec<String>>,
+    pub(crate) min_beta: Option<f64>,
+    pub(crate) out: Option<String>,
+}
+
+pub struct ServeConfig {
+    pub(crate) store: StoreConfig,
+    pub(crate) address: String,
+    pub(crate) workers: usize,
+    pub(crate) cache_ttl: Duration,
+    pub(crate) cache_entries: usize,
+}
+
+pub struct ShellConfig {
+    pub(crate) neo4j: Neo4jConfig,
+    pub(crate) history_file: Option<PathBuf>,
+}
+
+/// Which nodes to wipe. All given scopes have to match; without scopes, everything is wiped.
+pub struct WipeScope {
+    pub(crate) labels: Option<Vec<String>>,
+    pub(crate) traits: Vec<TraitId>,
+    pub(crate) run_id: Option<String>,
+    pub(crate) sub_dir: Option<String>,
+}
+
+pub struct WipeConfig {
+    pub(crate) neo4j: Neo4jConfig,
+    pub(crate) scope: WipeScope,
+    pub(crate) batch_size: usize,
+    pub(crate) yes: bool,
+    pub(crate) drop_schema: bool,
+}
+
+pub struct StatusConfig {
+    pub(crate) neo4j: Neo4jConfig,
+    pub(crate) format: OutputFormat,
+    pub(crate) out: Option<String>,
+}
+
+pub struct LsConfig {
+    pub(crate) dir: String,
+    pub(crate) recursive: bool,
+    pub(crate) long: bool,
+    pub(crate) glob: Option<String>,
+    pub(crate) summary: bool,
+    pub(crate) format: Option<OutputFormat>,
+    pub(crate) out: Option<String>,
+}
+
+pub struct CacheConfig {
+    pub(crate) clear: bool,
+    pub(crate) long: bool,
+    pub(crate) format: OutputFormat,
+    pub(crate) out: Option<String>,
+}
+
+pub struct CheckConfig {
+    pub(crate) neo4j: Neo4jConfig,
+    pub(crate) fix: bool,
+    pub(crate) yes: bool,
+    pub(crate) limit: usize,
+    pub(crate) format: OutputFormat,
+    pub(crate) out: Option<String>,
+}
+
 pub struct ClientConfig {
     pub(crate) local_config: LocalConfig,
     pub(crate) neo4j: Neo4jConfig,
 }
 
+pub struct UploadConfig {
+    pub(crate) client_config: ClientConfig,
+    pub(crate) hgnc_file: Option<String>,
+    pub(crate) run_id: String,
+    pub(crate) sub_dir: String,
+}
+
 pub struct PigeanConfig {
     pub(crate) data_dir: String,
     pub(crate) sub_dir: String,
     pub(crate) factors_dir: String,
     pub(crate) factors_sub_dir: String,
     pub(crate) out: String,
+    pub(crate) hgnc_file: Option<String>,
+    pub(crate) duplicates: DuplicatePolicy,
 }
 
 #[derive(Deserialize)]
@@ -98,15 +448,96 @@ pub struct Neo4jConfigBuilder {
     uri: Option<String>,
     user: Option<String>,
     password: Option<String>,
+    password_file: Option<String>,
+    password_command: Option<String>,
+    #[serde(skip)]
+    password_on_command_line: bool,
+    database: Option<String>,
+    max_connections: Option<usize>,
+    fetch_size: Option<usize>,
+    tls: Option<bool>,
+    trust_all_certificates: Option<bool>,
+    ca_file: Option<String>,
+    connect_timeout_secs: Option<u64>,
+    query_timeout_secs: Option<u64>,
+    max_retries: Option<u32>,
+    retry_initial_delay_ms: Option<u64>,
+    retry_max_delay_ms: Option<u64>,
 }
 #[derive(Deserialize)]
 pub struct ConfigBuilder {
     action: Option<Action>,
     data_dir: Option<PathBuf>,
     neo4j: Option<Neo4jConfigBuilder>,
+    s3: Option<S3ConfigBuilder>,
+    cache: Option<S3CacheConfigBuilder>,
     file: Option<String>,
     out: Option<String>,
-    pigean: Option<PigeanConfigBuilder>
+    hgnc_file: Option<String>,
+    bulk_dir: Option<String>,
+    traits: Option<Vec<String>>,
+    min_similarity: Option<f64>,
+    backend: Option<Backend>,
+    id: Option<String>,
+    limit: Option<usize>,
+    cypher: Option<String>,
+    params: Option<Vec<String>>,
+    format: Option<OutputFormat>,
+    sort: Option<String>,
+    ascending: Option<bool>,
+    seeds: Option<Vec<String>>,
+    hops: Option<usize>,
+    rel_types: Option<Vec<String>>,
+    min_weight: Option<f64>,
+    sources: Option<Vec<String>>,
+    min_beta: Option<f64>,
+    // The wipe scope, the load run and the confirmation only come from the command line.
+    #[serde(skip)]
+    wipe_traits: Option<Vec<String>>,
+    #[serde(skip)]
+    labels: Option<Vec<String>>,
+    #[serde(skip)]
+    run_id: Option<String>,
+    #[serde(skip)]
+    sub_dir: Option<String>,
+    batch_size: Option<usize>,
+    #[serde(skip)]
+    yes: Option<bool>,
+    #[serde(skip)]
+    drop_schema: Option<bool>,
+    #[serde(skip)]
+    fix: Option<bool>,
+    recursive: Option<bool>,
+    long: Option<bool>,
+    glob: Option<String>,
+    summary: Option<bool>,
+    #[serde(skip)]
+    no_cache: Option<bool>,
+    #[serde(skip)]
+    refresh: Option<bool>,
+    #[serde(skip)]
+    clear: Option<bool>,
+    pigean: Option<PigeanConfigBuilder>,
+    embed: Option<EmbedConfigBuilder>,
+    serve: Option<ServeConfigBuilder>,
+    #[serde(skip)]
+    app_dir: Option<PathBuf>,
+}
+#[derive(Clone, Deserialize)]
+pub struct S3ConfigBuilder {
+    endpoint_url: Option<String>,
+    region: Option<String>,
+    profile: Option<String>,
+    force_path_style: Option<bool>,
+    anonymous: Option<bool>,
+    request_payer: Option<bool>,
+}
+#[derive(Clone, Deserialize)]
+pub struct S3CacheConfigBuilder {
+    enabled: Option<bool>,
+    dir: Option<PathBuf>,
+    max_size_mb: Option<u64>,
+    listing_ttl_secs: Option<u64>,
 }
 #[derive(Deserialize)]
 pub struct PigeanConfigBuilder {
@@ -114,24 +545,94 @@ pub struct PigeanConfigBuilder {
     sub_dir: Option<String>,
     factors_dir: Option<String>,
     factors_sub_dir: Option<String>,
+    duplicates: Option<DuplicatePolicy>,
+}
+#[derive(Deserialize)]
+pub struct EmbedConfigBuilder {
+    dims: Option<usize>,
+    iterations: Option<usize>,
+    seed: Option<u64>,
+    write_back: Option<bool>,
+}
+#[derive(Deserialize)]
+pub struct ServeConfigBuilder {
+    address: Option<String>,
+    workers: Option<usize>,
+    cache_ttl_secs: Option<u64>,
+    cache_entries: Option<usize>,
 }
 
 impl Neo4jConfigBuilder {
     pub fn new() -> Neo4jConfigBuilder {
-        let uri: Option<String> = None;
-        let user: Option<String> = None;
-        let password: Option<String> = None;
         Neo4jConfigBuilder {
-            uri, user, password
+            uri: None,
+            user: None,
+            password: None,
+            password_file: None,
+            password_command: None,
+            password_on_command_line: false,
+            database: None,
+            max_connections: None,
+            fetch_size: None,
+            tls: None,
+            trust_all_certificates: None,
+            ca_file: None,
+            connect_timeout_secs: None,
+            query_timeout_secs: None,
+            max_retries: None,
+            retry_initial_delay_ms: None,
+            retry_max_delay_ms: None,
+        }
+    }
+    /// The password options without reading a file or running a command.
+    fn password_options(&self) -> PasswordOptions {
+        PasswordOptions {
+            password: self.password.clone(),
+            on_command_line: self.password_on_command_line,
+            env_password: std::env::var_os(PASSWORD_ENV_VAR),
+            password_file: self.password_file.clone(),
+            password_command: self.password_command.clone(),
+            interactive: std::io::stdin().is_terminal(),
         }
     }
     pub fn build(self) -> Result<Neo4jConfig, Error> {
-        let uri = self.uri.ok_or(Error::from("No URI (neo4j/uri) )specified."))?;
-        let user = self.user.ok_or(Error::from("No user (neo4j/user) specified."))?;
-        let password =
-            self.password.ok_or(Error::from("No password (neo4j/password) specified."))?;
+        let password_options = self.password_options();
+        let Neo4jConfigBuilder {
+            uri, user, database, max_connections, fetch_size, tls, trust_all_certificates, ca_file,
+            connect_timeout_secs, query_timeout_secs, max_retries, retry_initial_delay_ms,
+            retry_max_delay_ms, ..
+        } = self;
+        let uri = uri.ok_or(Error::from("No URI (neo4j/uri) )specified."))?;
+        let uri = tls_uri(&uri, tls, trust_all_certificates, ca_file.is_some())?;
+        let user = user.ok_or(Error::from("No user (neo4j/user) specified."))?;
+        let (password, password_source) = password_options.resolve(&user)?;
+        info!("Using Neo4j password from {password_source}.");
+        if let PasswordSource::CommandLine = password_source {
+            warn!("A password given on the command line may end up in the shell history. \
+                Consider {PASSWORD_ENV_VAR}, neo4j/password_file or neo4j/password_command.");
+        }
+        if max_connections == Some(0) {
+            Err(Error::from("Need at least one connection (neo4j/max_connections)."))?
+        }
+        if fetch_size == Some(0) {
+            Err(Error::from("Fetch size (neo4j/fetch_size) needs to be positive."))?
+        }
+        let connect_timeout = Duration::from_secs(
+            connect_timeout_secs.unwrap_or(DEFAULT_NEO4J_CONNECT_TIMEOUT_SECS)
+        );
+        let query_timeout = query_timeout_secs.map(Duration::from_secs);
+        let retry = RetryConfig {
+            max_retries: max_retries.unwrap_or(DEFAULT_NEO4J_MAX_RETRIES),
+            initial_delay: Duration::from_millis(
+                retry_initial_delay_ms.unwrap_or(DEFAULT_NEO4J_RETRY_INITIAL_DELAY_MS)
+            ),
+            max_delay: Duration::from_millis(
+                retry_max_delay_ms.unwrap_or(DEFAULT_NEO4J_RETRY_MAX_DELAY_MS)
+            ),
+        };
         Ok(Neo4jConfig {
-            uri, user, password
+            uri, user, password, password_source, database, max_connections, fetch_size, ca_file,
+            connect_timeout, query_timeout, retry
         })
     }
 }
@@ -141,11 +642,12 @@ impl PigeanConfigBuilder {
         let sub_dir: Option<String> = None;
         let factors_dir: Option<String> = None;
         let factors_sub_dir: Option<String> = None;
-        PigeanConfigBuilder { data_dir, sub_dir, factors_dir, factors_sub_dir }
+        let duplicates: Option<DuplicatePolicy> = None;
+        PigeanConfigBuilder { data_dir, sub_dir, factors_dir, factors_sub_dir, duplicates }
     }
-    pub fn build(self, out: String) -> Result<PigeanConfig, Error> {
+    pub fn build(self, out: String, hgnc_file: Option<String>) -> Result<PigeanConfig, Error> {
         let PigeanConfigBuilder {
-            data_dir, sub_dir, factors_dir, factors_sub_dir
+            data_dir, sub_dir, factors_dir, factors_sub_dir, duplicates
         } = self;
         let data_dir =
             data_dir.ok_or(Error::from("No PIGEAN data directory specified."))?;
@@ -155,24 +657,195 @@ impl PigeanConfigBuilder {
             factors_dir.ok_or(Error::from("No PIGEAN factors directory specified."))?;
         let factors_sub_dir =
             factors_sub_dir.ok_or(Error::from("No PIGEAN factors sub directory specified."))?;
+        let duplicates = duplicates.unwrap_or(DuplicatePolicy::Max);
         Ok(PigeanConfig {
-            data_dir, sub_dir, factors_dir, factors_sub_dir, out
+            data_dir, sub_dir, factors_dir, factors_sub_dir, out, hgnc_file, duplicates
         })
     }
 }
+impl EmbedConfigBuilder {
+    pub fn new() -> EmbedConfigBuilder {
+        let dims: Option<usize> = None;
+        let iterations: Option<usize> = None;
+        let seed: Option<u64> = None;
+        let write_back: Option<bool> = None;
+        EmbedConfigBuilder { dims, iterations, seed, write_back }
+    }
+    pub fn dims(&self) -> usize {
+        self.dims.unwrap_or(DEFAULT_EMBED_DIMS)
+    }
+    pub fn build(self, bulk_dir: String, out: String, neo4j: Option<Neo4jConfigBuilder>)
+        -> Result<EmbedConfig, Error> {
+        let EmbedConfigBuilder { dims, iterations, seed, write_back } = self;
+        let dims = dims.unwrap_or(DEFAULT_EMBED_DIMS);
+        if dims < 2 {
+            Err(Error::from("Need at least two embedding dimensions (embed/dims)."))?
+        }
+        let iterations = iterations.unwrap_or(DEFAULT_EMBED_ITERATIONS);
+        let neo4j =
+            if write_back.unwrap_or(false) { Some(neo4j_config(neo4j)?) } else { None };
+        Ok(EmbedConfig { bulk_dir, out, dims, iterations, seed, neo4j })
+    }
+}
+impl WipeScope {
+    /// Whether the scope selects only some of the nodes with a label.
+    pub(crate) fn is_partial(&self) -> bool {
+        !self.traits.is_empty() || self.run_id.is_some() || self.sub_dir.is_some()
+    }
+}
+
+impl S3ConfigBuilder {
+    pub fn new() -> S3ConfigBuilder {
+        S3ConfigBuilder {
+            endpoint_url: None,
+            region: None,
+            profile: None,
+            force_path_style: None,
+            anonymous: None,
+            request_payer: None,
+        }
+    }
+    pub fn build(self) -> Result<S3Config, Error> {
+        let S3ConfigBuilder {
+            endpoint_url, region, profile, force_path_style, anonymous, request_payer
+        } = self;
+        if let Some(endpoint_url) = &endpoint_url
+            && !endpoint_url.starts_with("http://") && !endpoint_url.starts_with("https://") {
+            Err(Error::from(format!(
+                "Endpoint URL (s3/endpoint_url) needs to start with http:// or https://, but is \
+                '{endpoint_url}'."
+            )))?
+        }
+        let anonymous = anonymous.unwrap_or(false);
+        if anonymous && profile.is_some() {
+            Err(Error::from("Cannot use a profile (s3/profile) for anonymous access."))?
+        }
+        let force_path_style = force_path_style.unwrap_or(false);
+        let request_payer = request_payer.unwrap_or(false);
+        Ok(S3Config { endpoint_url, region, profile, force_path_style, anonymous, request_payer })
+    }
+}
+impl S3CacheConfigBuilder {
+    pub fn new() -> S3CacheConfigBuilder {
+        S3CacheConfigBuilder { enabled: None, dir: None, max_size_mb: None, listing_ttl_secs: None }
+    }
+    pub fn build(self, app_dir: Option<&Path>, no_cache: bool, refresh: bool)
+        -> Result<S3CacheConfig, Error> {
+        let S3CacheConfigBuilder { enabled, dir, max_size_mb, listing_ttl_secs } = self;
+        if no_cache && refresh {
+            Err(Error::from("Cannot both skip (--no-cache) and refresh (--refresh) the cache."))?
+        }
+        let dir = dir.or_else(|| app_dir.map(|app_dir| app_dir.join(S3_CACHE_DIR)));
+        let mode = if no_cache || !enabled.unwrap_or(true) || dir.is_none() {
+            CacheMode::Off
+        } else if refresh {
+            CacheMode::Refresh
+        } else {
+            CacheMode::Use
+        };
+        let max_size_mb = max_size_mb.unwrap_or(DEFAULT_S3_CACHE_MAX_SIZE_MB);
+        let max_bytes = max_size_mb.saturating_mul(1024 * 1024);
+        let listing_ttl =
+            Duration::from_secs(listing_ttl_secs.unwrap_or(DEFAULT_S3_CACHE_LISTING_TTL_SECS));
+        Ok(S3CacheConfig { dir, mode, max_bytes, listing_ttl })
+    }
+}
+impl ServeConfigBuilder {
+    pub fn new() -> ServeConfigBuilder {
+        let address: Option<String> = None;
+        let workers: Option<usize> = None;
+        let cache_ttl_secs: Option<u64> = None;
+        let cache_entries: Option<usize> = None;
+        ServeConfigBuilder { address, workers, cache_ttl_secs, cache_entries }
+    }
+    pub fn build(self, store: StoreConfig) -> Result<ServeConfig, Error> {
+        let ServeConfigBuilder { address, workers, cache_ttl_secs, cache_entries } = self;
+        let address = address.unwrap_or_else(|| DEFAULT_SERVE_ADDRESS.to_string());
+        let workers = workers.unwrap_or(DEFAULT_SERVE_WORKERS);
+        if workers == 0 {
+            Err(Error::from("Need at least one worker (serve/workers)."))?
+        }
+        let cache_ttl = Duration::from_secs(cache_ttl_secs.unwrap_or(DEFAULT_CACHE_TTL_SECS));
+        let cache_entries = cache_entries.unwrap_or(DEFAULT_CACHE_ENTRIES);
+        Ok(ServeConfig { store, address, workers, cache_ttl, cache_entries })
+    }
+}
 impl ConfigBuilder {
     pub fn new() -> ConfigBuilder {
         let action: Option<Action> = None;
         let data_dir: Option<PathBuf> = None;
         let neo4j = Some(Neo4jConfigBuilder::new());
+        let s3 = Some(S3ConfigBuilder::new());
+        let cache = Some(S3CacheConfigBuilder::new());
         let file: Option<String> = None;
         let out: Option<String> = None;
+        let hgnc_file: Option<String> = None;
+        let bulk_dir: Option<String> = None;
+        let traits: Option<Vec<String>> = None;
+        let min_similarity: Option<f64> = None;
+        let backend: Option<Backend> = None;
+        let id: Option<String> = None;
+        let limit: Option<usize> = None;
+        let cypher: Option<String> = None;
+        let params: Option<Vec<String>> = None;
+        let format: Option<OutputFormat> = None;
+        let sort: Option<String> = None;
+        let ascending: Option<bool> = None;
+        let seeds: Option<Vec<String>> = None;
+        let hops: Option<usize> = None;
+        let rel_types: Option<Vec<String>> = None;
+        let min_weight: Option<f64> = None;
+        let sources: Option<Vec<String>> = None;
+        let min_beta: Option<f64> = None;
+        let wipe_traits: Option<Vec<String>> = None;
+        let labels: Option<Vec<String>> = None;
+        let run_id: Option<String> = None;
+        let sub_dir: Option<String> = None;
+        let batch_size: Option<usize> = None;
+        let yes: Option<bool> = None;
+        let drop_schema: Option<bool> = None;
+        let fix: Option<bool> = None;
+        let recursive: Option<bool> = None;
+        let long: Option<bool> = None;
+        let glob: Option<String> = None;
+        let summary: Option<bool> = None;
+        let no_cache: Option<bool> = None;
+        let refresh: Option<bool> = None;
+        let clear: Option<bool> = None;
         let pigean = Some(PigeanConfigBuilder::new());
-        ConfigBuilder { action, data_dir, neo4j, file, out, pigean }
+        let embed = Some(EmbedConfigBuilder::new());
+        let serve = Some(ServeConfigBuilder::new());
+        let app_dir: Option<PathBuf> = None;
+        ConfigBuilder {
+            action, data_dir, neo4j, s3, cache, file, out, hgnc_file, bulk_dir, traits,
+            min_similarity, backend, id, limit, cypher, params, format, sort, ascending, seeds,
+            hops, rel_types, min_weight, sources, min_beta, wipe_traits, labels, run_id, sub_dir,
+            batch_size, yes, drop_schema, fix, recursive, long, glob, summary, no_cache, refresh,
+            clear, pigean, embed, serve, app_dir
+        }
     }
     pub fn neo4j_mut(&mut self) -> &mut Neo4jConfigBuilder {
         self.neo4j.get_or_insert_with(Neo4jConfigBuilder::new)
     }
+    pub fn with_app_dir(self, app_dir: PathBuf) -> ConfigBuilder {
+        ConfigBuilder { app_dir: Some(app_dir), ..self }
+    }
+    /// The S3 settings, which apply to any action, so they are built apart from the action.
+    pub fn s3_config(&self) -> Result<S3Config, Error> {
+        match &self.s3 {
+            Some(s3) => s3.clone().build(),
+            None => Ok(S3Config::default()),
+        }
+    }
+    /// The S3 cache settings, which apply to any action reading from S3.
+    pub fn s3_cache_config(&self) -> Result<S3CacheConfig, Error> {
+        let cache = self.cache.clone().unwrap_or_default();
+        cache.build(self.app_dir.as_deref(), self.no_cache.unwrap_or(false),
+                    self.refresh.unwrap_or(false))
+    }
+    pub fn serve_mut(&mut self) -> &mut ServeConfigBuilder {
+        self.serve.get_or_insert_with(ServeConfigBuilder::new)
+    }
     pub fn with_cli_options(self, cli_options: CliOptions) -> ConfigBuilder {
         let mut builder = self;
         if let Some(action) = cli_options.action {
@@ -188,7 +861,9 @@ impl ConfigBuilder {
             builder.neo4j_mut().user = Some(user);
         }
         if let Some(password) = cli_options.args.password {
-            builder.neo4j_mut().password = Some(password);
+            let neo4j = builder.neo4j_mut();
+            neo4j.password = Some(password);
+            neo4j.password_on_command_line = true;
         }
         if let Some(file) = cli_options.args.file {
             builder.file = Some(file);
@@ -196,8 +871,119 @@ impl ConfigBuilder {
         if let Some(out) = cli_options.args.out {
             builder.out = Some(out);
         }
+        if let Some(hgnc_file) = cli_options.args.hgnc_file {
+            builder.hgnc_file = Some(hgnc_file);
+        }
+        if let Some(bulk_dir) = cli_options.args.bulk_dir {
+            builder.bulk_dir = Some(bulk_dir);
+        }
+        if let Some(traits) = cli_options.args.traits {
+            builder.wipe_traits = Some(traits.clone());
+            builder.traits = Some(traits);
+        }
+        if let Some(min_similarity) = cli_options.args.min_similarity {
+            builder.min_similarity = Some(min_similarity);
+        }
+        if let Some(backend) = cli_options.args.backend {
+            builder.backend = Some(backend);
+        }
+        if let Some(id) = cli_options.args.id {
+            builder.id = Some(id);
+        }
+        if let Some(limit) = cli_options.args.limit {
+            builder.limit = Some(limit);
+        }
+        if let Some(cypher) = cli_options.args.cypher {
+            builder.cypher = Some(cypher);
+        }
+        if !cli_options.args.params.is_empty() {
+            builder.params = Some(cli_options.args.params);
+        }
+        if let Some(format) = cli_options.args.format {
+            builder.format = Some(format);
+        }
+        if let Some(sort) = cli_options.args.sort {
+            builder.sort = Some(sort);
+        }
+        if cli_options.args.ascending {
+            builder.ascending = Some(true);
+        }
+        if let Some(seeds) = cli_options.args.seeds {
+            builder.seeds = Some(seeds);
+        }
+        if let Some(hops) = cli_options.args.hops {
+            builder.hops = Some(hops);
+        }
+        if let Some(rel_types) = cli_options.args.rel_types {
+            builder.rel_types = Some(rel_types);
+        }
+        if let Some(min_weight) = cli_options.args.min_weight {
+            builder.min_weight = Some(min_weight);
+        }
+        if let Some(sources) = cli_options.args.sources {
+            builder.sources = Some(sources);
+        }
+        if let Some(min_beta) = cli_options.args.min_beta {
+            builder.min_beta = Some(min_beta);
+        }
+        if let Some(address) = cli_options.args.address {
+            builder.serve_mut().address = Some(address);
+        }
+        if let Some(labels) = cli_options.args.labels {
+            builder.labels = Some(labels);
+        }
+        if let Some(run_id) = cli_options.args.run_id {
+            builder.run_id = Some(run_id);
+        }
+        if let Some(sub_dir) = cli_options.args.sub_dir {
+            builder.sub_dir = Some(sub_dir);
+        }
+        if let Some(batch_size) = cli_options.args.batch_size {
+            builder.batch_size = Some(batch_size);
+        }
+        if cli_options.args.yes {
+            builder.yes = Some(true);
+        }
+        if cli_options.args.drop_schema {
+            builder.drop_schema = Some(true);
+        }
+        if cli_options.args.fix {
+            builder.fix = Some(true);
+        }
+        if cli_options.args.recursive {
+            builder.recursive = Some(true);
+        }
+        if cli_options.args.long {
+            builder.long = Some(true);
+        }
+        if let Some(glob) = cli_options.args.glob {
+            builder.glob = Some(glob);
+        }
+        if cli_options.args.summary {
+            builder.summary = Some(true);
+        }
+        if cli_options.args.no_cache {
+            builder.no_cache = Some(true);
+        }
+        if cli_options.args.refresh {
+            builder.refresh = Some(true);
+        }
+        if cli_options.args.clear {
+            builder.clear = Some(true);
+        }
         builder
     }
+    fn lookup_config(self) -> Result<LookupConfig, Error> {
+        let ConfigBuilder {
+            neo4j, bulk_dir, backend, id, limit, sort, ascending, format, out, ..
+        } = self;
+        let store = store_config(backend, neo4j, bulk_dir)?;
+        let id = id.ok_or_else(|| Error::from("No node id specified."))?;
+        let limit = limit.unwrap_or(DEFAULT_LIMIT);
+        let ascending = ascending.unwrap_or(false);
+        let format = format.unwrap_or(OutputFormat::Table);
+        Ok(LookupConfig { store, id, limit, sort, ascending, format, out })
+    }
     fn get_action(&self) -> Result<Action, Error> {
         self.action.ok_or(Error::from(
             format!("No action specified. Possible actions are {}.", all_actions_list())
@@ -207,10 +993,16 @@ impl ConfigBuilder {
         let action = self.get_action()?;
         match action {
             Action::Hello => {
-                let ConfigBuilder { data_dir, .. } = self;
+                let ConfigBuilder { data_dir, neo4j, .. } = self;
                 let data_dir =
                     data_dir.ok_or_else(|| Error::from("No data directory specified."))?;
-                Ok(ActionConfig::Hello(LocalConfig { data_dir }))
+                let local_config = LocalConfig { data_dir };
+                let neo4j = neo4j.unwrap_or_default();
+                let password_source = neo4j.password_options().source();
+                let Neo4jConfigBuilder { uri: neo4j_uri, user: neo4j_user, .. } = neo4j;
+                Ok(ActionConfig::Hello(HelloConfig {
+                    local_config, neo4j_uri, neo4j_user, password_source
+                }))
             }
             Action::Survey => {
                 let ConfigBuilder { data_dir, .. } = self;
@@ -228,22 +1020,42 @@ impl ConfigBuilder {
                 Ok(ActionConfig::Ping(ClientConfig { local_config, neo4j }))
             }
             Action::Upload => {
-                let ConfigBuilder { data_dir, neo4j, .. }
+                let ConfigBuilder { data_dir, neo4j, hgnc_file, run_id, sub_dir, .. }
                     = self;
                 let data_dir =
                     data_dir.ok_or_else(|| Error::from("No data directory specified."))?;
+                // Unless given, a run is named after its start and the sub-directory is the
+                // name of the data directory.
+                let run_id = run_id.unwrap_or_else(|| {
+                    humantime::format_rfc3339_seconds(SystemTime::now()).to_string()
+                });
+                let sub_dir = sub_dir.unwrap_or_else(|| {
+                    data_dir.file_name().map(|name| name.to_string_lossy().to_string())
+                        .unwrap_or_default()
+                });
                 let local_config = LocalConfig { data_dir };
                 let neo4j = neo4j_config(neo4j)?;
-                Ok(ActionConfig::Upload(ClientConfig { local_config, neo4j }))
+                let client_config = ClientConfig { local_config, neo4j };
+                Ok(ActionConfig::Upload(UploadConfig { client_config, hgnc_file, run_id, sub_dir }))
             }
             Action::Wipe => {
-                let ConfigBuilder { data_dir, neo4j, .. } 
-                    = self;
-                let data_dir = 
-                    data_dir.ok_or_else(|| Error::from("No data directory specified."))?;
-                let local_config = LocalConfig { data_dir };
+                let ConfigBuilder {
+                    neo4j, labels, wipe_traits, run_id, sub_dir, batch_size, yes, drop_schema, ..
+                } = self;
+                let scope = WipeScope { labels, traits: trait_ids(wipe_traits), run_id, sub_dir };
+                let batch_size = batch_size.unwrap_or(DEFAULT_WIPE_BATCH_SIZE);
+                if batch_size == 0 {
+                    Err(Error::from("Batch size needs to be positive."))?
+                }
+                let drop_schema = drop_schema.unwrap_or(false);
+                if drop_schema && scope.is_partial() {
+                    Err(Error::from(
+                        "Can only drop the schema when wiping everything or whole labels."
+                    ))?
+                }
+                let yes = yes.unwrap_or(false);
                 let neo4j = neo4j_config(neo4j)?;
-                Ok(ActionConfig::Wipe(ClientConfig { local_config, neo4j }))
+                Ok(ActionConfig::Wipe(WipeConfig { neo4j, scope, batch_size, yes, drop_schema }))
             }
             Action::Cat => {
                 let ConfigBuilder { file, .. } = self;
@@ -251,37 +1063,186 @@ impl ConfigBuilder {
                 Ok(ActionConfig::Cat(file))
             }
             Action::Ls => {
-                let ConfigBuilder { file, .. } = self;
-                let file = file.ok_or_else(|| Error::from("No directory specified."))?;
-                Ok(ActionConfig::Ls(file))
+                let ConfigBuilder { file, recursive, long, glob, summary, format, out, .. } = self;
+                let dir = file.ok_or_else(|| Error::from("No directory specified."))?;
+                let recursive = recursive.unwrap_or(false);
+                let long = long.unwrap_or(false);
+                let summary = summary.unwrap_or(false);
+                if long && summary {
+                    Err(Error::from("Cannot list both long (--long) and summary (--summary)."))?
+                }
+                Ok(ActionConfig::Ls(LsConfig { dir, recursive, long, glob, summary, format, out }))
             }
             Action::Bulk => {
-                let ConfigBuilder { pigean, out, .. } = self;
+                let ConfigBuilder { pigean, out, hgnc_file, .. } = self;
                 let pigean = 
                     pigean.ok_or_else(|| Error::from("No PIGEAN configuration specified."))?;
                 let out = 
                     out.ok_or_else(|| Error::from("No output directory specified."))?;
-                let pigean = pigean.build(out)?;
+                let pigean = pigean.build(out, hgnc_file)?;
                 Ok(ActionConfig::Bulk(pigean))
             }
             Action::Factors => {
-                let ConfigBuilder { pigean, out, .. } = self;
+                let ConfigBuilder { pigean, out, hgnc_file, .. } = self;
                 let pigean = 
                     pigean.ok_or_else(|| Error::from("No PIGEAN configuration specified."))?;
                 let out = 
                     out.ok_or_else(|| Error::from("No output directory specified."))?;
-                let pigean = pigean.build(out)?;
+                let pigean = pigean.build(out, hgnc_file)?;
                 Ok(ActionConfig::Factors(pigean))
             }
             Action::TraitGeneSets => {
-                let ConfigBuilder { pigean, out, .. } = self;
+                let ConfigBuilder { pigean, out, hgnc_file, .. } = self;
                 let pigean =
                     pigean.ok_or_else(|| Error::from("No PIGEAN configuration specified."))?;
                 let out =
                     out.ok_or_else(|| Error::from("No output directory specified."))?;
-                let pigean = pigean.build(out)?;
+                let pigean = pigean.build(out, hgnc_file)?;
                 Ok(ActionConfig::TraitGeneSets(pigean))
             }
+            Action::Stats => {
+                let ConfigBuilder { file, out, .. } = self;
+                let dir = file.ok_or_else(|| Error::from("No directory specified."))?;
+                let out = out.ok_or_else(|| Error::from("No output file specified."))?;
+                Ok(ActionConfig::Stats(StatsConfig { dir, out }))
+            }
+            Action::FactorOverlap => {
+                let ConfigBuilder { bulk_dir, traits, out, min_similarity, .. } = self;
+                let bulk_dir =
+                    bulk_dir.ok_or_else(|| Error::from("No bulk directory specified."))?;
+                let out = out.ok_or_else(|| Error::from("No output directory specified."))?;
+                let traits = trait_ids(traits);
+                Ok(ActionConfig::FactorOverlap(FactorOverlapConfig {
+                    bulk_dir, traits, out, min_similarity
+                }))
+            }
+            Action::CompareTraits => {
+                let ConfigBuilder { traits, backend, neo4j, bulk_dir, out, .. } = self;
+                let traits = trait_ids(traits);
+                if traits.len() < 2 {
+                    Err(Error::from("Need at least two traits (traits) to compare."))?
+                }
+                let store = store_config(backend, neo4j, bulk_dir)?;
+                Ok(ActionConfig::CompareTraits(CompareTraitsConfig { traits, store, out }))
+            }
+            Action::Embed => {
+                let ConfigBuilder { bulk_dir, out, neo4j, embed, .. } = self;
+                let bulk_dir =
+                    bulk_dir.ok_or_else(|| Error::from("No bulk directory specified."))?;
+                let out = out.ok_or_else(|| Error::from("No output file specified."))?;
+                let embed = embed.unwrap_or_default();
+                Ok(ActionConfig::Embed(embed.build(bulk_dir, out, neo4j)?))
+            }
+            Action::VectorIndex => {
+                let ConfigBuilder { neo4j, embed, .. } = self;
+                let neo4j = neo4j_config(neo4j)?;
+                let dims = embed.unwrap_or_default().dims();
+                Ok(ActionConfig::VectorIndex(VectorIndexConfig { neo4j, dims }))
+            }
+            Action::Similar => {
+                let ConfigBuilder { neo4j, id, limit, .. } = self;
+                let neo4j = neo4j_config(neo4j)?;
+                let id = id.ok_or_else(|| Error::from("No node id specified."))?;
+                let limit = limit.unwrap_or(DEFAULT_LIMIT);
+                Ok(ActionConfig::Similar(SimilarConfig { neo4j, id, limit }))
+            }
+            Action::MetaFactors => {
+                let ConfigBuilder { bulk_dir, out, min_similarity, .. } = self;
+                let bulk_dir =
+                    bulk_dir.ok_or_else(|| Error::from("No bulk directory specified."))?;
+                let out = out.ok_or_else(|| Error::from("No output directory specified."))?;
+                let min_similarity = min_similarity.unwrap_or(DEFAULT_META_FACTOR_SIMILARITY);
+                if !(min_similarity > 0.0 && min_similarity <= 1.0) {
+                    Err(Error::from("Minimum similarity needs to be above 0 and at most 1."))?
+                }
+                Ok(ActionConfig::MetaFactors(MetaFactorsConfig { bulk_dir, out, min_similarity }))
+            }
+            Action::Query => {
+                let ConfigBuilder { neo4j, cypher, file, params, format, out, .. } = self;
+                let neo4j = neo4j_config(neo4j)?;
+                if cypher.is_none() && file.is_none() {
+                    Err(Error::from("No Cypher statement (cypher) or file specified."))?
+                }
+                let params = parse_params(params)?;
+                let format = format.unwrap_or(OutputFormat::Table);
+                Ok(ActionConfig::Query(QueryConfig { neo4j, cypher, file, params, format, out }))
+            }
+            Action::GenesForTrait => {
+                Ok(ActionConfig::GenesForTrait(self.lookup_config()?))
+            }
+            Action::GeneSetsForTrait => {
+                Ok(ActionConfig::GeneSetsForTrait(self.lookup_config()?))
+            }
+            Action::FactorsForTrait => {
+                Ok(ActionConfig::FactorsForTrait(self.lookup_config()?))
+            }
+            Action::FactorsForGene => {
+                Ok(ActionConfig::FactorsForGene(self.lookup_config()?))
+            }
+            Action::FactorsForGeneSet => {
+                Ok(ActionConfig::FactorsForGeneSet(self.lookup_config()?))
+            }
+            Action::ExportSubgraph => {
+                let ConfigBuilder {
+                    neo4j, bulk_dir, backend, seeds, hops, rel_types, min_weight, out, ..
+                } = self;
+                let store = store_config(backend, neo4j, bulk_dir)?;
+                let seeds = seeds.filter(|seeds| !seeds.is_empty())
+                    .ok_or_else(|| Error::from("No seed nodes (seeds) specified."))?;
+                let hops = hops.unwrap_or(DEFAULT_HOPS);
+                let out = out.ok_or_else(|| Error::from("No output file specified."))?;
+                Ok(ActionConfig::ExportSubgraph(ExportSubgraphConfig {
+                    store, seeds, hops, rel_types, min_weight, out
+                }))
+            }
+            Action::ExportTerms => {
+                let ConfigBuilder {
+                    neo4j, bulk_dir, backend, traits, sources, min_beta, out, ..
+                } = self;
+                let store = store_config(backend, neo4j, bulk_dir)?;
+                let traits = trait_ids(traits);
+                Ok(ActionConfig::ExportTerms(ExportTermsConfig {
+                    store, traits, sources, min_beta, out
+                }))
+            }
+            Action::Serve => {
+                let ConfigBuilder { neo4j, bulk_dir, backend, serve, .. } = self;
+                let store = store_config(backend, neo4j, bulk_dir)?;
+                Ok(ActionConfig::Serve(serve.unwrap_or_default().build(store)?))
+            }
+            Action::Shell => {
+                let ConfigBuilder { neo4j, app_dir, .. } = self;
+                let neo4j = neo4j_config(neo4j)?;
+                let history_file = app_dir.map(|app_dir| app_dir.join(SHELL_HISTORY_FILE));
+                Ok(ActionConfig::Shell(ShellConfig { neo4j, history_file }))
+            }
+            Action::Status => {
+                let ConfigBuilder { neo4j, format, out, .. } = self;
+                let format = format.unwrap_or(OutputFormat::Table);
+                if let OutputFormat::Csv | OutputFormat::Tsv = format {
+                    Err(Error::from(format!(
+                        "Status is available as {} or {}.", OutputFormat::Table, OutputFormat::Json
+                    )))?
+                }
+                let neo4j = neo4j_config(neo4j)?;
+                Ok(ActionConfig::Status(StatusConfig { neo4j, format, out }))
+            }
+            Action::Check => {
+                let ConfigBuilder { neo4j, fix, yes, limit, format, out, .. } = self;
+                let fix = fix.unwrap_or(false);
+                let yes = yes.unwrap_or(false);
+                let limit = limit.unwrap_or(DEFAULT_LIMIT);
+                let format = format.unwrap_or(OutputFormat::Table);
+                let neo4j = neo4j_config(neo4j)?;
+                Ok(ActionConfig::Check(CheckConfig { neo4j, fix, yes, limit, format, out }))
+            }
+            Action::Cache => {
+                let ConfigBuilder { clear, long, format, out, .. } = self;
+                let clear = clear.unwrap_or(false);
+                let long = long.unwrap_or(false);
+                let format = format.unwrap_or(OutputFormat::Table);
+                Ok(ActionConfig::Cache(CacheConfig { clear, long, format, out }))
+            }
         }
     }
 }
@@ -299,6 +1260,26 @@ impl Display for Action {
             Action::Bulk => write!(f, "{}", action::BULK),
             Action::Factors => write!(f, "{}", action::FACTORS),
             Action::TraitGeneSets => write!(f, "{}", action::PGS),
+            Action::Stats => write!(f, "{}", action::STATS),
+            Action::FactorOverlap => write!(f, "{}", action::FACTOR_OVERLAP),
+            Action::CompareTraits => write!(f, "{}", action::COMPARE_TRAITS),
+            Action::Embed => write!(f, "{}", action::EMBED),
+            Action::VectorIndex => write!(f, "{}", action::VECTOR_INDEX),
+            Action::Similar => write!(f, "{}", action::SIMILAR),
+            Action::MetaFactors => write!(f, "{}", action::META_FACTORS),
+            Action::Query => write!(f, "{}", action::QUERY),
+            Action::GenesForTrait => write!(f, "{}", action::GENES_FOR_TRAIT),
+            Action::GeneSetsForTrait => write!(f, "{}", action::GENE_SETS_FOR_TRAIT),
+            Action::FactorsForTrait => write!(f, "{}", action::FACTORS_FOR_TRAIT),
+            Action::FactorsForGene => write!(f, "{}", action::FACTORS_FOR_GENE),
+            Action::FactorsForGeneSet => write!(f, "{}", action::FACTORS_FOR_GENE_SET),
+            Action::ExportSubgraph => write!(f, "{}", action::EXPORT_SUBGRAPH),
+            Action::ExportTerms => write!(f, "{}", action::EXPORT_TERMS),
+            Action::Serve => write!(f, "{}", action::SERVE),
+            Action::Shell => write!(f, "{}", action::SHELL),
+            Action::Status => write!(f, "{}", action::STATUS),
+            Action::Check => write!(f, "{}", action::CHECK),
+            Action::Cache => write!(f, "{}", action::CACHE),
         }
     }
 }
@@ -316,6 +1297,26 @@ impl Action {
             Action::Bulk => action::BULK,
             Action::Factors => action::FACTORS,
             Action::TraitGeneSets => action::PGS,
+            Action::Stats => action::STATS,
+            Action::FactorOverlap => action::FACTOR_OVERLAP,
+            Action::CompareTraits => action::COMPARE_TRAITS,
+            Action::Embed => action::EMBED,
+            Action::VectorIndex => action::VECTOR_INDEX,
+            Action::Similar => action::SIMILAR,
+            Action::MetaFactors => action::META_FACTORS,
+            Action::Query => action::QUERY,
+            Action::GenesForTrait => action::GENES_FOR_TRAIT,
+            Action::GeneSetsForTrait => action::GENE_SETS_FOR_TRAIT,
+            Action::FactorsForTrait => action::FACTORS_FOR_TRAIT,
+            Action::FactorsForGene => action::FACTORS_FOR_GENE,
+            Action::FactorsForGeneSet => action::FACTORS_FOR_GENE_SET,
+            Action::ExportSubgraph => action::EXPORT_SUBGRAPH,
+            Action::ExportTerms => action::EXPORT_TERMS,
+            Action::Serve => action::SERVE,
+            Action::Shell => action::SHELL,
+            Action::Status => action::STATUS,
+            Action::Check => action::CHECK,
+            Action::Cache => action::CACHE,
         }
     }
     pub fn about(&self) -> &'static str {
@@ -330,6 +1331,26 @@ impl Action {
             Action::Bulk => about::BULK,
             Action::Factors => about::FACTORS,
             Action::TraitGeneSets => about::PGS,
+            Action::Stats => about::STATS,
+            Action::FactorOverlap => about::FACTOR_OVERLAP,
+            Action::CompareTraits => about::COMPARE_TRAITS,
+            Action::Embed => about::EMBED,
+            Action::VectorIndex => about::VECTOR_INDEX,
+            Action::Similar => about::SIMILAR,
+            Action::MetaFactors => about::META_FACTORS,
+            Action::Query => about::QUERY,
+            Action::GenesForTrait => about::GENES_FOR_TRAIT,
+            Action::GeneSetsForTrait => about::GENE_SETS_FOR_TRAIT,
+            Action::FactorsForTrait => about::FACTORS_FOR_TRAIT,
+            Action::FactorsForGene => about::FACTORS_FOR_GENE,
+            Action::FactorsForGeneSet => about::FACTORS_FOR_GENE_SET,
+            Action::ExportSubgraph => about::EXPORT_SUBGRAPH,
+            Action::ExportTerms => about::EXPORT_TERMS,
+            Action::Serve => about::SERVE,
+            Action::Shell => about::SHELL,
+            Action::Status => about::STATUS,
+            Action::Check => about::CHECK,
+            Action::Cache => about::CACHE,
         }
     }
 }
@@ -345,10 +1366,26 @@ impl Default for ConfigBuilder {
     }
 }
 
+impl Default for S3ConfigBuilder {
+    fn default() -> Self { S3ConfigBuilder::new() }
+}
+
+impl Default for S3CacheConfigBuilder {
+    fn default() -> Self { S3CacheConfigBuilder::new() }
+}
+
 impl Default for PigeanConfigBuilder {
     fn default() -> Self { PigeanConfigBuilder::new() }
 }
 
+impl Default for EmbedConfigBuilder {
+    fn default() -> Self { EmbedConfigBuilder::new() }
+}
+
+impl Default for ServeConfigBuilder {
+    fn default() -> Self { ServeConfigBuilder::new() }
+}
+
 impl TryFrom<&str> for Action {
     type Error = Error;
 
@@ -364,11 +1401,63 @@ impl TryFrom<&str> for Action {
             action::BULK => Ok(Action::Bulk),
             action::FACTORS => Ok(Action::Factors),
             action::PGS => Ok(Action::TraitGeneSets),
+            action::STATS => Ok(Action::Stats),
+            action::FACTOR_OVERLAP => Ok(Action::FactorOverlap),
+            action::COMPARE_TRAITS => Ok(Action::CompareTraits),
+            action::EMBED => Ok(Action::Embed),
+            action::VECTOR_INDEX => Ok(Action::VectorIndex),
+            action::SIMILAR => Ok(Action::Similar),
+            action::META_FACTORS => Ok(Action::MetaFactors),
+            action::QUERY => Ok(Action::Query),
+            action::GENES_FOR_TRAIT => Ok(Action::GenesForTrait),
+            action::GENE_SETS_FOR_TRAIT => Ok(Action::GeneSetsForTrait),
+            action::FACTORS_FOR_TRAIT => Ok(Action::FactorsForTrait),
+            action::FACTORS_FOR_GENE => Ok(Action::FactorsForGene),
+            action::FACTORS_FOR_GENE_SET => Ok(Action::FactorsForGeneSet),
+            action::EXPORT_SUBGRAPH => Ok(Action::ExportSubgraph),
+            action::EXPORT_TERMS => Ok(Action::ExportTerms),
+            action::SERVE => Ok(Action::Serve),
+            action::SHELL => Ok(Action::Shell),
+            action::STATUS => Ok(Action::Status),
+            action::CHECK => Ok(Action::Check),
+            action::CACHE => Ok(Action::Cache),
             _ => Err(Error::from(format!("Unknown action: {value }"))),
         }
     }
 }
 
+impl Display for Backend {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Backend::Neo4j => write!(f, "{}", backend::NEO4J),
+            Backend::Memory => write!(f, "{}", backend::MEMORY),
+        }
+    }
+}
+
+impl TryFrom<&str> for Backend {
+    type Error = Error;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value {
+            backend::NEO4J => Ok(Backend::Neo4j),
+            backend::MEMORY => Ok(Backend::Memory),
+            _ => Err(Error::from(format!(
+                "Unknown backend: {value}. Known backends are {} and {}.", backend::NEO4J,
+                backend::MEMORY
+            ))),
+        }
+    }
+}
+
+impl TryFrom<String> for Backend {
+    type Error = Error;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        Backend::try_from(value.as_str())
+    }
+}
+
 impl TryFrom<&str> for ConfigBuilder {
     type Error = Error;
     fn try_from(string: &str) -> Result<Self, Self::Error> {
@@ -382,6 +1471,58 @@ impl TryFrom<&str> for ConfigBuilder {
     }
 }
 
+fn store_config(backend: Option<Backend>, neo4j: Option<Neo4jConfigBuilder>,
+                bulk_dir: Option<String>) -> Result<StoreConfig, Error> {
+    match backend.unwrap_or(Backend::Neo4j) {
+        Backend::Neo4j => Ok(StoreConfig::Neo4j(Box::new(neo4j_config(neo4j)?))),
+        Backend::Memory => {
+            let bulk_dir = bulk_dir.ok_or_else(|| {
+                Error::from("No bulk directory specified for the memory backend.")
+            })?;
+            Ok(StoreConfig::Memory { bulk_dir })
+        }
+    }
+}
+
+fn parse_params(params: Option<Vec<String>>) -> Result<Vec<(String, String)>, Error> {
+    params.unwrap_or_default().iter().map(|param| {
+        let (key, value) = param.split_once('=').ok_or_else(|| {
+            Error::from(format!("Parameter '{param}' is not of the form key=value."))
+        })?;
+        Ok((key.trim().to_string(), value.to_string()))
+    }).collect()
+}
+
+fn trait_ids(traits: Option<Vec<String>>) -> Vec<TraitId> {
+    traits.unwrap_or_default().into_iter().map(TraitId::new).collect()
+}
+
+/// Applies the TLS options to the scheme of the URI, e.g. `bolt` becomes `bolt+s` with TLS and
+/// `bolt+ssc` when trusting all certificates. Without TLS options, the URI is left alone.
+fn tls_uri(uri: &str, tls: Option<bool>, trust_all_certificates: Option<bool>, has_ca_file: bool)
+    -> Result<String, Error> {
+    let (scheme, rest) = uri.split_once("://").unwrap_or(("bolt", uri));
+    let (base, security) = scheme.split_once('+').unwrap_or((scheme, ""));
+    let security = match (tls, trust_all_certificates) {
+        (Some(false), Some(true)) => Err(Error::from(
+            "Cannot trust all certificates (neo4j/trust_all_certificates) without TLS \
+            (neo4j/tls)."
+        ))?,
+        (Some(false), _) if has_ca_file => Err(Error::from(
+            "Cannot use a CA file (neo4j/ca_file) without TLS (neo4j/tls)."
+        ))?,
+        (Some(false), _) => "",
+        (_, Some(true)) => "ssc",
+        (Some(true), _) => if security.is_empty() { "s" } else { security },
+        (None, _) => if security.is_empty() && has_ca_file { "s" } else { security },
+    };
+    if security.is_empty() {
+        Ok(format!("{base}://{rest}"))
+    } else {
+        Ok(format!("{base}+{security}://{rest}"))
+    }
+}
+
 fn neo4j_config(builder: Option<Neo4jConfigBuilder>) -> Result<Neo4jConfig, Error> {
     builder.ok_or(Error::from("No Neo4j configuration (neo4j) specified."))?.build()
 }
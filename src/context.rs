@@ -0,0 +1,76 @@
+use crate::cache::S3Cache;
+use crate::config::{Neo4jConfig, S3CacheConfig, S3Config};
+use crate::error::Error;
+use crate::neo::Neo;
+use aws_config::{BehaviorVersion, Region};
+use aws_sdk_s3::types::RequestPayer;
+use log::info;
+use std::sync::OnceLock;
+use tokio::runtime::{Builder, Runtime};
+
+/// What all actions share: one multi-threaded runtime, the S3 cache, and the S3 client and Neo4j
+/// connection pool, each created on first use so actions that need neither do not pay for them.
+pub struct AppContext {
+    runtime: Runtime,
+    s3_config: S3Config,
+    s3_cache: S3Cache,
+    s3_client: OnceLock<aws_sdk_s3::Client>,
+    neo: OnceLock<Neo>,
+}
+
+impl AppContext {
+    pub fn new(s3_config: S3Config, s3_cache_config: S3CacheConfig)
+        -> Result<AppContext, Error> {
+        let runtime = Builder::new_multi_thread().enable_all().build()?;
+        let s3_cache = S3Cache::new(s3_cache_config);
+        Ok(AppContext {
+            runtime, s3_config, s3_cache, s3_client: OnceLock::new(), neo: OnceLock::new()
+        })
+    }
+    pub(crate) fn runtime(&self) -> &Runtime {
+        &self.runtime
+    }
+    pub(crate) fn s3_client(&self) -> &aws_sdk_s3::Client {
+        self.s3_client.get_or_init(|| {
+            let S3Config { endpoint_url, region, profile, force_path_style, anonymous, .. } =
+                &self.s3_config;
+            let mut loader = aws_config::defaults(BehaviorVersion::latest());
+            if let Some(endpoint_url) = endpoint_url {
+                info!("Using S3 endpoint {endpoint_url}");
+                loader = loader.endpoint_url(endpoint_url);
+            }
+            if let Some(region) = region {
+                loader = loader.region(Region::new(region.clone()));
+            }
+            if let Some(profile) = profile {
+                info!("Using AWS profile {profile}");
+                loader = loader.profile_name(profile);
+            }
+            if *anonymous {
+                info!("Accessing S3 anonymously");
+                loader = loader.no_credentials();
+            }
+            let sdk_config = self.runtime.block_on(loader.load());
+            let s3_config = aws_sdk_s3::config::Builder::from(&sdk_config)
+                .force_path_style(*force_path_style)
+                .build();
+            aws_sdk_s3::Client::from_conf(s3_config)
+        })
+    }
+    pub(crate) fn s3_cache(&self) -> &S3Cache {
+        &self.s3_cache
+    }
+    /// Set on every request if the configuration accepts requester-pays charges.
+    pub(crate) fn s3_request_payer(&self) -> Option<RequestPayer> {
+        self.s3_config.request_payer.then_some(RequestPayer::Requester)
+    }
+    /// A handle on the Neo4j connection pool, connecting with the given configuration the first
+    /// time. An action only ever uses one configuration, so later calls share the first pool.
+    pub(crate) fn neo(&self, config: &Neo4jConfig) -> Result<Neo, Error> {
+        if let Some(neo) = self.neo.get() {
+            return Ok(neo.clone());
+        }
+        let neo = Neo::for_config(self.runtime.handle().clone(), config)?;
+        Ok(self.neo.get_or_init(|| neo).clone())
+    }
+}
@@ -0,0 +1,391 @@
+use arrow_array::builder::{FixedSizeListBuilder, Float32Builder};
+use arrow_array::{ArrayRef, RecordBatch, StringArray};
+use crate::bulk;
+use crate::bulk::{FactorGene, FactorGeneSet, FactorPheno};
+use crate::config::{EmbedConfig, Neo4jConfig};
+use crate::context::AppContext;
+use crate::error::Error;
+use crate::neo::RowEater;
+use crate::schema::{labels, VECTOR_PROPERTY};
+use log::info;
+use neo4rs::{query, BoltType, Row};
+use parquet::arrow::ArrowWriter;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+const WRITE_BATCH_SIZE: usize = 500;
+const NPY_MAGIC: &[u8] = b"\x93NUMPY";
+const NPY_ALIGNMENT: usize = 64;
+
+mod formats {
+    pub(crate) const CSV: &str = "csv";
+    pub(crate) const NPY: &str = "npy";
+    pub(crate) const PARQUET: &str = "parquet";
+}
+
+#[derive(Clone, PartialEq, Eq, Hash)]
+struct NodeKey {
+    label: &'static str,
+    id: String,
+}
+
+/// An undirected weighted graph, with nodes identified by label and id.
+struct Adjacency {
+    nodes: Vec<NodeKey>,
+    indices: HashMap<NodeKey, usize>,
+    neighbors: Vec<Vec<(usize, f32)>>,
+    n_edges: usize,
+}
+
+/// Each node has a source and a sink dimension fixed at 1 and -1. All other dimensions are
+/// the weighted average of the neighbors' values, updated once per iteration.
+struct Embedding {
+    dims: usize,
+    anchors: Vec<(usize, usize)>,
+    vectors: Vec<f32>,
+}
+
+struct WriteBackRowEater {}
+
+impl RowEater for WriteBackRowEater {
+    type Summary = ();
+    fn eat(&mut self, _row: Row) -> Result<(), Error> {
+        Ok(())
+    }
+    fn finish(&mut self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+impl Adjacency {
+    fn new() -> Adjacency {
+        Adjacency {
+            nodes: Vec::new(), indices: HashMap::new(), neighbors: Vec::new(), n_edges: 0
+        }
+    }
+    fn node(&mut self, label: &'static str, id: String) -> usize {
+        let key = NodeKey { label, id };
+        match self.indices.get(&key) {
+            Some(&i) => i,
+            None => {
+                let i = self.nodes.len();
+                self.nodes.push(key.clone());
+                self.indices.insert(key, i);
+                self.neighbors.push(Vec::new());
+                i
+            }
+        }
+    }
+    fn add_edge(&mut self, label1: &'static str, id1: String, label2: &'static str, id2: String,
+                weight: f64) {
+        let i1 = self.node(label1, id1);
+        let i2 = self.node(label2, id2);
+        if weight.is_finite() && weight != 0.0 {
+            self.neighbors[i1].push((i2, weight as f32));
+            self.neighbors[i2].push((i1, weight as f32));
+            self.n_edges += 1;
+        }
+    }
+}
+
+fn read_adjacency(context: &AppContext, bulk_dir: &str) -> Result<Adjacency, Error> {
+    let mut adjacency = Adjacency::new();
+    bulk::for_each_row(context, bulk_dir, |row: FactorPheno| {
+        adjacency.add_edge(labels::TRAIT, row.pheno.to_string(), labels::FACTOR,
+                           row.factor.to_string(), row.any_relevance);
+        Ok(())
+    })?;
+    bulk::for_each_row(context, bulk_dir, |row: FactorGene| {
+        adjacency.add_edge(labels::FACTOR, row.factor.to_string(), labels::GENE,
+                           row.gene.to_string(), row.weight);
+        Ok(())
+    })?;
+    bulk::for_each_row(context, bulk_dir, |row: FactorGeneSet| {
+        adjacency.add_edge(labels::FACTOR, row.factor.to_string(), labels::GENE_SET,
+                           row.gene_set.to_string(), row.weight);
+        Ok(())
+    })?;
+    Ok(adjacency)
+}
+
+impl Embedding {
+    fn new(n_nodes: usize, dims: usize, seed: Option<u64>) -> Embedding {
+        let mut rng = match seed {
+            Some(seed) => fastrand::Rng::with_seed(seed),
+            None => fastrand::Rng::new(),
+        };
+        let mut vectors = vec![0.0f32; n_nodes * dims];
+        let anchors: Vec<(usize, usize)> = (0..n_nodes).map(|i| {
+            let source = rng.usize(..dims);
+            let sink = rng.usize(..(dims - 1));
+            let sink = if sink >= source { sink + 1 } else { sink };
+            vectors[i * dims + source] = 1.0;
+            vectors[i * dims + sink] = -1.0;
+            (source, sink)
+        }).collect();
+        Embedding { dims, anchors, vectors }
+    }
+    fn vector(&self, i: usize) -> &[f32] {
+        &self.vectors[i * self.dims..(i + 1) * self.dims]
+    }
+    fn iterate(&mut self, adjacency: &Adjacency) -> f32 {
+        let dims = self.dims;
+        let mut new_vectors = vec![0.0f32; self.vectors.len()];
+        let mut max_change: f32 = 0.0;
+        for (i, new_vector) in new_vectors.chunks_mut(dims).enumerate() {
+            let neighbors = &adjacency.neighbors[i];
+            let den: f32 = neighbors.iter().map(|(_, weight)| weight).sum();
+            if den != 0.0 {
+                for &(j, weight) in neighbors {
+                    for (value, neighbor_value) in new_vector.iter_mut().zip(self.vector(j)) {
+                        *value += weight * neighbor_value;
+                    }
+                }
+                for value in new_vector.iter_mut() {
+                    *value /= den;
+                }
+            }
+            let (source, sink) = self.anchors[i];
+            new_vector[source] = 1.0;
+            new_vector[sink] = -1.0;
+            for (new_value, old_value) in new_vector.iter().zip(self.vector(i)) {
+                max_change = max_change.max((new_value - old_value).abs());
+            }
+        }
+        self.vectors = new_vectors;
+        max_change
+    }
+}
+
+fn write_csv(adjacency: &Adjacency, embedding: &Embedding, out_file: &Path)
+    -> Result<(), Error> {
+    let mut writer = csv::Writer::from_path(out_file)?;
+    let mut headers = vec!["label".to_string(), "id".to_string()];
+    headers.extend((0..embedding.dims).map(|i| format!("v{i}")));
+    writer.write_record(&headers)?;
+    for (i, node) in adjacency.nodes.iter().enumerate() {
+        let mut record = vec![node.label.to_string(), node.id.clone()];
+        record.extend(embedding.vector(i).iter().map(|value| value.to_string()));
+        writer.write_record(&record)?;
+    }
+    Ok(())
+}
+
+fn write_nodes(adjacency: &Adjacency, nodes_file: &Path) -> Result<(), Error> {
+    let mut writer = csv::Writer::from_path(nodes_file)?;
+    writer.write_record(["label", "id"])?;
+    for node in &adjacency.nodes {
+        writer.write_record([node.label, node.id.as_str()])?;
+    }
+    Ok(())
+}
+
+/// Writes the vectors as a two-dimensional little-endian float32 array in NumPy's NPY format,
+/// one row per node. The nodes are written in the same order to a separate CSV file.
+fn write_npy(adjacency: &Adjacency, embedding: &Embedding, out_file: &Path)
+    -> Result<(), Error> {
+    let mut header = format!(
+        "{{'descr': '<f4', 'fortran_order': False, 'shape': ({}, {}), }}", adjacency.nodes.len(),
+        embedding.dims
+    );
+    let preamble_len = NPY_MAGIC.len() + 4;
+    let padding = NPY_ALIGNMENT - (preamble_len + header.len() + 1) % NPY_ALIGNMENT;
+    header.push_str(&" ".repeat(padding % NPY_ALIGNMENT));
+    header.push('\n');
+    let mut writer = BufWriter::new(File::create(out_file)?);
+    writer.write_all(NPY_MAGIC)?;
+    writer.write_all(&[1, 0])?;
+    writer.write_all(&(header.len() as u16).to_le_bytes())?;
+    writer.write_all(header.as_bytes())?;
+    for value in &embedding.vectors {
+        writer.write_all(&value.to_le_bytes())?;
+    }
+    writer.flush()?;
+    let nodes_file = nodes_file(out_file);
+    info!("Writing node order to {}", nodes_file.display());
+    write_nodes(adjacency, &nodes_file)
+}
+
+/// Writes one row per node with the label, the id and the vector as a fixed-size list of
+/// float32, the layout that pandas, polars and Arrow-based tools read as an embedding column.
+fn write_parquet(adjacency: &Adjacency, embedding: &Embedding, out_file: &Path)
+    -> Result<(), Error> {
+    let labels = StringArray::from_iter_values(adjacency.nodes.iter().map(|node| node.label));
+    let ids = StringArray::from_iter_values(adjacency.nodes.iter().map(|node| node.id.as_str()));
+    let mut vectors =
+        FixedSizeListBuilder::with_capacity(Float32Builder::new(), embedding.dims as i32,
+                                            adjacency.nodes.len());
+    for i in 0..adjacency.nodes.len() {
+        vectors.values().append_slice(embedding.vector(i));
+        vectors.append(true);
+    }
+    let batch = RecordBatch::try_from_iter([
+        ("label", Arc::new(labels) as ArrayRef),
+        ("id", Arc::new(ids) as ArrayRef),
+        ("vector", Arc::new(vectors.finish()) as ArrayRef),
+    ])?;
+    let mut writer = ArrowWriter::try_new(File::create(out_file)?, batch.schema(), None)?;
+    writer.write(&batch)?;
+    writer.close()?;
+    Ok(())
+}
+
+fn nodes_file(out_file: &Path) -> PathBuf {
+    let stem =
+        out_file.file_stem().map(|stem| stem.to_string_lossy().to_string()).unwrap_or_default();
+    out_file.with_file_name(format!("{stem}_nodes.csv"))
+}
+
+/// The output format by file extension, checked before computing anything.
+fn vectors_format(out: &str) -> Result<&'static str, Error> {
+    let extension = Path::new(out).extension()
+        .map(|extension| extension.to_string_lossy().to_lowercase());
+    match extension.as_deref() {
+        Some(formats::CSV) => Ok(formats::CSV),
+        Some(formats::NPY) => Ok(formats::NPY),
+        Some(formats::PARQUET) => Ok(formats::PARQUET),
+        _ => Err(Error::from(format!(
+            "Unsupported output format for {out}. Supported formats are .{}, .{} and .{}.",
+            formats::CSV, formats::NPY, formats::PARQUET
+        ))),
+    }
+}
+
+fn write_vectors(adjacency: &Adjacency, embedding: &Embedding, out: &str) -> Result<(), Error> {
+    let out_file = Path::new(out);
+    info!("Writing {} vectors to {out}", adjacency.nodes.len());
+    match vectors_format(out)? {
+        formats::NPY => write_npy(adjacency, embedding, out_file),
+        formats::PARQUET => write_parquet(adjacency, embedding, out_file),
+        _ => write_csv(adjacency, embedding, out_file),
+    }
+}
+
+fn write_back(context: &AppContext, adjacency: &Adjacency, embedding: &Embedding,
+              neo4j: &Neo4jConfig) -> Result<(), Error> {
+    let neo = context.neo(neo4j)?;
+    let mut row_eater = WriteBackRowEater {};
+    for label in labels::EMBEDDED {
+        let statement = format!(
+            "UNWIND $rows AS row MATCH (n:{label} {{ id: row.id }}) SET n.{VECTOR_PROPERTY} = \
+            row.vector"
+        );
+        let indices: Vec<usize> = adjacency.nodes.iter().enumerate()
+            .filter(|(_, node)| node.label == label).map(|(i, _)| i).collect();
+        info!("Writing {} {label} vectors to Neo4j", indices.len());
+        for batch in indices.chunks(WRITE_BATCH_SIZE) {
+            let rows: Vec<HashMap<String, BoltType>> = batch.iter().map(|&i| {
+                let mut row: HashMap<String, BoltType> = HashMap::new();
+                row.insert("id".to_string(), adjacency.nodes[i].id.clone().into());
+                row.insert("vector".to_string(), embedding.vector(i).to_vec().into());
+                row
+            }).collect();
+            neo.cypher(query(&statement).param("rows", rows), &mut row_eater)?;
+        }
+    }
+    Ok(())
+}
+
+pub(crate) fn embed(context: &AppContext, config: &EmbedConfig) -> Result<(), Error> {
+    vectors_format(&config.out)?;
+    let adjacency = read_adjacency(context, &config.bulk_dir)?;
+    info!("Read {} nodes and {} weighted edges.", adjacency.nodes.len(), adjacency.n_edges);
+    let mut embedding = Embedding::new(adjacency.nodes.len(), config.dims, config.seed);
+    for iteration in 1..=config.iterations {
+        let max_change = embedding.iterate(&adjacency);
+        info!("Iteration {iteration} of {}: max change {max_change}", config.iterations);
+    }
+    write_vectors(&adjacency, &embedding, &config.out)?;
+    match &config.neo4j {
+        Some(neo4j) => write_back(context, &adjacency, &embedding, neo4j)?,
+        None => info!("Not writing vectors to Neo4j (embed/write_back is not set)."),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{write_parquet, Adjacency, Embedding, NodeKey};
+    use crate::schema::labels;
+    use arrow_array::{Array, FixedSizeListArray, Float32Array, StringArray};
+    use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+    use std::fs::File;
+
+    fn star() -> Adjacency {
+        let mut adjacency = Adjacency::new();
+        adjacency.add_edge(labels::FACTOR, "a".to_string(), labels::GENE, "b".to_string(), 1.0);
+        adjacency.add_edge(labels::FACTOR, "a".to_string(), labels::GENE, "c".to_string(), 3.0);
+        adjacency
+    }
+
+    #[test]
+    fn adjacency_is_symmetric_and_skips_empty_weights() {
+        let mut adjacency = star();
+        adjacency.add_edge(labels::GENE, "b".to_string(), labels::GENE, "c".to_string(), 0.0);
+        adjacency.add_edge(labels::GENE, "b".to_string(), labels::TRAIT, "d".to_string(),
+                           f64::NAN);
+        let ids: Vec<&str> = adjacency.nodes.iter().map(|node| node.id.as_str()).collect();
+        assert_eq!(ids, ["a", "b", "c", "d"]);
+        assert_eq!(adjacency.n_edges, 2);
+        assert_eq!(adjacency.neighbors, [vec![(1, 1.0), (2, 3.0)], vec![(0, 1.0)],
+                                         vec![(0, 3.0)], vec![]]);
+        let key = NodeKey { label: labels::GENE, id: "c".to_string() };
+        assert_eq!(adjacency.indices[&key], 2);
+    }
+
+    #[test]
+    fn anchors_are_fixed_by_seed() {
+        let dims = 4;
+        let embedding = Embedding::new(20, dims, Some(42));
+        assert_eq!(embedding.anchors, Embedding::new(20, dims, Some(42)).anchors);
+        for (i, &(source, sink)) in embedding.anchors.iter().enumerate() {
+            assert_ne!(source, sink);
+            for (dim, &value) in embedding.vector(i).iter().enumerate() {
+                let expected = if dim == source { 1.0 } else if dim == sink { -1.0 } else { 0.0 };
+                assert_eq!(value, expected);
+            }
+        }
+    }
+
+    #[test]
+    fn iterate_averages_neighbors_and_keeps_anchors() {
+        let adjacency = star();
+        let mut embedding = Embedding {
+            dims: 3,
+            anchors: vec![(0, 1), (1, 2), (2, 0)],
+            vectors: vec![1.0, -1.0, 0.0, 0.0, 1.0, -1.0, -1.0, 0.0, 1.0],
+        };
+        let max_change = embedding.iterate(&adjacency);
+        // a averages b and c with weights 1 and 3: (1 * [0, 1, -1] + 3 * [-1, 0, 1]) / 4
+        // = [-0.75, 0.25, 0.5], then its anchors overwrite the first two dimensions.
+        assert_eq!(embedding.vector(0), [1.0, -1.0, 0.5]);
+        assert_eq!(embedding.vector(1), [1.0, 1.0, -1.0]);
+        assert_eq!(embedding.vector(2), [-1.0, -1.0, 1.0]);
+        assert_eq!(max_change, 1.0);
+    }
+
+    #[test]
+    fn parquet_has_one_row_per_node() {
+        let adjacency = star();
+        let embedding = Embedding::new(adjacency.nodes.len(), 3, Some(7));
+        let out_file =
+            std::env::temp_dir().join(format!("ygramul-embed-{}.parquet", std::process::id()));
+        write_parquet(&adjacency, &embedding, &out_file).unwrap();
+        let reader =
+            ParquetRecordBatchReaderBuilder::try_new(File::open(&out_file).unwrap()).unwrap()
+                .build().unwrap();
+        let batches: Vec<_> = reader.map(Result::unwrap).collect();
+        std::fs::remove_file(&out_file).unwrap();
+        assert_eq!(batches.len(), 1);
+        let batch = &batches[0];
+        let ids = batch.column(1).as_any().downcast_ref::<StringArray>().unwrap();
+        assert_eq!(ids.iter().flatten().collect::<Vec<_>>(), ["a", "b", "c"]);
+        let vectors = batch.column(2).as_any().downcast_ref::<FixedSizeListArray>().unwrap();
+        assert_eq!(vectors.len(), 3);
+        let values = vectors.values().as_any().downcast_ref::<Float32Array>().unwrap();
+        assert_eq!(values.values().as_ref(), embedding.vectors.as_slice());
+    }
+}
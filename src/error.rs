@@ -2,6 +2,7 @@ use std::fmt::{Debug, Display, Formatter};
 use std::num::ParseFloatError;
 use aws_sdk_s3::error::SdkError;
 use aws_sdk_s3::operation::get_object::GetObjectError;
+use aws_sdk_s3::operation::head_object::HeadObjectError;
 use aws_sdk_s3::operation::list_objects_v2::ListObjectsV2Error;
 use aws_sdk_s3::primitives::ByteStreamError;
 use log::SetLoggerError;
@@ -13,9 +14,13 @@ mod errors {
     pub(crate) const NEO4RS_DE_ERROR: &str = "Neo4rs deserialization error";
     pub(crate) const PARSE_FLOAT_ERROR: &str = "Parse float error";
     pub(crate) const GET_OBJECT_ERROR: &str = "Get object error";
+    pub(crate) const HEAD_OBJECT_ERROR: &str = "Head object error";
     pub(crate) const LIST_OBJECTS_V2_ERROR: &str = "List objects v2 error";
     pub(crate) const BYTE_STREAM_ERROR: &str = "Byte stream error";
     pub(crate) const CSV_ERROR: &str = "CSV error";
+    pub(crate) const JSON_ERROR: &str = "JSON error";
+    pub(crate) const ARROW_ERROR: &str = "Arrow error";
+    pub(crate) const PARQUET_ERROR: &str = "Parquet error";
 }
 pub struct Error {
     message: String,
@@ -115,6 +120,12 @@ impl From<SdkError<GetObjectError>> for Error {
     }
 }
 
+impl From<SdkError<HeadObjectError>> for Error {
+    fn from(error: SdkError<HeadObjectError>) -> Self {
+        Error::new(errors::HEAD_OBJECT_ERROR.to_string(), Some(Box::new(error)))
+    }
+}
+
 impl From<SdkError<ListObjectsV2Error>> for Error {
     fn from(error: SdkError<ListObjectsV2Error>) -> Self {
         Error::new(errors::LIST_OBJECTS_V2_ERROR.to_string(), Some(Box::new(error)))
@@ -131,4 +142,22 @@ impl From<csv::Error> for Error {
     fn from(error: csv::Error) -> Self {
         Error::new(errors::CSV_ERROR.to_string(), Some(Box::new(error)))
     }
-}
\ No newline at end of file
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(error: serde_json::Error) -> Self {
+        Error::new(errors::JSON_ERROR.to_string(), Some(Box::new(error)))
+    }
+}
+
+impl From<arrow_schema::ArrowError> for Error {
+    fn from(error: arrow_schema::ArrowError) -> Self {
+        Error::new(errors::ARROW_ERROR.to_string(), Some(Box::new(error)))
+    }
+}
+
+impl From<parquet::errors::ParquetError> for Error {
+    fn from(error: parquet::errors::ParquetError) -> Self {
+        Error::new(errors::PARQUET_ERROR.to_string(), Some(Box::new(error)))
+    }
+}
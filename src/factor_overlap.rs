@@ -0,0 +1,322 @@
+use crate::bulk;
+use crate::bulk::{FactorGene, FactorGeneSet, FactorPheno};
+use crate::config::FactorOverlapConfig;
+use crate::context::AppContext;
+use crate::error::Error;
+use crate::ids::{FactorId, TraitId};
+use log::{info, warn};
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::path::Path;
+
+const GENES_FILE: &str = "factor_overlap_genes.tsv";
+const GENE_SETS_FILE: &str = "factor_overlap_gene_sets.tsv";
+const SIMILAR_FILE: &str = "factor_similar.csv";
+
+struct FactorInfo {
+    id: FactorId,
+    label: String,
+    trait_id: TraitId,
+    trait_rank: usize,
+    n_members: usize,
+    sum_weights: f64,
+}
+
+/// The factors of the selected traits, and for each gene or gene set the factors it belongs
+/// to, with weights. Traits are ranked in the order they were selected, which also orders the
+/// factors of each pair.
+struct Loadings {
+    trait_ranks: HashMap<TraitId, usize>,
+    factors: Vec<FactorInfo>,
+    indices: HashMap<FactorId, usize>,
+    members: HashMap<String, Vec<(usize, f64)>>,
+}
+
+#[derive(Default)]
+struct Intersection {
+    n_intersect: usize,
+    sum1_intersect: f64,
+    sum2_intersect: f64,
+}
+
+#[derive(Serialize)]
+struct OverlapRow {
+    factor1: FactorId,
+    label1: String,
+    trait1: TraitId,
+    factor2: FactorId,
+    label2: String,
+    trait2: TraitId,
+    n_intersect: usize,
+    n_union: usize,
+    overlap: f64,
+    sum1_intersect: f64,
+    sum2_intersect: f64,
+    sum1_union: f64,
+    sum2_union: f64,
+    weighted_overlap: f64,
+}
+
+#[derive(Serialize)]
+struct SimilarRow {
+    factor1: FactorId,
+    factor2: FactorId,
+    gene_overlap: f64,
+    gene_weighted_overlap: f64,
+    gene_set_overlap: f64,
+    gene_set_weighted_overlap: f64,
+}
+
+impl Loadings {
+    fn new(factor_phenos: &[FactorPheno], traits: &[TraitId]) -> Loadings {
+        let trait_ranks = traits.iter().cloned().enumerate().map(|(rank, id)| (id, rank))
+            .collect();
+        let mut loadings = Loadings {
+            trait_ranks, factors: Vec::new(), indices: HashMap::new(), members: HashMap::new()
+        };
+        for factor_pheno in factor_phenos {
+            let FactorPheno { factor, label, pheno, .. } = factor_pheno;
+            if let Some(&trait_rank) = loadings.trait_ranks.get(pheno) {
+                loadings.add_factor(factor, label, pheno, trait_rank);
+            }
+        }
+        loadings
+    }
+    fn add_factor(&mut self, factor: &FactorId, label: &str, trait_id: &TraitId,
+                  trait_rank: usize) -> usize {
+        match self.indices.get(factor) {
+            Some(&i) => i,
+            None => {
+                let i = self.factors.len();
+                self.factors.push(FactorInfo {
+                    id: factor.clone(),
+                    label: label.to_string(),
+                    trait_id: trait_id.clone(),
+                    trait_rank,
+                    n_members: 0,
+                    sum_weights: 0.0,
+                });
+                self.indices.insert(factor.clone(), i);
+                i
+            }
+        }
+    }
+    fn add(&mut self, factor: &FactorId, member: String, weight: f64) {
+        let i = match self.indices.get(factor) {
+            Some(&i) => i,
+            None => match self.trait_ranks.get(&factor.trait_id) {
+                Some(&trait_rank) => self.add_factor(factor, "", &factor.trait_id, trait_rank),
+                None => return,
+            },
+        };
+        let weight = if weight.is_finite() { weight } else { 0.0 };
+        let factor = &mut self.factors[i];
+        factor.n_members += 1;
+        factor.sum_weights += weight;
+        self.members.entry(member).or_default().push((i, weight));
+    }
+    fn overlaps(&self) -> Vec<OverlapRow> {
+        let mut intersections: HashMap<(usize, usize), Intersection> = HashMap::new();
+        for factors in self.members.values() {
+            for &(i1, weight1) in factors {
+                for &(i2, weight2) in factors {
+                    if self.is_pair(i1, i2) {
+                        let intersection = intersections.entry((i1, i2)).or_default();
+                        intersection.n_intersect += 1;
+                        intersection.sum1_intersect += weight1;
+                        intersection.sum2_intersect += weight2;
+                    }
+                }
+            }
+        }
+        let mut rows: Vec<OverlapRow> =
+            intersections.into_iter().map(|((i1, i2), intersection)| {
+                self.overlap_row(i1, i2, intersection)
+            }).collect();
+        rows.sort_by(|row1, row2| {
+            row2.overlap.total_cmp(&row1.overlap)
+                .then(row2.weighted_overlap.total_cmp(&row1.weighted_overlap))
+                .then_with(|| row1.factor1.cmp(&row2.factor1))
+                .then_with(|| row1.factor2.cmp(&row2.factor2))
+        });
+        rows
+    }
+    fn is_pair(&self, i1: usize, i2: usize) -> bool {
+        self.factors[i1].trait_rank < self.factors[i2].trait_rank
+    }
+    fn overlap_row(&self, i1: usize, i2: usize, intersection: Intersection) -> OverlapRow {
+        let Intersection { n_intersect, sum1_intersect, sum2_intersect } = intersection;
+        let factor1 = &self.factors[i1];
+        let factor2 = &self.factors[i2];
+        let n_union = factor1.n_members + factor2.n_members - n_intersect;
+        let overlap = (n_intersect as f64) / (n_union as f64);
+        let sum1_union = factor1.sum_weights;
+        let sum2_union = factor2.sum_weights;
+        let weighted_overlap =
+            (sum1_intersect * sum2_intersect) / (sum1_union * sum2_union);
+        OverlapRow {
+            factor1: factor1.id.clone(),
+            label1: factor1.label.clone(),
+            trait1: factor1.trait_id.clone(),
+            factor2: factor2.id.clone(),
+            label2: factor2.label.clone(),
+            trait2: factor2.trait_id.clone(),
+            n_intersect,
+            n_union,
+            overlap,
+            sum1_intersect,
+            sum2_intersect,
+            sum1_union,
+            sum2_union,
+            weighted_overlap,
+        }
+    }
+}
+
+/// The traits in the order given, or all traits with factors in lexical order.
+fn selected_traits(config: &FactorOverlapConfig, factor_phenos: &[FactorPheno])
+    -> Result<Vec<TraitId>, Error> {
+    let known: BTreeSet<TraitId> =
+        factor_phenos.iter().map(|factor_pheno| factor_pheno.pheno.clone()).collect();
+    if config.traits.is_empty() {
+        info!("No traits specified, using all {} traits with factors.", known.len());
+        return Ok(known.into_iter().collect());
+    }
+    for trait_id in &config.traits {
+        if !known.contains(trait_id) {
+            warn!("Trait {trait_id} has no factors in {}.", config.bulk_dir);
+        }
+    }
+    let mut traits: Vec<TraitId> = Vec::new();
+    for trait_id in &config.traits {
+        if !traits.contains(trait_id) {
+            traits.push(trait_id.clone());
+        }
+    }
+    if traits.len() < 2 {
+        Err(Error::from("Need at least two traits to compute factor overlap."))?
+    }
+    Ok(traits)
+}
+
+fn write_overlaps(rows: &[OverlapRow], out_file: &Path) -> Result<(), Error> {
+    info!("Writing {} factor pairs to {}", rows.len(), out_file.display());
+    let mut writer = csv::WriterBuilder::new().delimiter(b'\t').from_path(out_file)?;
+    for row in rows {
+        writer.serialize(row)?;
+    }
+    Ok(())
+}
+
+fn write_similar(gene_rows: &[OverlapRow], gene_set_rows: &[OverlapRow], min_similarity: f64,
+                 out_file: &Path) -> Result<(), Error> {
+    let mut pairs: BTreeMap<(FactorId, FactorId), SimilarRow> = BTreeMap::new();
+    for (rows, is_genes) in [(gene_rows, true), (gene_set_rows, false)] {
+        for row in rows {
+            let key = (row.factor1.clone(), row.factor2.clone());
+            let similar = pairs.entry(key).or_insert_with(|| SimilarRow {
+                factor1: row.factor1.clone(),
+                factor2: row.factor2.clone(),
+                gene_overlap: 0.0,
+                gene_weighted_overlap: 0.0,
+                gene_set_overlap: 0.0,
+                gene_set_weighted_overlap: 0.0,
+            });
+            if is_genes {
+                similar.gene_overlap = row.overlap;
+                similar.gene_weighted_overlap = row.weighted_overlap;
+            } else {
+                similar.gene_set_overlap = row.overlap;
+                similar.gene_set_weighted_overlap = row.weighted_overlap;
+            }
+        }
+    }
+    let mut writer = csv::Writer::from_path(out_file)?;
+    let mut n_edges: usize = 0;
+    for similar in pairs.into_values() {
+        if similar.gene_overlap.max(similar.gene_set_overlap) >= min_similarity {
+            writer.serialize(similar)?;
+            n_edges += 1;
+        }
+    }
+    info!("Wrote {n_edges} FACTOR_SIMILAR edges with similarity at least {min_similarity} to {}",
+        out_file.display());
+    Ok(())
+}
+
+pub(crate) fn factor_overlap(context: &AppContext, config: &FactorOverlapConfig)
+    -> Result<(), Error> {
+    let factor_phenos = bulk::read_rows::<FactorPheno>(context, &config.bulk_dir)?;
+    let traits = selected_traits(config, &factor_phenos)?;
+    let mut gene_loadings = Loadings::new(&factor_phenos, &traits);
+    bulk::for_each_row(context, &config.bulk_dir, |row: FactorGene| {
+        gene_loadings.add(&row.factor, row.gene.to_string(), row.weight);
+        Ok(())
+    })?;
+    let mut gene_set_loadings = Loadings::new(&factor_phenos, &traits);
+    bulk::for_each_row(context, &config.bulk_dir, |row: FactorGeneSet| {
+        gene_set_loadings.add(&row.factor, row.gene_set.to_string(), row.weight);
+        Ok(())
+    })?;
+    info!("Computing overlap between {} factors of {} traits.", gene_loadings.factors.len(),
+        traits.len());
+    let out_dir = Path::new(&config.out);
+    std::fs::create_dir_all(out_dir)?;
+    let gene_rows = gene_loadings.overlaps();
+    write_overlaps(&gene_rows, &out_dir.join(GENES_FILE))?;
+    let gene_set_rows = gene_set_loadings.overlaps();
+    write_overlaps(&gene_set_rows, &out_dir.join(GENE_SETS_FILE))?;
+    match config.min_similarity {
+        Some(min_similarity) => {
+            write_similar(&gene_rows, &gene_set_rows, min_similarity,
+                          &out_dir.join(SIMILAR_FILE))?;
+        }
+        None => info!("No minimum similarity specified, not writing FACTOR_SIMILAR edges."),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Loadings;
+    use crate::bulk::FactorPheno;
+    use crate::ids::{FactorId, TraitId};
+
+    fn factor(id: &str) -> FactorId {
+        id.parse().unwrap()
+    }
+
+    #[test]
+    fn overlaps_follow_trait_order() {
+        let factor_phenos: Vec<FactorPheno> = ["F1_T2D", "F2_T2D", "F1_AD", "F1_T1D"].iter()
+            .map(|id| FactorPheno {
+                factor: factor(id), label: id.to_lowercase(), pheno: factor(id).trait_id,
+                any_relevance: 1.0,
+            }).collect();
+        let traits = [TraitId::new("T2D".to_string()), TraitId::new("AD".to_string())];
+        let mut loadings = Loadings::new(&factor_phenos, &traits);
+        let members = [
+            ("F1_T2D", "APOE", 1.0), ("F1_T2D", "LDLR", 0.5), ("F1_T2D", "PCSK9", 0.5),
+            ("F2_T2D", "APOE", 1.0), ("F1_AD", "APOE", 0.5), ("F1_AD", "CLU", 1.0),
+            ("F1_T1D", "APOE", 1.0),
+        ];
+        for (id, gene, weight) in members {
+            loadings.add(&factor(id), gene.to_string(), weight);
+        }
+        let rows = loadings.overlaps();
+        let pairs: Vec<(String, String)> = rows.iter()
+            .map(|row| (row.factor1.to_string(), row.factor2.to_string())).collect();
+        assert_eq!(pairs, [("F2_T2D".to_string(), "F1_AD".to_string()),
+                           ("F1_T2D".to_string(), "F1_AD".to_string())]);
+        // F1_T2D and F1_AD share APOE out of APOE, LDLR, PCSK9 and CLU, with weights 1 and
+        // 0.5 out of totals of 2 and 1.5.
+        let row = &rows[1];
+        assert_eq!((row.label1.as_str(), row.label2.as_str()), ("f1_t2d", "f1_ad"));
+        assert_eq!((row.n_intersect, row.n_union), (1, 4));
+        assert_eq!(row.overlap, 0.25);
+        assert_eq!((row.sum1_intersect, row.sum2_intersect), (1.0, 0.5));
+        assert_eq!((row.sum1_union, row.sum2_union), (2.0, 1.5));
+        assert_eq!(row.weighted_overlap, 0.5 / 3.0);
+        assert_eq!((rows[0].overlap, rows[0].weighted_overlap), (0.5, 0.5 / 1.5));
+    }
+}
@@ -1,4 +1,16 @@
-use crate::config::LocalConfig;
+use crate::config::HelloConfig;
 
-pub(crate) fn hello(config: &LocalConfig) {
-    println!("Data directory: {}", config.data_dir.display())}
\ No newline at end of file
+pub(crate) fn hello(config: &HelloConfig) {
+    let local_config = &config.local_config;
+    println!("Data directory: {}", local_config.data_dir.display());
+    if let Some(uri) = &config.neo4j_uri {
+        println!("Neo4j URI: {uri}")
+    }
+    if let Some(user) = &config.neo4j_user {
+        println!("Neo4j user: {user}")
+    }
+    match &config.password_source {
+        Some(source) => println!("Neo4j password from: {source}"),
+        None => println!("Neo4j password: none available"),
+    }
+}
@@ -0,0 +1,331 @@
+use crate::context::AppContext;
+use crate::error::Error;
+use crate::s3;
+use crate::s3::FilePath;
+use crate::tsv::{TsvConsumer, TsvEater, TsvEaterMaker};
+use log::{info, warn};
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::path::Path;
+
+mod fields {
+    pub(crate) const HGNC_ID: &str = "hgnc_id";
+    pub(crate) const SYMBOL: &str = "symbol";
+    pub(crate) const ALIAS_SYMBOL: &str = "alias_symbol";
+    pub(crate) const PREV_SYMBOL: &str = "prev_symbol";
+    pub(crate) const ENSEMBL_GENE_ID: &str = "ensembl_gene_id";
+    pub(crate) const ENTREZ_ID: &str = "entrez_id";
+}
+
+const N_UNMAPPED_LOGGED: usize = 20;
+
+pub(crate) struct HgncGene {
+    pub(crate) symbol: String,
+    pub(crate) hgnc_id: String,
+    pub(crate) ensembl_id: String,
+    pub(crate) entrez_id: String,
+    aliases: Vec<String>,
+    previous: Vec<String>,
+}
+
+#[derive(Serialize)]
+struct GeneIdsRow<'a> {
+    gene: &'a str,
+    hgnc_id: &'a str,
+    ensembl_id: &'a str,
+    entrez_id: &'a str,
+}
+
+struct HgncTable {
+    genes: Vec<HgncGene>,
+    by_symbol: HashMap<String, usize>,
+    by_previous: HashMap<String, BTreeSet<usize>>,
+    by_alias: HashMap<String, BTreeSet<usize>>,
+}
+
+enum Mapping {
+    Mapped(usize),
+    Ambiguous,
+    Unknown,
+}
+
+pub(crate) struct GeneNormalizer {
+    table: Option<HgncTable>,
+    seen: BTreeSet<usize>,
+    unmapped: BTreeMap<String, usize>,
+    ambiguous: BTreeMap<String, usize>,
+    n_renamed: usize,
+}
+
+struct HgncTsvEater {
+    symbol: Option<String>,
+    hgnc_id: String,
+    ensembl_id: String,
+    entrez_id: String,
+    aliases: Vec<String>,
+    previous: Vec<String>,
+}
+
+struct HgncTsvEaterMaker {}
+
+impl HgncTsvEater {
+    fn new() -> Self {
+        HgncTsvEater {
+            symbol: None,
+            hgnc_id: String::new(),
+            ensembl_id: String::new(),
+            entrez_id: String::new(),
+            aliases: Vec::new(),
+            previous: Vec::new(),
+        }
+    }
+}
+
+fn split_symbols(value: &str) -> Vec<String> {
+    value.trim_matches('"').split('|').map(|symbol| symbol.trim())
+        .filter(|symbol| !symbol.is_empty()).map(|symbol| symbol.to_string()).collect()
+}
+
+impl TsvEater for HgncTsvEater {
+    type Row = HgncGene;
+
+    fn field(&mut self, name: &str, value: &str) -> Result<(), Error> {
+        match name {
+            fields::SYMBOL => self.symbol = Some(value.to_string()),
+            fields::HGNC_ID => self.hgnc_id = value.to_string(),
+            fields::ENSEMBL_GENE_ID => self.ensembl_id = value.to_string(),
+            fields::ENTREZ_ID => self.entrez_id = value.to_string(),
+            fields::ALIAS_SYMBOL => self.aliases = split_symbols(value),
+            fields::PREV_SYMBOL => self.previous = split_symbols(value),
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn finish(self) -> Result<Self::Row, Error> {
+        let HgncTsvEater { symbol, hgnc_id, ensembl_id, entrez_id, aliases, previous } = self;
+        let symbol = symbol.ok_or_else(|| Error::from("Missing HGNC symbol"))?;
+        Ok(HgncGene { symbol, hgnc_id, ensembl_id, entrez_id, aliases, previous })
+    }
+}
+
+impl TsvEaterMaker for HgncTsvEaterMaker {
+    type Row = HgncGene;
+    type Eater = HgncTsvEater;
+
+    fn make(&self) -> Self::Eater {
+        HgncTsvEater::new()
+    }
+}
+
+fn symbol_key(symbol: &str) -> String {
+    symbol.trim().to_uppercase()
+}
+
+impl HgncTable {
+    fn new(genes: Vec<HgncGene>) -> HgncTable {
+        let mut by_symbol: HashMap<String, usize> = HashMap::new();
+        let mut by_previous: HashMap<String, BTreeSet<usize>> = HashMap::new();
+        let mut by_alias: HashMap<String, BTreeSet<usize>> = HashMap::new();
+        for (i, gene) in genes.iter().enumerate() {
+            by_symbol.insert(symbol_key(&gene.symbol), i);
+            for previous in &gene.previous {
+                by_previous.entry(symbol_key(previous)).or_default().insert(i);
+            }
+            for alias in &gene.aliases {
+                by_alias.entry(symbol_key(alias)).or_default().insert(i);
+            }
+        }
+        HgncTable { genes, by_symbol, by_previous, by_alias }
+    }
+    fn map(&self, symbol: &str) -> Mapping {
+        let key = symbol_key(symbol);
+        if let Some(i) = self.by_symbol.get(&key) {
+            return Mapping::Mapped(*i);
+        }
+        for candidates in [self.by_previous.get(&key), self.by_alias.get(&key)].into_iter().flatten() {
+            let mut iter = candidates.iter();
+            match (iter.next(), iter.next()) {
+                (Some(i), None) => return Mapping::Mapped(*i),
+                (Some(_), Some(_)) => return Mapping::Ambiguous,
+                _ => {}
+            }
+        }
+        Mapping::Unknown
+    }
+}
+
+impl GeneNormalizer {
+    pub(crate) fn identity() -> GeneNormalizer {
+        GeneNormalizer {
+            table: None,
+            seen: BTreeSet::new(),
+            unmapped: BTreeMap::new(),
+            ambiguous: BTreeMap::new(),
+            n_renamed: 0,
+        }
+    }
+    pub(crate) fn load(context: &AppContext, file: &str) -> Result<GeneNormalizer, Error> {
+        info!("Loading HGNC gene table from {file}");
+        let mut genes: Vec<HgncGene> = Vec::new();
+        let mut tsv_consumer = TsvConsumer::new('\t', HgncTsvEaterMaker {}, |gene| {
+            genes.push(gene);
+            Ok(())
+        });
+        let file_path = FilePath::from_path(file)?;
+        s3::process_file(context, &file_path, &mut tsv_consumer)
+            .map_err(|e| Error::wrap(format!("Failed to load HGNC table {file}"), e))?;
+        info!("Loaded {} approved genes from HGNC table", genes.len());
+        Ok(GeneNormalizer::with_genes(genes))
+    }
+    fn with_genes(genes: Vec<HgncGene>) -> GeneNormalizer {
+        let mut normalizer = GeneNormalizer::identity();
+        normalizer.table = Some(HgncTable::new(genes));
+        normalizer
+    }
+    pub(crate) fn for_file(context: &AppContext, file: Option<&str>)
+        -> Result<GeneNormalizer, Error> {
+        match file {
+            Some(file) => GeneNormalizer::load(context, file),
+            None => Ok(GeneNormalizer::identity()),
+        }
+    }
+    pub(crate) fn normalize(&mut self, symbol: &str) -> String {
+        let Some(table) = &self.table else {
+            return symbol.to_string();
+        };
+        match table.map(symbol) {
+            Mapping::Mapped(i) => {
+                self.seen.insert(i);
+                let approved = &table.genes[i].symbol;
+                if approved != symbol {
+                    self.n_renamed += 1;
+                }
+                approved.clone()
+            }
+            Mapping::Ambiguous => {
+                *self.ambiguous.entry(symbol.to_string()).or_default() += 1;
+                symbol.to_string()
+            }
+            Mapping::Unknown => {
+                *self.unmapped.entry(symbol.to_string()).or_default() += 1;
+                symbol.to_string()
+            }
+        }
+    }
+    pub(crate) fn log_summary(&self) {
+        if self.table.is_none() {
+            return;
+        }
+        info!("Mapped {} distinct genes to approved HGNC symbols, renaming {} occurrences",
+            self.seen.len(), self.n_renamed);
+        log_unmapped("ambiguous", &self.ambiguous);
+        log_unmapped("unknown", &self.unmapped);
+    }
+    pub(crate) fn write_ids(&self, out_file: &Path) -> Result<(), Error> {
+        let Some(table) = &self.table else {
+            return Ok(());
+        };
+        info!("Writing gene ids file to {}", out_file.display());
+        let mut writer = csv::Writer::from_path(out_file)?;
+        for &i in &self.seen {
+            let gene = &table.genes[i];
+            writer.serialize(GeneIdsRow {
+                gene: &gene.symbol,
+                hgnc_id: &gene.hgnc_id,
+                ensembl_id: &gene.ensembl_id,
+                entrez_id: &gene.entrez_id,
+            })?;
+        }
+        Ok(())
+    }
+}
+
+fn log_unmapped(kind: &str, genes: &BTreeMap<String, usize>) {
+    if genes.is_empty() {
+        return;
+    }
+    let n_occurrences: usize = genes.values().sum();
+    warn!("{} distinct genes ({} occurrences) are {} in the HGNC table and were kept as is.",
+        genes.len(), n_occurrences, kind);
+    for (gene, count) in genes.iter().take(N_UNMAPPED_LOGGED) {
+        warn!("Unmappable gene ({kind}): {gene} ({count} times)");
+    }
+    if genes.len() > N_UNMAPPED_LOGGED {
+        warn!("... and {} more {} genes", genes.len() - N_UNMAPPED_LOGGED, kind);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{GeneNormalizer, HgncTsvEaterMaker};
+    use crate::tsv::TsvReader;
+    use std::io::BufReader;
+
+    const HGNC_TSV: &str = "\
+hgnc_id\tsymbol\talias_symbol\tprev_symbol\tensembl_gene_id\tentrez_id
+HGNC:613\tAPOE\t\"AD2|NARC1|LPG\"\t\tENSG00000130203\t348
+HGNC:6547\tLDLR\tAD2\tFH\tENSG00000130164\t3949
+HGNC:20001\tPCSK9\t\tNARC1\tENSG00000169174\t255738
+HGNC:3700\tFH\t\t\tENSG00000091483\t2271
+";
+
+    fn normalizer() -> GeneNormalizer {
+        let reader =
+            TsvReader::new(BufReader::new(HGNC_TSV.as_bytes()), '\t', HgncTsvEaterMaker {})
+                .unwrap();
+        GeneNormalizer::with_genes(reader.collect::<Result<_, _>>().unwrap())
+    }
+
+    #[test]
+    fn symbol_then_previous_then_alias() {
+        let mut genes = normalizer();
+        // FH is an approved symbol and a previous symbol of LDLR.
+        assert_eq!(genes.normalize("FH"), "FH");
+        // NARC1 is a previous symbol of PCSK9 and an alias of APOE.
+        assert_eq!(genes.normalize("NARC1"), "PCSK9");
+        assert_eq!(genes.normalize("LPG"), "APOE");
+        assert_eq!(genes.normalize("XYZ"), "XYZ");
+    }
+
+    #[test]
+    fn case_insensitive() {
+        let mut genes = normalizer();
+        assert_eq!(genes.normalize("apoe"), "APOE");
+        assert_eq!(genes.normalize("Narc1"), "PCSK9");
+        assert_eq!(genes.normalize("lpg"), "APOE");
+        assert_eq!(genes.n_renamed, 3);
+        assert_eq!(genes.normalize("APOE"), "APOE");
+        assert_eq!(genes.n_renamed, 3);
+    }
+
+    #[test]
+    fn ambiguous_aliases_are_kept_and_counted() {
+        let mut genes = normalizer();
+        for symbol in ["AD2", "ad2", "AD2", "XYZ"] {
+            assert_eq!(genes.normalize(symbol), symbol);
+        }
+        let ambiguous: Vec<(&str, usize)> =
+            genes.ambiguous.iter().map(|(gene, count)| (gene.as_str(), *count)).collect();
+        assert_eq!(ambiguous, [("AD2", 2), ("ad2", 1)]);
+        assert_eq!(genes.unmapped.get("XYZ"), Some(&1));
+        assert!(genes.seen.is_empty());
+    }
+
+    #[test]
+    fn ids_of_seen_genes() {
+        let mut genes = normalizer();
+        for symbol in ["fh", "NARC1", "LPG", "APOE", "AD2"] {
+            genes.normalize(symbol);
+        }
+        let out_file =
+            std::env::temp_dir().join(format!("ygramul-hgnc-{}.csv", std::process::id()));
+        genes.write_ids(&out_file).unwrap();
+        let ids = std::fs::read_to_string(&out_file).unwrap();
+        std::fs::remove_file(&out_file).unwrap();
+        assert_eq!(ids, "gene,hgnc_id,ensembl_id,entrez_id\n\
+            APOE,HGNC:613,ENSG00000130203,348\n\
+            PCSK9,HGNC:20001,ENSG00000169174,255738\n\
+            FH,HGNC:3700,ENSG00000091483,2271\n");
+    }
+}
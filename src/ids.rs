@@ -0,0 +1,205 @@
+use crate::error::Error;
+use serde::de::Visitor;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+const ESCAPE: char = '%';
+const FACTOR_SEPARATOR: char = '_';
+const TRAIT_GENE_SET_SEPARATOR: char = ':';
+
+macro_rules! string_id {
+    ($name:ident) => {
+        #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+        pub(crate) struct $name(String);
+
+        impl $name {
+            pub(crate) fn new(id: String) -> Self {
+                $name(id)
+            }
+            pub(crate) fn as_str(&self) -> &str {
+                &self.0
+            }
+        }
+
+        impl Display for $name {
+            fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+                write!(f, "{}", self.0)
+            }
+        }
+
+        impl FromStr for $name {
+            type Err = Error;
+
+            fn from_str(string: &str) -> Result<Self, Self::Err> {
+                Ok($name(string.to_string()))
+            }
+        }
+    };
+}
+
+string_id!(TraitId);
+string_id!(GeneId);
+string_id!(GeneSetId);
+
+/// A factor of a trait, written as `<factor>_<trait>`, e.g. `Factor1_T2D`. The factor part is
+/// escaped, so the trait part may contain any character.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub(crate) struct FactorId {
+    pub(crate) factor: String,
+    pub(crate) trait_id: TraitId,
+}
+
+/// A gene set in the context of a trait, written as `<gene_set>:<trait>`. The gene set part is
+/// escaped, so the trait part may contain any character.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub(crate) struct TraitGeneSetId {
+    pub(crate) gene_set: GeneSetId,
+    pub(crate) trait_id: TraitId,
+}
+
+impl FactorId {
+    pub(crate) fn new(factor: String, trait_id: TraitId) -> FactorId {
+        FactorId { factor, trait_id }
+    }
+}
+
+impl TraitGeneSetId {
+    pub(crate) fn new(gene_set: GeneSetId, trait_id: TraitId) -> TraitGeneSetId {
+        TraitGeneSetId { gene_set, trait_id }
+    }
+}
+
+fn escape(string: &str, separator: char) -> String {
+    let mut escaped = String::with_capacity(string.len());
+    for c in string.chars() {
+        if c == ESCAPE || c == separator {
+            escaped.push_str(&format!("{ESCAPE}{:02X}", c as u32));
+        } else {
+            escaped.push(c);
+        }
+    }
+    escaped
+}
+
+fn unescape(string: &str) -> Result<String, Error> {
+    let mut unescaped = String::with_capacity(string.len());
+    let mut chars = string.chars();
+    while let Some(c) = chars.next() {
+        if c == ESCAPE {
+            let code: String = chars.by_ref().take(2).collect();
+            let c = u8::from_str_radix(&code, 16).ok().filter(|_| code.len() == 2)
+                .ok_or_else(|| Error::from(format!("Invalid escape sequence in '{string}'.")))?;
+            unescaped.push(c as char);
+        } else {
+            unescaped.push(c);
+        }
+    }
+    Ok(unescaped)
+}
+
+impl Display for FactorId {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let factor = escape(&self.factor, FACTOR_SEPARATOR);
+        if self.trait_id.as_str().is_empty() {
+            write!(f, "{factor}")
+        } else {
+            write!(f, "{}{}{}", factor, FACTOR_SEPARATOR, self.trait_id)
+        }
+    }
+}
+
+impl FromStr for FactorId {
+    type Err = Error;
+
+    fn from_str(string: &str) -> Result<Self, Self::Err> {
+        let (factor, trait_id) =
+            string.split_once(FACTOR_SEPARATOR).unwrap_or((string, ""));
+        Ok(FactorId::new(unescape(factor)?, TraitId::new(trait_id.to_string())))
+    }
+}
+
+impl Display for TraitGeneSetId {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let gene_set = escape(self.gene_set.as_str(), TRAIT_GENE_SET_SEPARATOR);
+        write!(f, "{}{}{}", gene_set, TRAIT_GENE_SET_SEPARATOR, self.trait_id)
+    }
+}
+
+impl FromStr for TraitGeneSetId {
+    type Err = Error;
+
+    fn from_str(string: &str) -> Result<Self, Self::Err> {
+        let (gene_set, trait_id) =
+            string.split_once(TRAIT_GENE_SET_SEPARATOR).ok_or_else(|| {
+                Error::from(format!("Trait gene set id '{string}' is missing a trait."))
+            })?;
+        let gene_set = GeneSetId::new(unescape(gene_set)?);
+        Ok(TraitGeneSetId::new(gene_set, TraitId::new(trait_id.to_string())))
+    }
+}
+
+macro_rules! serde_via_string {
+    ($name:ident) => {
+        impl Serialize for $name {
+            fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                serializer.collect_str(self)
+            }
+        }
+
+        impl<'de> Deserialize<'de> for $name {
+            fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                struct IdVisitor;
+                impl Visitor<'_> for IdVisitor {
+                    type Value = $name;
+                    fn expecting(&self, f: &mut Formatter) -> std::fmt::Result {
+                        write!(f, "a string")
+                    }
+                    fn visit_str<E: serde::de::Error>(self, value: &str) -> Result<$name, E> {
+                        value.parse().map_err(E::custom)
+                    }
+                }
+                deserializer.deserialize_str(IdVisitor)
+            }
+        }
+    };
+}
+
+serde_via_string!(TraitId);
+serde_via_string!(GeneId);
+serde_via_string!(GeneSetId);
+serde_via_string!(FactorId);
+serde_via_string!(TraitGeneSetId);
+
+#[cfg(test)]
+mod tests {
+    use super::{escape, unescape, FactorId, GeneSetId, TraitGeneSetId, TraitId};
+
+    #[test]
+    fn escape_round_trip() {
+        for string in ["plain", "a:b", "a_b", "100%", "%3A", "a%25:_b", ""] {
+            let escaped = escape(string, ':');
+            assert!(!escaped.contains(':'));
+            assert_eq!(unescape(&escaped).unwrap(), string);
+        }
+        assert_eq!(escape("a:b%", ':'), "a%3Ab%25");
+        assert!(unescape("a%3").is_err());
+        assert!(unescape("a%zz").is_err());
+    }
+
+    #[test]
+    fn factor_id_round_trip() {
+        let id = FactorId::new("Factor_1".to_string(), TraitId::new("T2D_adj".to_string()));
+        assert_eq!(id.to_string(), "Factor%5F1_T2D_adj");
+        assert_eq!(id.to_string().parse::<FactorId>().unwrap(), id);
+    }
+
+    #[test]
+    fn trait_gene_set_id_round_trip() {
+        let gene_set = GeneSetId::new("GO:0008150".to_string());
+        let id = TraitGeneSetId::new(gene_set, TraitId::new("T2D".to_string()));
+        assert_eq!(id.to_string(), "GO%3A0008150:T2D");
+        assert_eq!(id.to_string().parse::<TraitGeneSetId>().unwrap(), id);
+        assert!("no_trait".parse::<TraitGeneSetId>().is_err());
+    }
+}
@@ -1,10 +1,13 @@
 use crate::config::ActionConfig;
+use crate::context::AppContext;
 use crate::error::Error;
 
 pub mod cli;
 pub mod config;
 pub mod error;
 mod file_info;
+mod hgnc;
+mod ids;
 mod hello;
 mod neo;
 mod ping;
@@ -14,22 +17,65 @@ mod wipe;
 mod tsv;
 mod s3;
 mod pigean;
+mod stats;
+mod table;
+mod bulk;
+mod factor_overlap;
+mod compare_traits;
+mod embed;
+mod schema;
+mod similar;
+mod meta_factors;
+mod output;
+mod query;
+mod lookup;
+mod subgraph;
+mod terms;
+mod serve;
+mod shell;
+mod store;
+mod password;
+mod status;
+mod check;
+mod ls;
+mod cache;
+pub mod context;
 
-pub fn execute(config: &ActionConfig) -> Result<(), Error> {
+pub fn execute(context: &AppContext, config: &ActionConfig) -> Result<(), Error> {
     match config {
         ActionConfig::Hello(config) => hello::hello(config),
         ActionConfig::Survey(config) => {
             survey::survey(config)?;
         }
-        ActionConfig::Ping(config) => ping::ping_neo4j(config)?,
-        ActionConfig::Upload(config) => upload::upload_data(config)?,
-        ActionConfig::Wipe(config) => wipe::wipe(config)?,
-        ActionConfig::Cat(config) => s3::cat(config)?,
-        ActionConfig::Ls(config) => s3::ls(config)?,
-        ActionConfig::Bulk(config) => pigean::phenos::create_bulk_files(config)?,
-        ActionConfig::Factors(config) => pigean::factors::create_bulk_files(config)?,
+        ActionConfig::Ping(config) => ping::ping_neo4j(context, config)?,
+        ActionConfig::Upload(config) => upload::upload_data(context, config)?,
+        ActionConfig::Wipe(config) => wipe::wipe(context, config)?,
+        ActionConfig::Cat(config) => s3::cat(context, config)?,
+        ActionConfig::Ls(config) => ls::ls(context, config)?,
+        ActionConfig::Bulk(config) => pigean::phenos::create_bulk_files(context, config)?,
+        ActionConfig::Factors(config) => pigean::factors::create_bulk_files(context, config)?,
         ActionConfig::TraitGeneSets(config) =>
-            pigean::pgs::create_bulk_files(config)?,
+            pigean::pgs::create_bulk_files(context, config)?,
+        ActionConfig::Stats(config) => stats::stats(context, config)?,
+        ActionConfig::FactorOverlap(config) => factor_overlap::factor_overlap(context, config)?,
+        ActionConfig::CompareTraits(config) => compare_traits::compare_traits(context, config)?,
+        ActionConfig::Embed(config) => embed::embed(context, config)?,
+        ActionConfig::VectorIndex(config) => similar::create_vector_indexes(context, config)?,
+        ActionConfig::Similar(config) => similar::similar(context, config)?,
+        ActionConfig::MetaFactors(config) => meta_factors::meta_factors(context, config)?,
+        ActionConfig::Query(config) => query::query(context, config)?,
+        ActionConfig::GenesForTrait(config) => lookup::genes_for_trait(context, config)?,
+        ActionConfig::GeneSetsForTrait(config) => lookup::gene_sets_for_trait(context, config)?,
+        ActionConfig::FactorsForTrait(config) => lookup::factors_for_trait(context, config)?,
+        ActionConfig::FactorsForGene(config) => lookup::factors_for_gene(context, config)?,
+        ActionConfig::FactorsForGeneSet(config) => lookup::factors_for_gene_set(context, config)?,
+        ActionConfig::ExportSubgraph(config) => subgraph::export_subgraph(context, config)?,
+        ActionConfig::ExportTerms(config) => terms::export_terms(context, config)?,
+        ActionConfig::Serve(config) => serve::serve(context, config)?,
+        ActionConfig::Shell(config) => shell::shell(context, config)?,
+        ActionConfig::Status(config) => status::status(context, config)?,
+        ActionConfig::Check(config) => check::check(context, config)?,
+        ActionConfig::Cache(config) => cache::cache(context, config)?,
     }
     Ok(())
 }
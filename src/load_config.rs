@@ -14,7 +14,7 @@ pub(crate) fn load_config() -> Result<ConfigBuilder, Error> {
             .map_err(|io_error|
                 Error::wrap(config_file.to_string_lossy().to_string(), io_error)
             )?;
-    let config = ConfigBuilder::try_from(config_toml.as_str())?;
+    let config = ConfigBuilder::try_from(config_toml.as_str())?.with_app_dir(app_dir);
     Ok(config)
 }
 
@@ -0,0 +1,287 @@
+use crate::config::LookupConfig;
+use crate::context::AppContext;
+use crate::error::Error;
+use crate::neo::{Neo, RowEater};
+use crate::output;
+use crate::output::RecordWriter;
+use crate::schema::{labels, rel_types};
+use crate::store;
+use log::info;
+use neo4rs::{query, Row};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+const KEY_ID: &str = "id";
+const KEY_SKIP: &str = "skip";
+const KEY_LIMIT: &str = "limit";
+
+mod matches {
+    pub(crate) const GENES_FOR_TRAIT: &str =
+        "MATCH (t:Trait { id: $id })-[e:TRAIT_GENE]->(g:Gene)";
+    pub(crate) const GENE_SETS_FOR_TRAIT: &str =
+        "MATCH (t:Trait { id: $id })-[e:TRAIT_GENE_SET]->(gs:GeneSet)";
+    pub(crate) const FACTORS_FOR_TRAIT: &str =
+        "MATCH (t:Trait { id: $id })-[e:TRAIT_FACTOR]->(f:Factor)";
+    pub(crate) const FACTORS_FOR_GENE: &str =
+        "MATCH (f:Factor)-[e:FACTOR_GENE]->(g:Gene { id: $id })";
+    pub(crate) const FACTORS_FOR_GENE_SET: &str =
+        "MATCH (f:Factor)-[e:FACTOR_GENE_SET]->(gs:GeneSet { id: $id })";
+}
+
+/// A row returned by a built-in lookup. The `RETURN` items are aliased to the columns, which
+/// are also the fields of the implementing struct.
+trait Lookup: DeserializeOwned + Serialize {
+    const RETURN: &'static str;
+    const COLUMNS: &'static [&'static str];
+    const DEFAULT_SORT: &'static str;
+}
+
+#[derive(Deserialize, Serialize)]
+struct TraitGene {
+    gene: String,
+    prior: Option<f64>,
+    combined: Option<f64>,
+}
+
+#[derive(Deserialize, Serialize)]
+struct TraitGeneSet {
+    gene_set: String,
+    beta: Option<f64>,
+    beta_uncorrected: Option<f64>,
+}
+
+#[derive(Deserialize, Serialize)]
+struct WeightedFactor {
+    factor: String,
+    label: Option<String>,
+    weight: Option<f64>,
+}
+
+impl Lookup for TraitGene {
+    const RETURN: &'static str = "g.id AS gene, e.prior AS prior, e.combined AS combined";
+    const COLUMNS: &'static [&'static str] = &["gene", "prior", "combined"];
+    const DEFAULT_SORT: &'static str = "prior";
+}
+
+impl Lookup for TraitGeneSet {
+    const RETURN: &'static str =
+        "gs.id AS gene_set, e.beta AS beta, e.beta_uncorrected AS beta_uncorrected";
+    const COLUMNS: &'static [&'static str] = &["gene_set", "beta", "beta_uncorrected"];
+    const DEFAULT_SORT: &'static str = "beta";
+}
+
+impl Lookup for WeightedFactor {
+    // TRAIT_FACTOR weights are uploaded as strings, so they are converted here.
+    const RETURN: &'static str = "f.id AS factor, f.label AS label, toFloat(e.weight) AS weight";
+    const COLUMNS: &'static [&'static str] = &["factor", "label", "weight"];
+    const DEFAULT_SORT: &'static str = "weight";
+}
+
+struct LookupRowEater<L: Lookup> {
+    rows: Vec<L>,
+}
+
+impl<L: Lookup> RowEater for LookupRowEater<L> {
+    type Summary = Vec<L>;
+    fn eat(&mut self, row: Row) -> Result<(), Error> {
+        self.rows.push(row.to()?);
+        Ok(())
+    }
+    fn finish(&mut self) -> Result<Vec<L>, Error> {
+        Ok(std::mem::take(&mut self.rows))
+    }
+}
+
+#[derive(Copy, Clone)]
+pub(crate) enum LookupKind {
+    GenesForTrait,
+    GeneSetsForTrait,
+    FactorsForTrait,
+    FactorsForGene,
+    FactorsForGeneSet,
+}
+
+/// Where the value of a lookup column comes from.
+pub(crate) enum Column {
+    OtherId,
+    OtherProperty(&'static str),
+    EdgeProperty(&'static str),
+}
+
+/// A lookup as a single relationship from the node with the given id to the other nodes, for
+/// stores that do not run Cypher.
+pub(crate) struct Pattern {
+    pub(crate) label: &'static str,
+    pub(crate) rel_type: &'static str,
+    pub(crate) outgoing: bool,
+    pub(crate) other_label: &'static str,
+    pub(crate) columns: &'static [Column],
+}
+
+const GENES_FOR_TRAIT: Pattern = Pattern {
+    label: labels::TRAIT,
+    rel_type: rel_types::TRAIT_GENE,
+    outgoing: true,
+    other_label: labels::GENE,
+    columns: &[Column::OtherId, Column::EdgeProperty("prior"), Column::EdgeProperty("combined")],
+};
+const GENE_SETS_FOR_TRAIT: Pattern = Pattern {
+    label: labels::TRAIT,
+    rel_type: rel_types::TRAIT_GENE_SET,
+    outgoing: true,
+    other_label: labels::GENE_SET,
+    columns: &[
+        Column::OtherId, Column::EdgeProperty("beta"), Column::EdgeProperty("beta_uncorrected")
+    ],
+};
+const FACTORS_FOR_TRAIT: Pattern = Pattern {
+    label: labels::TRAIT,
+    rel_type: rel_types::TRAIT_FACTOR,
+    outgoing: true,
+    other_label: labels::FACTOR,
+    columns: &[Column::OtherId, Column::OtherProperty("label"), Column::EdgeProperty("weight")],
+};
+const FACTORS_FOR_GENE: Pattern = Pattern {
+    label: labels::GENE,
+    rel_type: rel_types::FACTOR_GENE,
+    outgoing: false,
+    ..FACTORS_FOR_TRAIT
+};
+const FACTORS_FOR_GENE_SET: Pattern = Pattern {
+    label: labels::GENE_SET,
+    rel_type: rel_types::FACTOR_GENE_SET,
+    outgoing: false,
+    ..FACTORS_FOR_TRAIT
+};
+
+/// Which rows of a lookup to return, and in which order.
+pub(crate) struct Page<'a> {
+    pub(crate) sort: Option<&'a str>,
+    pub(crate) ascending: bool,
+    pub(crate) skip: usize,
+    pub(crate) limit: usize,
+}
+
+fn rows<L: Lookup>(neo: &Neo, match_clause: &str, id: &str, page: &Page)
+    -> Result<Vec<Value>, Error> {
+    let sort = sort_column(L::COLUMNS, L::DEFAULT_SORT, page.sort)?;
+    let direction = if page.ascending { "ASC" } else { "DESC" };
+    let statement = format!(
+        "{match_clause}\nRETURN {}\nORDER BY {sort} IS NULL, {sort} {direction}, {}\n\
+        SKIP ${KEY_SKIP} LIMIT ${KEY_LIMIT}", L::RETURN, L::COLUMNS[0]
+    );
+    let query = query(&statement)
+        .param(KEY_ID, id)
+        .param(KEY_SKIP, page.skip as i64)
+        .param(KEY_LIMIT, page.limit as i64);
+    let rows = neo.read(query, &mut LookupRowEater::<L> { rows: Vec::new() })?;
+    rows.into_iter().map(|row| Ok(serde_json::to_value(row)?)).collect()
+}
+
+fn sort_column(columns: &'static [&'static str], default_sort: &'static str, sort: Option<&str>)
+    -> Result<&'static str, Error> {
+    match sort {
+        None => Ok(default_sort),
+        Some(sort) => columns.iter().find(|column| **column == sort).copied().ok_or_else(|| {
+            Error::from(format!(
+                "Cannot sort by {sort}. Possible columns are {}.", columns.join(", ")
+            ))
+        }),
+    }
+}
+
+impl LookupKind {
+    pub(crate) fn columns(&self) -> &'static [&'static str] {
+        match self {
+            LookupKind::GenesForTrait => TraitGene::COLUMNS,
+            LookupKind::GeneSetsForTrait => TraitGeneSet::COLUMNS,
+            LookupKind::FactorsForTrait | LookupKind::FactorsForGene
+            | LookupKind::FactorsForGeneSet => WeightedFactor::COLUMNS,
+        }
+    }
+    fn default_sort(&self) -> &'static str {
+        match self {
+            LookupKind::GenesForTrait => TraitGene::DEFAULT_SORT,
+            LookupKind::GeneSetsForTrait => TraitGeneSet::DEFAULT_SORT,
+            LookupKind::FactorsForTrait | LookupKind::FactorsForGene
+            | LookupKind::FactorsForGeneSet => WeightedFactor::DEFAULT_SORT,
+        }
+    }
+    /// The column to sort by, which is the default column unless another one is requested.
+    pub(crate) fn sort_column(&self, sort: Option<&str>) -> Result<&'static str, Error> {
+        sort_column(self.columns(), self.default_sort(), sort)
+    }
+    /// The lookup as a pattern, with one column source for each of the columns.
+    pub(crate) fn pattern(&self) -> &'static Pattern {
+        match self {
+            LookupKind::GenesForTrait => &GENES_FOR_TRAIT,
+            LookupKind::GeneSetsForTrait => &GENE_SETS_FOR_TRAIT,
+            LookupKind::FactorsForTrait => &FACTORS_FOR_TRAIT,
+            LookupKind::FactorsForGene => &FACTORS_FOR_GENE,
+            LookupKind::FactorsForGeneSet => &FACTORS_FOR_GENE_SET,
+        }
+    }
+    /// The rows from Neo4j as JSON objects with the columns as keys.
+    pub(crate) fn rows(&self, neo: &Neo, id: &str, page: &Page) -> Result<Vec<Value>, Error> {
+        match self {
+            LookupKind::GenesForTrait => {
+                rows::<TraitGene>(neo, matches::GENES_FOR_TRAIT, id, page)
+            }
+            LookupKind::GeneSetsForTrait => {
+                rows::<TraitGeneSet>(neo, matches::GENE_SETS_FOR_TRAIT, id, page)
+            }
+            LookupKind::FactorsForTrait => {
+                rows::<WeightedFactor>(neo, matches::FACTORS_FOR_TRAIT, id, page)
+            }
+            LookupKind::FactorsForGene => {
+                rows::<WeightedFactor>(neo, matches::FACTORS_FOR_GENE, id, page)
+            }
+            LookupKind::FactorsForGeneSet => {
+                rows::<WeightedFactor>(neo, matches::FACTORS_FOR_GENE_SET, id, page)
+            }
+        }
+    }
+}
+
+fn look_up(context: &AppContext, kind: LookupKind, config: &LookupConfig) -> Result<(), Error> {
+    let page = Page {
+        sort: config.sort.as_deref(), ascending: config.ascending, skip: 0, limit: config.limit
+    };
+    kind.sort_column(page.sort)?;
+    let store = store::open(context, &config.store)?;
+    let rows = store.lookup(kind, &config.id, &page)?;
+    info!("Found {} rows for {}.", rows.len(), config.id);
+    let columns = kind.columns();
+    let headers: Vec<String> = columns.iter().map(|column| column.to_string()).collect();
+    let mut writer = output::open(config.out.as_deref())?;
+    let mut record_writer = RecordWriter::new(config.format, headers, writer.as_mut())?;
+    for row in rows {
+        let values: Vec<Value> = columns.iter()
+            .map(|column| row.get(column).cloned().unwrap_or(Value::Null)).collect();
+        record_writer.write(&values)?;
+    }
+    record_writer.finish()
+}
+
+pub(crate) fn genes_for_trait(context: &AppContext, config: &LookupConfig) -> Result<(), Error> {
+    look_up(context, LookupKind::GenesForTrait, config)
+}
+
+pub(crate) fn gene_sets_for_trait(context: &AppContext, config: &LookupConfig)
+    -> Result<(), Error> {
+    look_up(context, LookupKind::GeneSetsForTrait, config)
+}
+
+pub(crate) fn factors_for_trait(context: &AppContext, config: &LookupConfig) -> Result<(), Error> {
+    look_up(context, LookupKind::FactorsForTrait, config)
+}
+
+pub(crate) fn factors_for_gene(context: &AppContext, config: &LookupConfig) -> Result<(), Error> {
+    look_up(context, LookupKind::FactorsForGene, config)
+}
+
+pub(crate) fn factors_for_gene_set(context: &AppContext, config: &LookupConfig)
+    -> Result<(), Error> {
+    look_up(context, LookupKind::FactorsForGeneSet, config)
+}
@@ -0,0 +1,110 @@
+use crate::config::LsConfig;
+use crate::context::AppContext;
+use crate::error::Error;
+use crate::output;
+use crate::output::{OutputFormat, RecordWriter};
+use crate::s3;
+use crate::s3::{Entry, FilePath};
+use globset::{GlobBuilder, GlobMatcher};
+use serde_json::json;
+use std::collections::BTreeMap;
+use std::io::Write;
+
+const TOP_LEVEL: &str = ".";
+const TOTAL: &str = "total";
+
+fn glob_matcher(glob: &str) -> Result<GlobMatcher, Error> {
+    let glob = GlobBuilder::new(glob).literal_separator(true).build()
+        .map_err(|error| Error::wrap(format!("Invalid glob pattern '{glob}'"), error))?;
+    Ok(glob.compile_matcher())
+}
+
+fn headers(headers: &[&str]) -> Vec<String> {
+    headers.iter().map(|header| header.to_string()).collect()
+}
+
+fn write_paths(entries: &[Entry], format: Option<OutputFormat>, writer: &mut dyn Write)
+    -> Result<(), Error> {
+    match format {
+        None => {
+            for entry in entries {
+                writeln!(writer, "{}", entry.path)?;
+            }
+            writer.flush()?;
+            Ok(())
+        }
+        Some(format) => {
+            let mut record_writer = RecordWriter::new(format, headers(&["path"]), writer)?;
+            for entry in entries {
+                record_writer.write(&[json!(entry.path)])?;
+            }
+            record_writer.finish()
+        }
+    }
+}
+
+fn write_long(entries: &[Entry], format: OutputFormat, writer: &mut dyn Write)
+    -> Result<(), Error> {
+    let headers = headers(&["path", "size", "last_modified", "etag"]);
+    let mut record_writer = RecordWriter::new(format, headers, writer)?;
+    for entry in entries {
+        let last_modified = entry.last_modified.map(|last_modified| {
+            humantime::format_rfc3339_seconds(last_modified).to_string()
+        });
+        record_writer.write(&[
+            json!(entry.path),
+            json!(entry.size),
+            json!(last_modified),
+            json!(entry.etag),
+        ])?;
+    }
+    record_writer.finish()
+}
+
+/// Files and bytes per sub-directory, with files directly in the directory under `.`, and the
+/// total.
+fn write_summary(entries: &[Entry], format: OutputFormat, writer: &mut dyn Write)
+    -> Result<(), Error> {
+    let mut totals: BTreeMap<String, (u64, u64)> = BTreeMap::new();
+    for entry in entries.iter().filter(|entry| !entry.is_dir()) {
+        let sub_dir = match entry.path.split_once('/') {
+            Some((sub_dir, _)) => format!("{sub_dir}/"),
+            None => TOP_LEVEL.to_string(),
+        };
+        let (n_files, n_bytes) = totals.entry(sub_dir).or_default();
+        *n_files += 1;
+        *n_bytes += entry.size.unwrap_or(0);
+    }
+    let n_files: u64 = totals.values().map(|(n_files, _)| n_files).sum();
+    let n_bytes: u64 = totals.values().map(|(_, n_bytes)| n_bytes).sum();
+    let mut record_writer =
+        RecordWriter::new(format, headers(&["sub_dir", "files", "bytes"]), writer)?;
+    for (sub_dir, (n_files, n_bytes)) in &totals {
+        record_writer.write(&[json!(sub_dir), json!(n_files), json!(n_bytes)])?;
+    }
+    record_writer.write(&[json!(TOTAL), json!(n_files), json!(n_bytes)])?;
+    record_writer.finish()
+}
+
+pub(crate) fn ls(context: &AppContext, config: &LsConfig) -> Result<(), Error> {
+    let dir = FilePath::from_path(&config.dir)?;
+    let matcher = config.glob.as_deref().map(glob_matcher).transpose()?;
+    // A pattern spanning sub-directories could not match anything otherwise.
+    let recursive = config.recursive || config.summary
+        || config.glob.as_ref().is_some_and(|glob| glob.contains('/'));
+    let entries: Vec<Entry> = s3::list(context, &dir, recursive)?.into_iter()
+        .filter(|entry| match &matcher {
+            Some(matcher) => matcher.is_match(entry.path.trim_end_matches('/')),
+            None => true,
+        })
+        .collect();
+    let mut writer = output::open(config.out.as_deref())?;
+    let format = config.format.unwrap_or(OutputFormat::Table);
+    if config.summary {
+        write_summary(&entries, format, &mut writer)
+    } else if config.long {
+        write_long(&entries, format, &mut writer)
+    } else {
+        write_paths(&entries, config.format, &mut writer)
+    }
+}
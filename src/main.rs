@@ -3,6 +3,7 @@ mod load_config;
 use log::{error, info, LevelFilter};
 use simplelog::{ColorChoice, Config as LogConfig, TermLogger, TerminalMode};
 use ygramul::error::Error;
+use ygramul::context::AppContext;
 use ygramul::execute;
 use ygramul::cli::get_cli_options;
 
@@ -15,13 +16,18 @@ fn main() {
     ).unwrap();
     match run() {
         Ok(_) => { info!("Done!") }
-        Err(error) => { error!("Error: {error}")}
+        Err(error) => {
+            error!("Error: {error}");
+            std::process::exit(1);
+        }
     }
 }
 
 fn run() -> Result<(), Error> {
     let options = get_cli_options()?;
-    let config = load_config::load_config()?.with_cli_options(options).build()?;
-    execute(&config)?;
+    let builder = load_config::load_config()?.with_cli_options(options);
+    let context = AppContext::new(builder.s3_config()?, builder.s3_cache_config()?)?;
+    let config = builder.build()?;
+    execute(&context, &config)?;
     Ok(())
 }
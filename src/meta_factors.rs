@@ -0,0 +1,385 @@
+use crate::bulk;
+use crate::bulk::{FactorGene, FactorGeneSet, FactorPheno};
+use crate::config::MetaFactorsConfig;
+use crate::context::AppContext;
+use crate::error::Error;
+use crate::ids::{FactorId, TraitId};
+use log::info;
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::path::Path;
+
+const META_FACTORS_FILE: &str = "meta_factors.csv";
+const INSTANCES_FILE: &str = "meta_factor_instances.csv";
+const META_FACTOR_PREFIX: &str = "MetaFactor";
+const MIN_MEMBERS: usize = 2;
+const N_LABELS: usize = 3;
+const LABEL_SEPARATOR: &str = "; ";
+
+/// A factor with its gene and gene set loadings. The gene block and the gene set block are
+/// each scaled to unit length, so both count equally for the cosine similarity.
+struct FactorLoadings {
+    id: FactorId,
+    label: Option<String>,
+    trait_id: TraitId,
+    genes: Vec<(String, f64)>,
+    gene_sets: Vec<(String, f64)>,
+}
+
+struct Factors {
+    factors: Vec<FactorLoadings>,
+    indices: HashMap<FactorId, usize>,
+}
+
+/// Sums of the similarities between the members of two clusters, kept only for pairs of
+/// factors at least as similar as the minimum similarity. Pairs below count as unrelated, so
+/// memory grows with the number of similar pairs, not with the square of the factors.
+struct Similarities {
+    n: usize,
+    sums: Vec<HashMap<usize, f32>>,
+}
+
+struct UnionFind {
+    parents: Vec<usize>,
+}
+
+#[derive(Serialize)]
+struct MetaFactorRow {
+    meta_factor: String,
+    label: String,
+    n_factors: usize,
+    n_traits: usize,
+}
+
+#[derive(Serialize)]
+struct InstanceRow {
+    factor: FactorId,
+    meta_factor: String,
+    similarity: f64,
+}
+
+impl Factors {
+    fn new() -> Factors {
+        Factors { factors: Vec::new(), indices: HashMap::new() }
+    }
+    fn factor(&mut self, id: &FactorId) -> &mut FactorLoadings {
+        let i = match self.indices.get(id) {
+            Some(&i) => i,
+            None => {
+                let i = self.factors.len();
+                self.factors.push(FactorLoadings {
+                    id: id.clone(),
+                    label: None,
+                    trait_id: id.trait_id.clone(),
+                    genes: Vec::new(),
+                    gene_sets: Vec::new(),
+                });
+                self.indices.insert(id.clone(), i);
+                i
+            }
+        };
+        &mut self.factors[i]
+    }
+    fn normalize(&mut self) {
+        for factor in &mut self.factors {
+            let has_genes = scale_to_unit(&mut factor.genes);
+            let has_gene_sets = scale_to_unit(&mut factor.gene_sets);
+            if has_genes && has_gene_sets {
+                let scale = std::f64::consts::FRAC_1_SQRT_2;
+                factor.genes.iter_mut().for_each(|(_, weight)| *weight *= scale);
+                factor.gene_sets.iter_mut().for_each(|(_, weight)| *weight *= scale);
+            }
+        }
+    }
+    /// The similarities of all pairs of factors at or above the minimum, computed one factor at
+    /// a time from the factors sharing genes or gene sets with it.
+    fn similarities(&self, min_similarity: f32) -> Similarities {
+        let n = self.factors.len();
+        let mut features: HashMap<(bool, &str), Vec<(usize, f64)>> = HashMap::new();
+        for (i, factor) in self.factors.iter().enumerate() {
+            for (gene, weight) in &factor.genes {
+                features.entry((true, gene.as_str())).or_default().push((i, *weight));
+            }
+            for (gene_set, weight) in &factor.gene_sets {
+                features.entry((false, gene_set.as_str())).or_default().push((i, *weight));
+            }
+        }
+        let mut similarities = Similarities::new(n);
+        let mut dot_products = vec![0.0f64; n];
+        let mut touched: Vec<usize> = Vec::new();
+        for (i, factor) in self.factors.iter().enumerate() {
+            let keys = factor.genes.iter().map(|(gene, weight)| ((true, gene.as_str()), *weight))
+                .chain(factor.gene_sets.iter()
+                    .map(|(gene_set, weight)| ((false, gene_set.as_str()), *weight)));
+            for (key, weight_i) in keys {
+                for &(j, weight_j) in &features[&key] {
+                    if j > i {
+                        if dot_products[j] == 0.0 {
+                            touched.push(j);
+                        }
+                        dot_products[j] += weight_i * weight_j;
+                    }
+                }
+            }
+            for j in touched.drain(..) {
+                let similarity = dot_products[j] as f32;
+                if similarity >= min_similarity {
+                    similarities.add(i, j, similarity);
+                }
+                dot_products[j] = 0.0;
+            }
+        }
+        similarities
+    }
+    /// The cosine similarity of two factors, whose loadings are scaled to unit length.
+    fn similarity(&self, i: usize, j: usize) -> f64 {
+        let (factor_i, factor_j) = (&self.factors[i], &self.factors[j]);
+        let genes: HashMap<&str, f64> =
+            factor_i.genes.iter().map(|(gene, weight)| (gene.as_str(), *weight)).collect();
+        let gene_sets: HashMap<&str, f64> = factor_i.gene_sets.iter()
+            .map(|(gene_set, weight)| (gene_set.as_str(), *weight)).collect();
+        let gene_dot: f64 = factor_j.genes.iter()
+            .filter_map(|(gene, weight)| genes.get(gene.as_str()).map(|other| weight * other))
+            .sum();
+        let gene_set_dot: f64 = factor_j.gene_sets.iter()
+            .filter_map(|(gene_set, weight)| {
+                gene_sets.get(gene_set.as_str()).map(|other| weight * other)
+            })
+            .sum();
+        gene_dot + gene_set_dot
+    }
+}
+
+fn scale_to_unit(loadings: &mut Vec<(String, f64)>) -> bool {
+    loadings.retain(|(_, weight)| weight.is_finite() && *weight != 0.0);
+    let norm = loadings.iter().map(|(_, weight)| weight * weight).sum::<f64>().sqrt();
+    if norm > 0.0 {
+        loadings.iter_mut().for_each(|(_, weight)| *weight /= norm);
+        true
+    } else {
+        false
+    }
+}
+
+impl Similarities {
+    fn new(n: usize) -> Similarities {
+        Similarities { n, sums: vec![HashMap::new(); n] }
+    }
+    fn add(&mut self, i: usize, j: usize, similarity: f32) {
+        *self.sums[i].entry(j).or_default() += similarity;
+        *self.sums[j].entry(i).or_default() += similarity;
+    }
+    /// Folds cluster `b` into cluster `a`: the sum for `a` and any other cluster becomes the
+    /// sum of the sums for `a` and `b`.
+    fn merge(&mut self, a: usize, b: usize) {
+        let sums_b = std::mem::take(&mut self.sums[b]);
+        self.sums[a].remove(&b);
+        for (k, sum) in sums_b {
+            if k != a {
+                self.sums[k].remove(&b);
+                self.add(a, k, sum);
+            }
+        }
+    }
+}
+
+impl UnionFind {
+    fn new(n: usize) -> UnionFind {
+        UnionFind { parents: (0..n).collect() }
+    }
+    fn find(&mut self, i: usize) -> usize {
+        let mut root = i;
+        while self.parents[root] != root {
+            root = self.parents[root];
+        }
+        let mut i = i;
+        while self.parents[i] != root {
+            let parent = self.parents[i];
+            self.parents[i] = root;
+            i = parent;
+        }
+        root
+    }
+    fn union(&mut self, i: usize, j: usize) {
+        let root_i = self.find(i);
+        let root_j = self.find(j);
+        if root_i != root_j {
+            self.parents[root_j] = root_i;
+        }
+    }
+}
+
+/// Average linkage clustering using the nearest-neighbor chain algorithm, merging clusters
+/// only while their average similarity is at least the minimum. A merged cluster keeps the slot
+/// of its first part, so each slot index is also the index of one of its members. A cluster
+/// with no neighbor similar enough is done, since merges elsewhere only lower its average
+/// similarities. Returns the clusters as lists of factor indices.
+fn cluster(mut similarities: Similarities, min_similarity: f32) -> Vec<Vec<usize>> {
+    let n = similarities.n;
+    let mut active = vec![true; n];
+    let mut sizes = vec![1usize; n];
+    let mut union_find = UnionFind::new(n);
+    let mut chain: Vec<usize> = Vec::new();
+    let mut next_start: usize = 0;
+    loop {
+        if chain.is_empty() {
+            while next_start < n && !active[next_start] {
+                next_start += 1;
+            }
+            if next_start == n {
+                break;
+            }
+            chain.push(next_start);
+        }
+        let a = chain[chain.len() - 1];
+        let average = |k: usize, sum: f32| sum / ((sizes[a] * sizes[k]) as f32);
+        let previous = if chain.len() > 1 { Some(chain[chain.len() - 2]) } else { None };
+        let mut nearest: Option<(usize, f32)> = previous.map(|p| {
+            (p, average(p, similarities.sums[a].get(&p).copied().unwrap_or(0.0)))
+        });
+        for (&k, &sum) in &similarities.sums[a] {
+            let similarity = average(k, sum);
+            let is_nearer = nearest.is_none_or(|(nearest_k, nearest_similarity)| {
+                similarity > nearest_similarity
+                    || (similarity == nearest_similarity && Some(nearest_k) != previous
+                        && k < nearest_k)
+            });
+            if active[k] && is_nearer {
+                nearest = Some((k, similarity));
+            }
+        }
+        match nearest {
+            Some((b, similarity)) if similarity >= min_similarity => {
+                if Some(b) == previous {
+                    chain.pop();
+                    chain.pop();
+                    union_find.union(a, b);
+                    similarities.merge(a, b);
+                    sizes[a] += sizes[b];
+                    active[b] = false;
+                } else {
+                    chain.push(b);
+                }
+            }
+            _ => {
+                active[a] = false;
+                chain.clear();
+            }
+        }
+    }
+    let mut clusters: BTreeMap<usize, Vec<usize>> = BTreeMap::new();
+    for i in 0..n {
+        clusters.entry(union_find.find(i)).or_default().push(i);
+    }
+    clusters.into_values().collect()
+}
+
+fn meta_factor_label(factors: &Factors, members: &[usize]) -> String {
+    let mut counts: BTreeMap<&str, usize> = BTreeMap::new();
+    for &i in members {
+        if let Some(label) = &factors.factors[i].label {
+            *counts.entry(label.as_str()).or_default() += 1;
+        }
+    }
+    let mut counts: Vec<(&str, usize)> = counts.into_iter().collect();
+    counts.sort_by(|(label1, count1), (label2, count2)| {
+        count2.cmp(count1).then(label1.cmp(label2))
+    });
+    counts.into_iter().take(N_LABELS).map(|(label, _)| label).collect::<Vec<&str>>()
+        .join(LABEL_SEPARATOR)
+}
+
+fn mean_similarity(factors: &Factors, i: usize, members: &[usize]) -> f64 {
+    let similarities: Vec<f64> = members.iter().filter(|&&j| j != i)
+        .map(|&j| factors.similarity(i, j)).collect();
+    similarities.iter().sum::<f64>() / (similarities.len() as f64)
+}
+
+fn read_factors(context: &AppContext, bulk_dir: &str) -> Result<Factors, Error> {
+    let mut factors = Factors::new();
+    bulk::for_each_row(context, bulk_dir, |row: FactorPheno| {
+        let factor = factors.factor(&row.factor);
+        factor.label = Some(row.label);
+        factor.trait_id = row.pheno;
+        Ok(())
+    })?;
+    bulk::for_each_row(context, bulk_dir, |row: FactorGene| {
+        factors.factor(&row.factor).genes.push((row.gene.to_string(), row.weight));
+        Ok(())
+    })?;
+    bulk::for_each_row(context, bulk_dir, |row: FactorGeneSet| {
+        factors.factor(&row.factor).gene_sets.push((row.gene_set.to_string(), row.weight));
+        Ok(())
+    })?;
+    factors.normalize();
+    Ok(factors)
+}
+
+pub(crate) fn meta_factors(context: &AppContext, config: &MetaFactorsConfig)
+    -> Result<(), Error> {
+    let factors = read_factors(context, &config.bulk_dir)?;
+    info!("Clustering {} factors with minimum similarity {}.", factors.factors.len(),
+        config.min_similarity);
+    let min_similarity = config.min_similarity as f32;
+    let similarities = factors.similarities(min_similarity);
+    let mut clusters: Vec<Vec<usize>> = cluster(similarities, min_similarity)
+        .into_iter().filter(|members| members.len() >= MIN_MEMBERS).collect();
+    clusters.sort_by_key(|members| std::cmp::Reverse(members.len()));
+    let out_dir = Path::new(&config.out);
+    std::fs::create_dir_all(out_dir)?;
+    let mut meta_factor_writer = csv::Writer::from_path(out_dir.join(META_FACTORS_FILE))?;
+    let mut instance_writer = csv::Writer::from_path(out_dir.join(INSTANCES_FILE))?;
+    let mut n_instances: usize = 0;
+    for (i, members) in clusters.iter().enumerate() {
+        let meta_factor = format!("{META_FACTOR_PREFIX}{}", i + 1);
+        let traits: BTreeSet<&TraitId> =
+            members.iter().map(|&j| &factors.factors[j].trait_id).collect();
+        meta_factor_writer.serialize(MetaFactorRow {
+            meta_factor: meta_factor.clone(),
+            label: meta_factor_label(&factors, members),
+            n_factors: members.len(),
+            n_traits: traits.len(),
+        })?;
+        for &j in members {
+            instance_writer.serialize(InstanceRow {
+                factor: factors.factors[j].id.clone(),
+                meta_factor: meta_factor.clone(),
+                similarity: mean_similarity(&factors, j, members),
+            })?;
+            n_instances += 1;
+        }
+    }
+    info!("Wrote {} meta-factors with {n_instances} member factors to {}", clusters.len(),
+        out_dir.display());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{cluster, Similarities};
+
+    fn similarities(n: usize, pairs: &[(usize, usize, f32)]) -> Similarities {
+        let mut similarities = Similarities::new(n);
+        for &(i, j, similarity) in pairs {
+            similarities.add(i, j, similarity);
+        }
+        similarities
+    }
+
+    #[test]
+    fn average_linkage() {
+        let pairs = [(0, 1, 0.9), (2, 3, 0.8), (0, 2, 0.6), (1, 2, 0.5)];
+        // {0, 1} and {2, 3} have an average similarity of (0.6 + 0.5) / 4 = 0.275.
+        assert_eq!(cluster(similarities(5, &pairs), 0.5), vec![vec![0, 1], vec![2, 3], vec![4]]);
+        assert_eq!(cluster(similarities(5, &pairs), 0.25), vec![vec![0, 1, 2, 3], vec![4]]);
+        let singletons: Vec<Vec<usize>> = (0..5).map(|i| vec![i]).collect();
+        assert_eq!(cluster(similarities(5, &pairs), 0.95), singletons);
+    }
+
+    #[test]
+    fn average_not_single_linkage() {
+        let pairs = [(0, 1, 0.9), (0, 2, 0.7), (1, 2, 0.2)];
+        assert_eq!(cluster(similarities(3, &pairs), 0.5), vec![vec![0, 1], vec![2]]);
+        assert_eq!(cluster(similarities(3, &pairs), 0.4), vec![vec![0, 1, 2]]);
+    }
+}
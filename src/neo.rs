@@ -1,36 +1,189 @@
 use crate::error::Error;
-use neo4rs::{Graph, Query, Row};
-use tokio::runtime::Runtime;
-use crate::config::Neo4jConfig;
+use neo4rs::{query, ConfigBuilder, Graph, Neo4jClientErrorKind, Neo4jErrorKind,
+             Neo4jSecurityErrorKind, Query, Row};
+use tokio::runtime::Handle;
+use crate::config::{Neo4jConfig, RetryConfig};
+use log::warn;
+use serde::de::DeserializeOwned;
+use std::time::Duration;
+
+const CONNECTIVITY_QUERY: &str = "RETURN 1";
 
 pub(crate) trait RowEater {
     type Summary;
     fn eat(&mut self, row: Row) -> Result<(), Error>;
     fn finish(&mut self) -> Result<Self::Summary, Error>;
 }
+/// Collects all rows, deserialized by column name.
+pub(crate) struct TypedRowEater<T: DeserializeOwned> {
+    rows: Vec<T>,
+}
+
+impl<T: DeserializeOwned> TypedRowEater<T> {
+    pub(crate) fn new() -> TypedRowEater<T> {
+        TypedRowEater { rows: Vec::new() }
+    }
+}
+
+impl<T: DeserializeOwned> RowEater for TypedRowEater<T> {
+    type Summary = Vec<T>;
+    fn eat(&mut self, row: Row) -> Result<(), Error> {
+        self.rows.push(row.to()?);
+        Ok(())
+    }
+    fn finish(&mut self) -> Result<Self::Summary, Error> {
+        Ok(std::mem::take(&mut self.rows))
+    }
+}
+
+/// A handle on a Neo4j connection pool; clones share the pool.
+#[derive(Clone)]
 pub(crate) struct Neo {
-    runtime: Runtime,
+    runtime: Handle,
     graph: Graph,
+    query_timeout: Option<Duration>,
+    retry: RetryConfig,
 }
 
-impl Neo {
-    pub(crate) fn new(uri: &str, user: &str, password: &str) -> Result<Neo, Error> {
-        let runtime = Runtime::new()?;
-        let graph = runtime.block_on(async { Graph::new(uri, user, password).await })?;
-        Ok(Neo { runtime, graph })
+/// Why an attempt to run a query failed, keeping driver errors apart so transient ones can
+/// be retried.
+enum Failure {
+    Driver(neo4rs::Error),
+    Other(Error),
+}
+
+impl From<neo4rs::Error> for Failure {
+    fn from(error: neo4rs::Error) -> Self {
+        Failure::Driver(error)
+    }
+}
+
+impl From<Failure> for Error {
+    fn from(failure: Failure) -> Self {
+        match failure {
+            Failure::Driver(error) => Error::from(error),
+            Failure::Other(error) => error,
+        }
+    }
+}
+
+impl Failure {
+    fn is_transient(&self) -> bool {
+        match self {
+            Failure::Driver(neo4rs::Error::Neo4j(error)) => matches!(
+                error.kind(),
+                Neo4jErrorKind::Transient
+                    | Neo4jErrorKind::Client(Neo4jClientErrorKind::SessionExpired)
+                    | Neo4jErrorKind::Client(Neo4jClientErrorKind::Security(
+                        Neo4jSecurityErrorKind::AuthorizationExpired
+                    ))
+            ),
+            Failure::Driver(neo4rs::Error::ConnectionError)
+            | Failure::Driver(neo4rs::Error::IOError { .. }) => true,
+            Failure::Driver(_) | Failure::Other(_) => false,
+        }
     }
-    pub(crate) fn for_config(config: &Neo4jConfig) -> Result<Neo, Error> {
-        Neo::new(&config.uri, &config.user, &config.password)
+}
+
+impl Neo {
+    pub(crate) fn for_config(runtime: Handle, config: &Neo4jConfig) -> Result<Neo, Error> {
+        // The AWS SDK and neo4rs enable different rustls crypto backends, so rustls cannot
+        // pick one on its own. Fails harmlessly if a provider has already been installed.
+        let _ = rustls::crypto::ring::default_provider().install_default();
+        let mut builder =
+            ConfigBuilder::new().uri(&config.uri).user(&config.user).password(&config.password);
+        if let Some(database) = &config.database {
+            builder = builder.db(database.as_str());
+        }
+        if let Some(max_connections) = config.max_connections {
+            builder = builder.max_connections(max_connections);
+        }
+        if let Some(fetch_size) = config.fetch_size {
+            builder = builder.fetch_size(fetch_size);
+        }
+        if let Some(ca_file) = &config.ca_file {
+            builder = builder.with_client_certificate(ca_file);
+        }
+        let neo4j_config = builder.build()?;
+        let connect_timeout = config.connect_timeout;
+        let graph = runtime.block_on(async {
+            // The pool only connects when first used, so connect now to fail early on a wrong
+            // URI or credentials.
+            let connect = async {
+                let graph = Graph::connect(neo4j_config).await?;
+                graph.run(query(CONNECTIVITY_QUERY)).await?;
+                Ok::<Graph, neo4rs::Error>(graph)
+            };
+            tokio::time::timeout(connect_timeout, connect).await.map_err(|_| {
+                Error::from(format!(
+                    "Could not connect to Neo4j within {}.",
+                    humantime::format_duration(connect_timeout)
+                ))
+            })?.map_err(Error::from)
+        })?;
+        Ok(Neo { runtime, graph, query_timeout: config.query_timeout, retry: config.retry })
     }
+    /// Runs the query once. A failed statement may still have been committed, so anything that
+    /// writes goes through here.
     pub(crate) fn cypher<E: RowEater>(&self, query: Query, row_eater: &mut E)
         -> Result<E::Summary, Error> {
-        self.runtime.block_on(async {
-            let mut result = self.graph.execute(query).await?;
-            while let Some(row) = result.next().await? {
-                row_eater.eat(row)?;
+        self.run(query, row_eater, 0)
+    }
+    /// Runs a query that is safe to repeat, such as a read. If it fails with a transient error
+    /// before any row has been eaten, it is retried with exponential backoff, as often as
+    /// configured (neo4j/max_retries).
+    pub(crate) fn read<E: RowEater>(&self, query: Query, row_eater: &mut E)
+        -> Result<E::Summary, Error> {
+        self.run(query, row_eater, self.retry.max_retries)
+    }
+    fn run<E: RowEater>(&self, query: Query, row_eater: &mut E, max_retries: u32)
+        -> Result<E::Summary, Error> {
+        let mut attempt: u32 = 0;
+        loop {
+            let mut n_rows: usize = 0;
+            let result = self.runtime.block_on(async {
+                let stream = self.stream(query.clone(), row_eater, &mut n_rows);
+                match self.query_timeout {
+                    Some(timeout) => {
+                        tokio::time::timeout(timeout, stream).await.unwrap_or_else(|_| {
+                            Err(Failure::Other(Error::from(format!(
+                                "Query timed out after {}.", humantime::format_duration(timeout)
+                            ))))
+                        })
+                    }
+                    None => stream.await,
+                }
+            });
+            match result {
+                Ok(()) => return row_eater.finish(),
+                Err(failure)
+                if n_rows == 0 && attempt < max_retries && failure.is_transient() => {
+                    let delay = self.backoff(attempt);
+                    attempt += 1;
+                    warn!("Retrying in {} (retry {attempt} of {max_retries}) after transient \
+                        error: {}", humantime::format_duration(delay), Error::from(failure));
+                    std::thread::sleep(delay);
+                }
+                Err(failure) => return Err(failure.into()),
             }
-            Ok::<(), Error>(())
-        })?;
-        row_eater.finish()
+        }
+    }
+    async fn stream<E: RowEater>(&self, query: Query, row_eater: &mut E, n_rows: &mut usize)
+        -> Result<(), Failure> {
+        let mut result = self.graph.execute(query).await?;
+        while let Some(row) = result.next().await? {
+            *n_rows += 1;
+            row_eater.eat(row).map_err(Failure::Other)?;
+        }
+        Ok(())
+    }
+    /// The delay before the retry after the given attempt: doubling from the initial delay up
+    /// to the maximum, with jitter so that concurrent clients do not retry in lockstep.
+    fn backoff(&self, attempt: u32) -> Duration {
+        let RetryConfig { initial_delay, max_delay, .. } = self.retry;
+        let delay = initial_delay.saturating_mul(2u32.saturating_pow(attempt)).min(max_delay);
+        let half = delay / 2;
+        let jitter = half.mul_f64(fastrand::f64());
+        Duration::from_millis((half + jitter).as_millis() as u64)
     }
 }
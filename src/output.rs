@@ -0,0 +1,129 @@
+use crate::error::Error;
+use crate::table::Table;
+use serde::Deserialize;
+use serde_json::Value;
+use std::fmt::{Display, Formatter};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+mod format {
+    pub(crate) const TABLE: &str = "table";
+    pub(crate) const CSV: &str = "csv";
+    pub(crate) const TSV: &str = "tsv";
+    pub(crate) const JSON: &str = "json";
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub enum OutputFormat {
+    Table,
+    Csv,
+    Tsv,
+    Json,
+}
+
+enum Sink<'a> {
+    Table(Table, &'a mut dyn Write),
+    Delimited(Box<csv::Writer<&'a mut dyn Write>>),
+    Json(Vec<String>, &'a mut dyn Write),
+}
+
+/// Writes records with a fixed list of columns as a pretty table, CSV, TSV or JSON Lines.
+pub(crate) struct RecordWriter<'a> {
+    sink: Sink<'a>,
+}
+
+pub(crate) fn open(out: Option<&str>) -> Result<Box<dyn Write>, Error> {
+    match out {
+        Some(out) => Ok(Box::new(BufWriter::new(File::create(out)?))),
+        None => Ok(Box::new(std::io::stdout().lock())),
+    }
+}
+
+pub(crate) fn cell(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(string) => string.clone(),
+        _ => value.to_string(),
+    }
+}
+
+impl<'a> RecordWriter<'a> {
+    pub(crate) fn new(format: OutputFormat, headers: Vec<String>, writer: &'a mut dyn Write)
+        -> Result<RecordWriter<'a>, Error> {
+        let sink = match format {
+            OutputFormat::Table => Sink::Table(Table::new(headers), writer),
+            OutputFormat::Csv | OutputFormat::Tsv => {
+                let delimiter = if format == OutputFormat::Csv { b',' } else { b'\t' };
+                let mut csv_writer =
+                    csv::WriterBuilder::new().delimiter(delimiter).from_writer(writer);
+                csv_writer.write_record(&headers)?;
+                Sink::Delimited(Box::new(csv_writer))
+            }
+            OutputFormat::Json => Sink::Json(headers, writer),
+        };
+        Ok(RecordWriter { sink })
+    }
+    pub(crate) fn write(&mut self, values: &[Value]) -> Result<(), Error> {
+        match &mut self.sink {
+            Sink::Table(table, _) => table.add_row(values.iter().map(cell).collect()),
+            Sink::Delimited(csv_writer) => {
+                csv_writer.write_record(values.iter().map(cell))?
+            }
+            Sink::Json(headers, writer) => {
+                let fields = headers.iter().zip(values).map(|(header, value)| {
+                    Ok(format!("{}:{}", serde_json::to_string(header)?, value))
+                }).collect::<Result<Vec<String>, Error>>()?;
+                writeln!(writer, "{{{}}}", fields.join(","))?
+            }
+        }
+        Ok(())
+    }
+    pub(crate) fn finish(self) -> Result<(), Error> {
+        match self.sink {
+            Sink::Table(table, writer) => {
+                write!(writer, "{table}")?;
+                writer.flush()?;
+            }
+            Sink::Delimited(mut csv_writer) => csv_writer.flush()?,
+            Sink::Json(_, writer) => writer.flush()?,
+        }
+        Ok(())
+    }
+}
+
+impl Display for OutputFormat {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OutputFormat::Table => write!(f, "{}", format::TABLE),
+            OutputFormat::Csv => write!(f, "{}", format::CSV),
+            OutputFormat::Tsv => write!(f, "{}", format::TSV),
+            OutputFormat::Json => write!(f, "{}", format::JSON),
+        }
+    }
+}
+
+impl TryFrom<&str> for OutputFormat {
+    type Error = Error;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value {
+            format::TABLE => Ok(OutputFormat::Table),
+            format::CSV => Ok(OutputFormat::Csv),
+            format::TSV => Ok(OutputFormat::Tsv),
+            format::JSON => Ok(OutputFormat::Json),
+            _ => Err(Error::from(format!(
+                "Unknown output format: {value}. Known formats are {}, {}, {} and {}.",
+                format::TABLE, format::CSV, format::TSV, format::JSON
+            ))),
+        }
+    }
+}
+
+impl TryFrom<String> for OutputFormat {
+    type Error = Error;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        OutputFormat::try_from(value.as_str())
+    }
+}
@@ -0,0 +1,191 @@
+use crate::error::Error;
+use std::ffi::OsString;
+use std::fmt::{Display, Formatter};
+use std::process::Command;
+
+pub(crate) const PASSWORD_ENV_VAR: &str = "NEO4J_PASSWORD";
+
+/// Where the Neo4j password comes from, in order of priority.
+#[derive(Clone, Debug)]
+pub(crate) enum PasswordSource {
+    CommandLine,
+    ConfigFile,
+    Environment,
+    File(String),
+    Command(String),
+    Prompt,
+}
+
+/// The ways a password may be given, as collected from config file, command line and
+/// environment, and whether the user can be prompted.
+pub(crate) struct PasswordOptions {
+    pub(crate) password: Option<String>,
+    pub(crate) on_command_line: bool,
+    pub(crate) env_password: Option<OsString>,
+    pub(crate) password_file: Option<String>,
+    pub(crate) password_command: Option<String>,
+    pub(crate) interactive: bool,
+}
+
+impl Display for PasswordSource {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PasswordSource::CommandLine => write!(f, "command line"),
+            PasswordSource::ConfigFile => write!(f, "config file (neo4j/password)"),
+            PasswordSource::Environment => write!(f, "environment variable {PASSWORD_ENV_VAR}"),
+            PasswordSource::File(path) => write!(f, "password file {path}"),
+            PasswordSource::Command(command) => write!(f, "output of command '{command}'"),
+            PasswordSource::Prompt => write!(f, "interactive prompt"),
+        }
+    }
+}
+
+impl PasswordOptions {
+    /// The source that would be used, without reading the password.
+    pub(crate) fn source(&self) -> Option<PasswordSource> {
+        if self.password.is_some() {
+            if self.on_command_line {
+                Some(PasswordSource::CommandLine)
+            } else {
+                Some(PasswordSource::ConfigFile)
+            }
+        } else if self.env_password.is_some() {
+            Some(PasswordSource::Environment)
+        } else if let Some(path) = &self.password_file {
+            Some(PasswordSource::File(path.clone()))
+        } else if let Some(command) = &self.password_command {
+            Some(PasswordSource::Command(command.clone()))
+        } else if self.interactive {
+            Some(PasswordSource::Prompt)
+        } else {
+            None
+        }
+    }
+    pub(crate) fn resolve(self, user: &str) -> Result<(String, PasswordSource), Error> {
+        let source = self.source().ok_or_else(|| Error::from(format!(
+            "No password (neo4j/password) specified. Set {PASSWORD_ENV_VAR}, neo4j/password_file \
+            or neo4j/password_command, or run from a terminal to be prompted."
+        )))?;
+        let password = match &source {
+            PasswordSource::CommandLine | PasswordSource::ConfigFile => {
+                self.password.unwrap_or_default()
+            }
+            PasswordSource::Environment => {
+                self.env_password.unwrap_or_default().into_string().map_err(|_| {
+                    Error::from(format!("Could not read {PASSWORD_ENV_VAR}: not valid unicode"))
+                })?
+            }
+            PasswordSource::File(path) => read_password_file(path)?,
+            PasswordSource::Command(command) => run_password_command(command)?,
+            PasswordSource::Prompt => {
+                rpassword::prompt_password(format!("Neo4j password for {user}: "))?
+            }
+        };
+        if password.is_empty() {
+            Err(Error::from(format!("Empty Neo4j password from {source}.")))?
+        }
+        Ok((password, source))
+    }
+}
+
+fn read_password_file(path: &str) -> Result<String, Error> {
+    let content = std::fs::read_to_string(path).map_err(|error| {
+        Error::wrap(format!("Could not read password file {path}"), error)
+    })?;
+    Ok(first_line(&content))
+}
+
+fn run_password_command(command: &str) -> Result<String, Error> {
+    let output = Command::new("sh").arg("-c").arg(command).output().map_err(|error| {
+        Error::wrap(format!("Could not run password command '{command}'"), error)
+    })?;
+    if !output.status.success() {
+        Err(Error::from(format!("Password command '{command}' failed with {}.", output.status)))?
+    }
+    let stdout = String::from_utf8(output.stdout).map_err(|error| {
+        Error::wrap(format!("Output of password command '{command}' is not UTF-8"), error)
+    })?;
+    Ok(first_line(&stdout))
+}
+
+/// The first line without its line ending, so files and commands may end with a newline.
+fn first_line(text: &str) -> String {
+    text.lines().next().unwrap_or_default().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{PasswordOptions, PasswordSource};
+
+    fn all_options() -> PasswordOptions {
+        PasswordOptions {
+            password: Some("given".to_string()),
+            on_command_line: true,
+            env_password: Some("from_env".into()),
+            password_file: Some("/no/such/file".to_string()),
+            password_command: Some("printf 'from_command\\nrest\\n'".to_string()),
+            interactive: true,
+        }
+    }
+
+    fn source(options: &PasswordOptions) -> String {
+        options.source().map(|source| source.to_string()).unwrap_or_default()
+    }
+
+    #[test]
+    fn sources_in_order_of_priority() {
+        let mut options = all_options();
+        assert_eq!(source(&options), "command line");
+        options.on_command_line = false;
+        assert_eq!(source(&options), "config file (neo4j/password)");
+        options.password = None;
+        assert_eq!(source(&options), "environment variable NEO4J_PASSWORD");
+        options.env_password = None;
+        assert_eq!(source(&options), "password file /no/such/file");
+        options.password_file = None;
+        assert_eq!(source(&options), "output of command 'printf 'from_command\\nrest\\n''");
+        options.password_command = None;
+        assert_eq!(source(&options), "interactive prompt");
+        options.interactive = false;
+        assert!(options.source().is_none());
+        assert!(options.resolve("neo4j").is_err());
+    }
+
+    #[test]
+    fn resolve_reads_the_chosen_source() {
+        let (password, source) = all_options().resolve("neo4j").unwrap();
+        assert_eq!(password, "given");
+        assert!(matches!(source, PasswordSource::CommandLine));
+        let options = PasswordOptions { password: None, ..all_options() };
+        assert_eq!(options.resolve("neo4j").unwrap().0, "from_env");
+        let password_file =
+            std::env::temp_dir().join(format!("ygramul-password-{}", std::process::id()));
+        std::fs::write(&password_file, "from_file\nrest\n").unwrap();
+        let options = PasswordOptions {
+            password: None, env_password: None,
+            password_file: Some(password_file.to_string_lossy().to_string()), ..all_options()
+        };
+        let resolved = options.resolve("neo4j");
+        std::fs::remove_file(&password_file).unwrap();
+        assert_eq!(resolved.unwrap().0, "from_file");
+        let options = PasswordOptions {
+            password: None, env_password: None, password_file: None, ..all_options()
+        };
+        assert_eq!(options.resolve("neo4j").unwrap().0, "from_command");
+    }
+
+    #[test]
+    fn empty_passwords_are_rejected() {
+        let options = PasswordOptions { password: Some(String::new()), ..all_options() };
+        let error = options.resolve("neo4j").unwrap_err();
+        assert_eq!(error.to_string(), "Empty Neo4j password from command line.");
+        let options =
+            PasswordOptions { password: None, env_password: Some("".into()), ..all_options() };
+        assert!(options.resolve("neo4j").is_err());
+        let options = PasswordOptions {
+            password: None, env_password: None, password_file: None,
+            password_command: Some("true".to_string()), ..all_options()
+        };
+        assert!(options.resolve("neo4j").is_err());
+    }
+}
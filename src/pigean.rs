@@ -1,6 +1,7 @@
 pub(crate) mod phenos;
 pub(crate) mod factors;
 pub(crate) mod pgs;
+pub(crate) mod dedup;
 
 use crate::error::Error;
 fn last_three_parts(string: &str) -> Option<(&str, &str, &str)> {
@@ -0,0 +1,290 @@
+use crate::error::Error;
+use hashbrown::HashTable;
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::fmt::{Display, Formatter};
+use std::hash::{BuildHasher, Hash, RandomState};
+use std::path::{Path, PathBuf};
+
+mod policy {
+    pub(crate) const MAX: &str = "max";
+    pub(crate) const MEAN: &str = "mean";
+    pub(crate) const FIRST: &str = "first";
+    pub(crate) const ERROR: &str = "error";
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub(crate) enum DuplicatePolicy {
+    Max,
+    Mean,
+    First,
+    Error,
+}
+
+/// Rows identified by a pair of ids, which the deduplicator borrows from the row instead of
+/// keeping a copy.
+pub(crate) trait Keyed {
+    type First: Hash + Eq + Display;
+    type Second: Hash + Eq + Display;
+    fn key(&self) -> (&Self::First, &Self::Second);
+}
+
+/// Rows with weights to resolve duplicates by. The first weight is the primary one, which picks
+/// the row kept under the max policy. NaN weights are ignored under both max and mean.
+pub(crate) trait Weighted {
+    fn weights(&self) -> Vec<f64>;
+    fn set_weights(&mut self, weights: &[f64]);
+}
+
+struct Entry<R> {
+    row: R,
+    sums: Vec<f64>,
+    counts: Vec<usize>,
+    n_rows: usize,
+    files: Vec<String>,
+}
+
+pub(crate) struct Deduplicator<R: Keyed + Weighted + Serialize> {
+    policy: DuplicatePolicy,
+    entries: Vec<Entry<R>>,
+    /// The positions of the entries, found by the hash of the key of their row.
+    indices: HashTable<usize>,
+    hasher: RandomState,
+}
+
+#[derive(Serialize)]
+struct CollisionRow {
+    key: String,
+    n_rows: usize,
+    files: String,
+}
+
+fn primary_weight<R: Weighted>(row: &R) -> f64 {
+    row.weights().first().copied().unwrap_or(f64::NAN)
+}
+
+/// Whether a row with this primary weight beats the kept one, with NaN losing to any number.
+fn is_better(weight: f64, kept: f64) -> bool {
+    !weight.is_nan() && (kept.is_nan() || weight > kept)
+}
+
+impl<R: Weighted> Entry<R> {
+    fn new(row: R, file: &str) -> Self {
+        let weights = row.weights();
+        let counts = weights.iter().map(|weight| usize::from(!weight.is_nan())).collect();
+        let sums = weights.iter().map(|weight| if weight.is_nan() { 0.0 } else { *weight })
+            .collect();
+        let files = vec![file.to_string()];
+        Entry { row, sums, counts, n_rows: 1, files }
+    }
+    fn add_to_sums(&mut self, row: &R) {
+        let columns = self.sums.iter_mut().zip(self.counts.iter_mut());
+        for ((sum, count), weight) in columns.zip(row.weights()) {
+            if !weight.is_nan() {
+                *sum += weight;
+                *count += 1;
+            }
+        }
+    }
+    fn resolve(mut self, policy: DuplicatePolicy) -> R {
+        if let DuplicatePolicy::Mean = policy {
+            let means: Vec<f64> = self.sums.iter().zip(&self.counts)
+                .map(|(sum, count)| if *count > 0 { sum / (*count as f64) } else { f64::NAN })
+                .collect();
+            self.row.set_weights(&means)
+        }
+        self.row
+    }
+}
+
+impl<R: Keyed + Weighted + Serialize> Deduplicator<R> {
+    pub(crate) fn new(policy: DuplicatePolicy) -> Self {
+        Deduplicator {
+            policy, entries: Vec::new(), indices: HashTable::new(), hasher: RandomState::new()
+        }
+    }
+    pub(crate) fn add(&mut self, row: R, file: &str) -> Result<(), Error> {
+        let Deduplicator { entries, indices, hasher, .. } = self;
+        let hash = hasher.hash_one(row.key());
+        match indices.find(hash, |&i| entries[i].row.key() == row.key()).copied() {
+            None => {
+                indices.insert_unique(hash, entries.len(),
+                                      |&i| hasher.hash_one(entries[i].row.key()));
+                entries.push(Entry::new(row, file));
+            }
+            Some(i) => {
+                let entry = &mut self.entries[i];
+                entry.n_rows += 1;
+                if !entry.files.iter().any(|known| known == file) {
+                    entry.files.push(file.to_string());
+                }
+                match self.policy {
+                    DuplicatePolicy::First | DuplicatePolicy::Error => {}
+                    DuplicatePolicy::Max => {
+                        if is_better(primary_weight(&row), primary_weight(&entry.row)) {
+                            entry.row = row;
+                        }
+                    }
+                    DuplicatePolicy::Mean => entry.add_to_sums(&row),
+                }
+            }
+        }
+        Ok(())
+    }
+    fn n_collisions(&self) -> usize {
+        self.entries.iter().filter(|entry| entry.n_rows > 1).count()
+    }
+    pub(crate) fn write(self, out_file: &Path) -> Result<(), Error> {
+        let n_collisions = self.n_collisions();
+        if n_collisions > 0 {
+            let report_file = report_file(out_file);
+            self.write_report(&report_file)?;
+            if let DuplicatePolicy::Error = self.policy {
+                Err(Error::from(format!(
+                    "{} keys occurred more than once for {}. See {} for details.", n_collisions,
+                    out_file.display(), report_file.display()
+                )))?
+            }
+            warn!("{} keys occurred more than once and were resolved using policy '{}'. \
+            See {} for details.", n_collisions, self.policy, report_file.display());
+        } else {
+            info!("No duplicate rows found for {}", out_file.display());
+        }
+        let mut writer = csv::Writer::from_path(out_file)?;
+        for entry in self.entries {
+            writer.serialize(entry.resolve(self.policy))?;
+        }
+        Ok(())
+    }
+    fn write_report(&self, report_file: &Path) -> Result<(), Error> {
+        let mut writer =
+            csv::WriterBuilder::new().delimiter(b'\t').from_path(report_file)?;
+        for entry in self.entries.iter().filter(|entry| entry.n_rows > 1) {
+            let (first, second) = entry.row.key();
+            writer.serialize(CollisionRow {
+                key: format!("{first} / {second}"),
+                n_rows: entry.n_rows,
+                files: entry.files.join(","),
+            })?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+fn report_file(out_file: &Path) -> PathBuf {
+    let stem =
+        out_file.file_stem().map(|stem| stem.to_string_lossy().to_string()).unwrap_or_default();
+    out_file.with_file_name(format!("{stem}_duplicates.tsv"))
+}
+
+impl Display for DuplicatePolicy {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DuplicatePolicy::Max => write!(f, "{}", policy::MAX),
+            DuplicatePolicy::Mean => write!(f, "{}", policy::MEAN),
+            DuplicatePolicy::First => write!(f, "{}", policy::FIRST),
+            DuplicatePolicy::Error => write!(f, "{}", policy::ERROR),
+        }
+    }
+}
+
+impl TryFrom<&str> for DuplicatePolicy {
+    type Error = Error;
+
+    fn try_from(value: &str) -> Result<Self, Error> {
+        match value {
+            policy::MAX => Ok(DuplicatePolicy::Max),
+            policy::MEAN => Ok(DuplicatePolicy::Mean),
+            policy::FIRST => Ok(DuplicatePolicy::First),
+            policy::ERROR => Ok(DuplicatePolicy::Error),
+            _ => Err(Error::from(format!(
+                "Unknown duplicate policy: {value}. Known policies are {}, {}, {} and {}.",
+                policy::MAX, policy::MEAN, policy::FIRST, policy::ERROR
+            ))),
+        }
+    }
+}
+
+impl TryFrom<String> for DuplicatePolicy {
+    type Error = Error;
+
+    fn try_from(value: String) -> Result<Self, Error> {
+        DuplicatePolicy::try_from(value.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Deduplicator, DuplicatePolicy, Keyed, Weighted};
+    use serde::Serialize;
+
+    #[derive(Serialize)]
+    struct Row {
+        pheno: String,
+        gene: String,
+        a: f64,
+        b: f64,
+    }
+
+    fn row(gene: &str, a: f64, b: f64) -> Row {
+        Row { pheno: "T2D".to_string(), gene: gene.to_string(), a, b }
+    }
+
+    impl Keyed for Row {
+        type First = String;
+        type Second = String;
+        fn key(&self) -> (&String, &String) {
+            (&self.pheno, &self.gene)
+        }
+    }
+
+    impl Weighted for Row {
+        fn weights(&self) -> Vec<f64> {
+            vec![self.a, self.b]
+        }
+        fn set_weights(&mut self, weights: &[f64]) {
+            self.a = weights[0];
+            self.b = weights[1];
+        }
+    }
+
+    fn resolve(policy: DuplicatePolicy, rows: Vec<Row>) -> Row {
+        let mut deduplicator = Deduplicator::new(policy);
+        for row in rows {
+            deduplicator.add(row, "file").unwrap();
+        }
+        deduplicator.entries.pop().unwrap().resolve(policy)
+    }
+
+    #[test]
+    fn max_keeps_whole_row() {
+        let rows = vec![row("APOE", 1.0, 5.0), row("APOE", 2.0, 3.0), row("APOE", f64::NAN, 9.0)];
+        let row = resolve(DuplicatePolicy::Max, rows);
+        assert_eq!((row.a, row.b), (2.0, 3.0));
+    }
+
+    #[test]
+    fn mean_ignores_nan() {
+        let rows = vec![row("APOE", 1.0, f64::NAN), row("APOE", f64::NAN, f64::NAN),
+                        row("APOE", 3.0, f64::NAN)];
+        let row = resolve(DuplicatePolicy::Mean, rows);
+        assert_eq!(row.a, 2.0);
+        assert!(row.b.is_nan());
+    }
+
+    #[test]
+    fn rows_are_grouped_by_key() {
+        let mut deduplicator = Deduplicator::new(DuplicatePolicy::First);
+        for (gene, file) in [("APOE", "file1"), ("LDLR", "file1"), ("APOE", "file2")] {
+            deduplicator.add(row(gene, 1.0, 1.0), file).unwrap();
+        }
+        let groups: Vec<(&str, usize, &[String])> = deduplicator.entries.iter()
+            .map(|entry| (entry.row.gene.as_str(), entry.n_rows, entry.files.as_slice()))
+            .collect();
+        let files = ["file1".to_string(), "file2".to_string()];
+        assert_eq!(groups, [("APOE", 2, &files[..]), ("LDLR", 1, &files[..1])]);
+        assert_eq!(deduplicator.n_collisions(), 1);
+    }
+}
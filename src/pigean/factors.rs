@@ -2,18 +2,15 @@ mod gene_factors;
 mod geneset_factors;
 mod pheno_factors;
 
-use std::fmt::Display;
 use std::path::Path;
 use log::info;
 use crate::config::PigeanConfig;
+use crate::context::AppContext;
 use crate::error::Error;
+use crate::hgnc::GeneNormalizer;
+use crate::ids::TraitId;
 use crate::{pigean, s3};
 
-pub(crate) struct Factor {
-    pub(crate) prefix: String,
-    pub(crate) pheno: String,
-}
-
 enum FactorFileKind {
     Genes,
     GeneSets,
@@ -22,16 +19,16 @@ enum FactorFileKind {
 
 pub struct FileInfo {
     pub(crate) path: String,
-    pub(crate) pheno: String,
+    pub(crate) pheno: TraitId,
     kind: FactorFileKind,
 }
 
-pub(crate) fn create_bulk_files(config: &PigeanConfig) -> Result<(), Error> {
+pub(crate) fn create_bulk_files(context: &AppContext, config: &PigeanConfig) -> Result<(), Error> {
     info!("Finding all files in {} for factor-gene-genset relations", config.factors_dir);
     let mut factor_gene_files: Vec<FileInfo> = Vec::new();
     let mut factor_geneset_files: Vec<FileInfo> = Vec::new();
     let mut factor_label_files: Vec<FileInfo> = Vec::new();
-    let data_files = s3::collect(&config.factors_dir)?;
+    let data_files = s3::collect(context, &config.factors_dir)?;
     for data_file in data_files {
         match classify_file(&data_file, &config.factors_sub_dir) {
             Some(file_info) => match file_info.kind {
@@ -54,20 +51,25 @@ pub(crate) fn create_bulk_files(config: &PigeanConfig) -> Result<(), Error> {
         factor_geneset_files.len());
     let factor_gene_file = Path::new(&config.out).join("factor_gene.csv");
     info!("Writing factor-gene file to {}", factor_gene_file.display());
-    gene_factors::add_files(&factor_gene_files, &factor_gene_file)?;
+    let mut genes = GeneNormalizer::for_file(context, config.hgnc_file.as_deref())?;
+    gene_factors::add_files(context, &factor_gene_files, &factor_gene_file, &mut genes,
+                            config.duplicates)?;
+    genes.log_summary();
+    genes.write_ids(&Path::new(&config.out).join("factor_gene_ids.csv"))?;
     let factor_geneset_file = Path::new(&config.out).join("factor_geneset.csv");
     info!("Writing factor-geneset file to {}", factor_geneset_file.display());
-    geneset_factors::add_files(&factor_geneset_files, &factor_geneset_file)?;
+    geneset_factors::add_files(context, &factor_geneset_files, &factor_geneset_file,
+                               config.duplicates)?;
     let factor_pheno_file = Path::new(&config.out).join("factor_phenos.csv");
     info!("Writing factor-pheno file to {}", factor_pheno_file.display());
-    pheno_factors::add_files(&factor_label_files, &factor_pheno_file)?;
+    pheno_factors::add_files(context, &factor_label_files, &factor_pheno_file, config.duplicates)?;
     Ok(())
 }
 
 fn classify_file(file: &str, sub_dir: &str) -> Option<FileInfo> {
     if let Some((pheno, sub, local)) = pigean::last_three_parts(file) {
         if sub == sub_dir {
-            let pheno = pheno.to_string();
+            let pheno = TraitId::new(pheno.to_string());
             let kind = match local {
                 "gc.out" => FactorFileKind::Genes,
                 "gsac.out" => FactorFileKind::GeneSets,
@@ -86,14 +88,3 @@ fn classify_file(file: &str, sub_dir: &str) -> Option<FileInfo> {
         None
     }
 }
-
-impl Factor {
-    pub(crate) fn new(prefix: String, pheno: String) -> Self {
-        Factor { prefix, pheno }
-    }
-}
-impl Display for Factor {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}_{}", self.prefix, self.pheno)
-    }
-}
\ No newline at end of file
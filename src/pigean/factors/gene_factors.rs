@@ -1,32 +1,54 @@
+use crate::context::AppContext;
 use crate::error::Error;
-use crate::pigean::factors::{Factor, FileInfo};
+use crate::hgnc::GeneNormalizer;
+use crate::pigean::dedup::{Deduplicator, DuplicatePolicy, Keyed, Weighted};
+use crate::ids::{FactorId, GeneId, TraitId};
+use crate::pigean::factors::FileInfo;
 use crate::s3;
 use crate::s3::FilePath;
 use crate::tsv::{TsvConsumer, TsvEater, TsvEaterMaker};
 use serde::Serialize;
-use std::io::Write;
 use std::path::Path;
 
 #[derive(Serialize)]
 struct GeneFactor {
-    factor: String,
-    gene: String,
+    factor: FactorId,
+    gene: GeneId,
     weight: f64,
 }
 
-fn write_gene_factor<W: Write>(writer: &mut csv::Writer<W>, item: GeneFactor)
-                               -> Result<(), Error> {
-    writer.serialize(item)?;
-    Ok(())
+impl Keyed for GeneFactor {
+    type First = FactorId;
+    type Second = GeneId;
+    fn key(&self) -> (&FactorId, &GeneId) {
+        (&self.factor, &self.gene)
+    }
+}
+
+impl Weighted for GeneFactor {
+    fn weights(&self) -> Vec<f64> {
+        vec![self.weight]
+    }
+    fn set_weights(&mut self, weights: &[f64]) {
+        self.weight = weights[0];
+    }
+}
+
+fn add_gene_factor(rows: &mut Deduplicator<GeneFactor>, file: &FileInfo, item: GeneFactor)
+                   -> Result<(), Error> {
+    rows.add(item, &file.path)
 }
-fn write_gene_factors<W: Write>(
-    writer: &mut csv::Writer<W>,
+fn add_gene_factors(
+    rows: &mut Deduplicator<GeneFactor>,
+    file: &FileInfo,
     gene_factors: Vec<GeneFactor>,
-    min_weight: f64
+    min_weight: f64,
+    genes: &mut GeneNormalizer,
 ) -> Result<(), Error> {
-    for gene_factor in gene_factors.into_iter() {
+    for mut gene_factor in gene_factors.into_iter() {
         if gene_factor.weight > min_weight {
-            write_gene_factor(writer, gene_factor)?
+            gene_factor.gene = GeneId::new(genes.normalize(gene_factor.gene.as_str()));
+            add_gene_factor(rows, file, gene_factor)?
         }
     }
     Ok(())
@@ -39,13 +61,13 @@ struct FactorWeight {
 }
 
 struct GeneFactorsTsvEater {
-    pheno: String,
+    pheno: TraitId,
     gene: Option<String>,
     factor_weights: Vec<FactorWeight>,
 }
 
 impl GeneFactorsTsvEater {
-    fn new(pheno: String) -> Self {
+    fn new(pheno: TraitId) -> Self {
         GeneFactorsTsvEater {
             pheno,
             gene: None,
@@ -74,10 +96,10 @@ impl TsvEater for GeneFactorsTsvEater {
     }
 
     fn finish(self) -> Result<Self::Row, Error> {
-        let gene = self.gene.ok_or(Error::from("No gene specified"))?;
+        let gene = GeneId::new(self.gene.ok_or(Error::from("No gene specified"))?);
         let gene_factors = self.factor_weights.into_iter().map(|fw| {
             let FactorWeight { prefix, weight } = fw;
-            let factor = Factor::new(prefix, self.pheno.clone()).to_string();
+            let factor = FactorId::new(prefix, self.pheno.clone());
             GeneFactor { factor, gene: gene.clone(), weight, }
         }).collect();
         Ok(gene_factors)
@@ -85,7 +107,7 @@ impl TsvEater for GeneFactorsTsvEater {
 }
 
 struct GeneFactorsTsvEaterMaker {
-    pheno: String,
+    pheno: TraitId,
 }
 
 impl TsvEaterMaker for GeneFactorsTsvEaterMaker {
@@ -96,24 +118,28 @@ impl TsvEaterMaker for GeneFactorsTsvEaterMaker {
     }
 }
 
-fn add_file<W: Write>(
+fn add_file(
+    context: &AppContext,
     file: &FileInfo,
-    writer: &mut csv::Writer<W>,
+    rows: &mut Deduplicator<GeneFactor>,
+    genes: &mut GeneNormalizer,
 ) -> Result<(), Error> {
     let tsv_eater_maker = GeneFactorsTsvEaterMaker { pheno: file.pheno.clone() };
     let mut tsv_consumer =
         TsvConsumer::new('\t', tsv_eater_maker, |gene_factors| {
-            write_gene_factors(writer, gene_factors, 0.01)
+            add_gene_factors(rows, file, gene_factors, 0.01, genes)
         });
     let file_path = FilePath::from_path(&file.path)?;
-    s3::process_file(&file_path, &mut tsv_consumer)
+    s3::process_file(context, &file_path, &mut tsv_consumer)
         .map_err(|e| Error::wrap("Failed to process file".to_string(), e))?;
     Ok(())
 }
-pub(crate) fn add_files(files: &[FileInfo], out_file: &Path) -> Result<(), Error> {
-    let mut writer = csv::WriterBuilder::new().delimiter(b',').from_path(out_file)?;
+pub(crate) fn add_files(context: &AppContext, files: &[FileInfo], out_file: &Path,
+                        genes: &mut GeneNormalizer, duplicates: DuplicatePolicy)
+    -> Result<(), Error> {
+    let mut rows = Deduplicator::new(duplicates);
     for file in files {
-        add_file(file, &mut writer)?;
+        add_file(context, file, &mut rows, genes)?;
     }
-    Ok(())
+    rows.write(out_file)
 }
\ No newline at end of file
@@ -1,32 +1,51 @@
+use crate::context::AppContext;
 use crate::error::Error;
-use crate::pigean::factors::{Factor, FileInfo};
+use crate::pigean::dedup::{Deduplicator, DuplicatePolicy, Keyed, Weighted};
+use crate::ids::{FactorId, GeneSetId, TraitId};
+use crate::pigean::factors::FileInfo;
 use crate::s3;
 use crate::s3::FilePath;
 use crate::tsv::{TsvConsumer, TsvEater, TsvEaterMaker};
 use serde::Serialize;
-use std::io::Write;
 use std::path::Path;
 
 #[derive(Serialize)]
 struct GeneSetFactor {
-    factor: String,
-    gene_set: String,
+    factor: FactorId,
+    gene_set: GeneSetId,
     weight: f64,
 }
 
-fn write_gene_set_factor<W: Write>(writer: &mut csv::Writer<W>, item: GeneSetFactor)
-                                   -> Result<(), Error> {
-    writer.serialize(item)?;
-    Ok(())
+impl Keyed for GeneSetFactor {
+    type First = FactorId;
+    type Second = GeneSetId;
+    fn key(&self) -> (&FactorId, &GeneSetId) {
+        (&self.factor, &self.gene_set)
+    }
+}
+
+impl Weighted for GeneSetFactor {
+    fn weights(&self) -> Vec<f64> {
+        vec![self.weight]
+    }
+    fn set_weights(&mut self, weights: &[f64]) {
+        self.weight = weights[0];
+    }
+}
+
+fn add_gene_set_factor(rows: &mut Deduplicator<GeneSetFactor>, file: &FileInfo,
+                       item: GeneSetFactor) -> Result<(), Error> {
+    rows.add(item, &file.path)
 }
-fn write_set_gene_factors<W: Write>(
-    writer: &mut csv::Writer<W>,
+fn add_set_gene_factors(
+    rows: &mut Deduplicator<GeneSetFactor>,
+    file: &FileInfo,
     gene_factors: Vec<GeneSetFactor>,
     min_weight: f64
 ) -> Result<(), Error> {
     for gene_factor in gene_factors.into_iter() {
         if gene_factor.weight > min_weight {
-            write_gene_set_factor(writer, gene_factor)?
+            add_gene_set_factor(rows, file, gene_factor)?
         }
     }
     Ok(())
@@ -38,13 +57,13 @@ struct FactorWeight {
 }
 
 struct GeneFactorsTsvEater {
-    pheno: String,
+    pheno: TraitId,
     gene_set: Option<String>,
     factor_weights: Vec<FactorWeight>,
 }
 
 impl GeneFactorsTsvEater {
-    fn new(pheno: String) -> Self {
+    fn new(pheno: TraitId) -> Self {
         GeneFactorsTsvEater {
             pheno,
             gene_set: None,
@@ -73,10 +92,11 @@ impl TsvEater for GeneFactorsTsvEater {
     }
 
     fn finish(self) -> Result<Self::Row, Error> {
-        let gene_set = self.gene_set.ok_or(Error::from("No gene set specified"))?;
+        let gene_set =
+            GeneSetId::new(self.gene_set.ok_or(Error::from("No gene set specified"))?);
         let gene_factors = self.factor_weights.into_iter().map(|fw| {
             let FactorWeight { prefix, weight } = fw;
-            let factor = Factor::new(prefix, self.pheno.clone()).to_string();
+            let factor = FactorId::new(prefix, self.pheno.clone());
             GeneSetFactor { factor, gene_set: gene_set.clone(), weight }
         }).collect();
         Ok(gene_factors)
@@ -84,7 +104,7 @@ impl TsvEater for GeneFactorsTsvEater {
 }
 
 struct GeneFactorsTsvEaterMaker {
-    pheno: String,
+    pheno: TraitId,
 }
 
 impl TsvEaterMaker for GeneFactorsTsvEaterMaker {
@@ -95,24 +115,26 @@ impl TsvEaterMaker for GeneFactorsTsvEaterMaker {
     }
 }
 
-fn add_file<W: Write>(
+fn add_file(
+    context: &AppContext,
     file: &FileInfo,
-    writer: &mut csv::Writer<W>,
+    rows: &mut Deduplicator<GeneSetFactor>,
 ) -> Result<(), Error> {
     let tsv_eater_maker = GeneFactorsTsvEaterMaker { pheno: file.pheno.clone() };
     let mut tsv_consumer =
         TsvConsumer::new('\t', tsv_eater_maker, |gene_factors| {
-            write_set_gene_factors(writer, gene_factors, 0.01)
+            add_set_gene_factors(rows, file, gene_factors, 0.01)
         });
     let file_path = FilePath::from_path(&file.path)?;
-    s3::process_file(&file_path, &mut tsv_consumer)
+    s3::process_file(context, &file_path, &mut tsv_consumer)
         .map_err(|e| Error::wrap("Failed to process file".to_string(), e))?;
     Ok(())
 }
-pub(crate) fn add_files(files: &[FileInfo], out_file: &Path) -> Result<(), Error> {
-    let mut writer = csv::Writer::from_path(out_file)?;
+pub(crate) fn add_files(context: &AppContext, files: &[FileInfo], out_file: &Path,
+                        duplicates: DuplicatePolicy) -> Result<(), Error> {
+    let mut rows = Deduplicator::new(duplicates);
     for file in files {
-        add_file(file, &mut writer)?;
+        add_file(context, file, &mut rows)?;
     }
-    Ok(())
+    rows.write(out_file)
 }
\ No newline at end of file
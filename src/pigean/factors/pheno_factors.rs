@@ -1,29 +1,48 @@
+use crate::context::AppContext;
 use crate::error::Error;
-use crate::pigean::factors::{Factor, FileInfo};
+use crate::pigean::dedup::{Deduplicator, DuplicatePolicy, Keyed, Weighted};
+use crate::ids::{FactorId, TraitId};
+use crate::pigean::factors::FileInfo;
 use crate::s3;
 use crate::s3::FilePath;
 use crate::tsv::{TsvConsumer, TsvEater, TsvEaterMaker};
 use serde::Serialize;
-use std::io::Write;
 use std::path::Path;
 
 #[derive(Serialize)]
 struct PhenoFactor {
-    factor: String,
+    factor: FactorId,
     label: String,
-    pheno: String,
+    pheno: TraitId,
     any_relevance: f64
 }
 
+impl Keyed for PhenoFactor {
+    type First = FactorId;
+    type Second = TraitId;
+    fn key(&self) -> (&FactorId, &TraitId) {
+        (&self.factor, &self.pheno)
+    }
+}
+
+impl Weighted for PhenoFactor {
+    fn weights(&self) -> Vec<f64> {
+        vec![self.any_relevance]
+    }
+    fn set_weights(&mut self, weights: &[f64]) {
+        self.any_relevance = weights[0];
+    }
+}
+
 struct FactorLabelTsvEater {
-    pheno: String,
+    pheno: TraitId,
     prefix: Option<String>,
     label: Option<String>,
     any_relevance: f64,
 }
 
 impl FactorLabelTsvEater {
-    fn new(pheno: String) -> Self {
+    fn new(pheno: TraitId) -> Self {
         FactorLabelTsvEater {
             pheno,
             prefix: None,
@@ -52,13 +71,13 @@ impl TsvEater for FactorLabelTsvEater {
         } = self;
         let prefix = prefix.ok_or_else(|| Error::from("Missing factor prefix"))?;
         let label = label.ok_or_else(|| Error::from("Missing factor label"))?;
-        let factor = Factor::new(prefix, pheno.clone()).to_string();
+        let factor = FactorId::new(prefix, pheno.clone());
         Ok(PhenoFactor { factor, label, pheno, any_relevance })
     }
 }
 
 struct FactorLabelsTsvEaterMaker {
-    pheno: String,
+    pheno: TraitId,
 }
 
 impl TsvEaterMaker for FactorLabelsTsvEaterMaker {
@@ -71,31 +90,32 @@ impl TsvEaterMaker for FactorLabelsTsvEaterMaker {
 }
 
 impl FactorLabelsTsvEaterMaker {
-    pub(crate) fn new(pheno: String) -> Self {
+    pub(crate) fn new(pheno: TraitId) -> Self {
         FactorLabelsTsvEaterMaker { pheno }
     }
 }
 
-fn add_file<W: Write>(
+fn add_file(
+    context: &AppContext,
     file: &FileInfo,
-    writer: &mut csv::Writer<W>,
+    rows: &mut Deduplicator<PhenoFactor>,
 ) -> Result<(), Error> {
     let tsv_eater_maker = FactorLabelsTsvEaterMaker::new(file.pheno.clone());
     let mut tsv_consumer =
         TsvConsumer::new('\t', tsv_eater_maker, |item| {
-            writer.serialize(item)?;
-            Ok(())
+            rows.add(item, &file.path)
     });
     let file_path = FilePath::from_path(&file.path)?;
-    s3::process_file(&file_path, &mut tsv_consumer)
+    s3::process_file(context, &file_path, &mut tsv_consumer)
         .map_err(|e| Error::wrap("Failed to process file".to_string(), e))?;
     Ok(())
 }
 
-pub(crate) fn add_files(files: &[FileInfo], out_file: &Path) -> Result<(), Error> {
-    let mut writer = csv::Writer::from_path(out_file)?;
+pub(crate) fn add_files(context: &AppContext, files: &[FileInfo], out_file: &Path,
+                        duplicates: DuplicatePolicy) -> Result<(), Error> {
+    let mut rows = Deduplicator::new(duplicates);
     for file in files {
-        add_file(file, &mut writer)?;
+        add_file(context, file, &mut rows)?;
     }
-    Ok(())
+    rows.write(out_file)
 }
\ No newline at end of file
@@ -1,17 +1,14 @@
 mod pheno_pgs;
 mod gene_pgs;
 
-use std::fmt::{Display, Formatter};
 use std::path::Path;
 use log::info;
 use crate::config::PigeanConfig;
+use crate::context::AppContext;
+use crate::hgnc::GeneNormalizer;
+use crate::ids::TraitId;
 use crate::{pigean, s3};
 
-pub(crate) struct PhenoGeneSet {
-    pub(crate) pheno: String,
-    pub(crate) gene_set: String,
-}
-
 enum FileKind {
     PhenoGeneSet,
     GeneGeneSet
@@ -19,15 +16,15 @@ enum FileKind {
 
 pub(crate) struct FileInfo {
     pub(crate) path: String,
-    pub(crate) pheno: String,
+    pub(crate) pheno: TraitId,
     kind: FileKind,
 }
 
-pub fn create_bulk_files(config: &PigeanConfig) -> Result<(), pigean::Error> {
+pub fn create_bulk_files(context: &AppContext, config: &PigeanConfig) -> Result<(), pigean::Error> {
     info!("Finding all files in {} for pheno-gene-geneset relations", config.data_dir);
     let mut gene_pgs_files: Vec<FileInfo> = Vec::new();
     let mut pheno_pgs_files: Vec<FileInfo> = Vec::new();
-    let data_files = s3::collect(&config.data_dir)?;
+    let data_files = s3::collect(context, &config.data_dir)?;
     for data_file in data_files {
         match classify_file(&data_file, &config.sub_dir) {
             Some(file_info) => match file_info.kind {
@@ -48,11 +45,14 @@ pub fn create_bulk_files(config: &PigeanConfig) -> Result<(), pigean::Error> {
 
     let pheno_pgs_file = Path::new(&config.out).join("pheno_geneset.csv");
     info!("Writing pheno-geneset file to {}", pheno_pgs_file.display());
-    pheno_pgs::add_files(&pheno_pgs_files, &pheno_pgs_file)?;
+    pheno_pgs::add_files(context, &pheno_pgs_files, &pheno_pgs_file, config.duplicates)?;
 
     let gene_pgs_file = Path::new(&config.out).join("gene_geneset.csv");
     info!("Writing gene-geneset file to {}", pheno_pgs_file.display());
-    gene_pgs::add_files(&gene_pgs_files, &gene_pgs_file)?;
+    let mut genes = GeneNormalizer::for_file(context, config.hgnc_file.as_deref())?;
+    gene_pgs::add_files(context, &gene_pgs_files, &gene_pgs_file, &mut genes, config.duplicates)?;
+    genes.log_summary();
+    genes.write_ids(&Path::new(&config.out).join("gene_geneset_ids.csv"))?;
 
     info!("Finished writing pheno-pheno-geneset and gene-pheno-geneset files");
     Ok(())
@@ -61,7 +61,7 @@ pub fn create_bulk_files(config: &PigeanConfig) -> Result<(), pigean::Error> {
 pub(crate) fn classify_file(file: &str, sub_dir: &str) -> Option<FileInfo> {
     if let Some((pheno, sub, local)) = pigean::last_three_parts(file) {
         if sub == sub_dir {
-            let pheno = pheno.to_string();
+            let pheno = TraitId::new(pheno.to_string());
             let file_kind = match local {
                 "gss.out" => FileKind::PhenoGeneSet,
                 "ggss.out" => FileKind::GeneGeneSet,
@@ -79,15 +79,3 @@ pub(crate) fn classify_file(file: &str, sub_dir: &str) -> Option<FileInfo> {
         None
     }
 }
-
-impl PhenoGeneSet {
-    pub fn new(pheno: String, gene_set: String) -> Self {
-        PhenoGeneSet { pheno, gene_set }
-    }
-}
-
-impl Display for PhenoGeneSet {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}:{}", self.gene_set, self.pheno)
-    }
-}
@@ -1,37 +1,59 @@
+use crate::context::AppContext;
 use crate::error::Error;
-use crate::pigean::pgs::{FileInfo, PhenoGeneSet};
+use crate::hgnc::GeneNormalizer;
+use crate::pigean::dedup::{Deduplicator, DuplicatePolicy, Keyed, Weighted};
+use crate::ids::{GeneId, GeneSetId, TraitGeneSetId, TraitId};
+use crate::pigean::pgs::FileInfo;
 use crate::s3;
 use crate::s3::FilePath;
 use crate::tsv::{TsvConsumer, TsvEater, TsvEaterMaker};
 use serde::Serialize;
-use std::io::Write;
 use std::path::Path;
 
 #[derive(Serialize)]
 pub(crate) struct GenePgs {
-    pub gene: String,
-    pub pgs: String,
+    pub gene: GeneId,
+    pub pgs: TraitGeneSetId,
     pub beta: f64,
 }
 
-pub(crate) fn write_gene_pgs<W: Write>(
-    writer: &mut csv::Writer<W>, item: GenePgs, min_beta: f64,
+impl Keyed for GenePgs {
+    type First = GeneId;
+    type Second = TraitGeneSetId;
+    fn key(&self) -> (&GeneId, &TraitGeneSetId) {
+        (&self.gene, &self.pgs)
+    }
+}
+
+impl Weighted for GenePgs {
+    fn weights(&self) -> Vec<f64> {
+        vec![self.beta]
+    }
+    fn set_weights(&mut self, weights: &[f64]) {
+        self.beta = weights[0];
+    }
+}
+
+pub(crate) fn add_gene_pgs(
+    rows: &mut Deduplicator<GenePgs>, file: &FileInfo, mut item: GenePgs, min_beta: f64,
+    genes: &mut GeneNormalizer,
 ) -> Result<(), Error> {
     if item.beta > min_beta {
-        writer.serialize(item)?;
+        item.gene = GeneId::new(genes.normalize(item.gene.as_str()));
+        rows.add(item, &file.path)?;
     }
     Ok(())
 }
 
 pub(crate) struct GenePgsTsvEater {
-    pheno: String,
+    pheno: TraitId,
     gene: Option<String>,
     gene_set: Option<String>,
     beta: f64,
 }
 
 impl GenePgsTsvEater {
-    pub(crate) fn new(pheno: String) -> Self {
+    pub(crate) fn new(pheno: TraitId) -> Self {
         GenePgsTsvEater {
             pheno,
             gene: None,
@@ -58,19 +80,19 @@ impl TsvEater for GenePgsTsvEater {
         let GenePgsTsvEater {
             pheno, gene, gene_set, beta,
         } = self;
-        let gene = gene.ok_or_else(|| Error::from("Missing gene"))?;
-        let gene_set = gene_set.ok_or_else(|| Error::from("Missing gene set"))?;
-        let pgs = PhenoGeneSet::new(pheno, gene_set).to_string();
+        let gene = GeneId::new(gene.ok_or_else(|| Error::from("Missing gene"))?);
+        let gene_set = GeneSetId::new(gene_set.ok_or_else(|| Error::from("Missing gene set"))?);
+        let pgs = TraitGeneSetId::new(gene_set, pheno);
         Ok(GenePgs { gene, pgs, beta })
     }
 }
 
 struct GenePgsTsvEaterMaker {
-    pheno: String,
+    pheno: TraitId,
 }
 
 impl GenePgsTsvEaterMaker {
-    pub(crate) fn new(pheno: String) -> Self {
+    pub(crate) fn new(pheno: TraitId) -> Self {
         GenePgsTsvEaterMaker { pheno }
     }
 }
@@ -84,23 +106,27 @@ impl TsvEaterMaker for GenePgsTsvEaterMaker {
     }
 }
 
-fn add_file<W: Write>(file: &FileInfo, writer: &mut csv::Writer<W>) -> Result<(), Error> {
+fn add_file(context: &AppContext, file: &FileInfo, rows: &mut Deduplicator<GenePgs>,
+            genes: &mut GeneNormalizer)
+    -> Result<(), Error> {
     let tsv_eater_maker = GenePgsTsvEaterMaker::new(file.pheno.clone());
     let mut tsv_consumer =
         TsvConsumer::new('\t', tsv_eater_maker, |item| {
-            write_gene_pgs(writer, item, 0.01)
+            add_gene_pgs(rows, file, item, 0.01, genes)
         });
     let file_path = FilePath::from_path(&file.path)
         .map_err(|e| Error::wrap(format!("Could not use {} as path", file.path), e))?;
-    s3::process_file(&file_path, &mut tsv_consumer)
+    s3::process_file(context, &file_path, &mut tsv_consumer)
         .map_err(|e| Error::wrap(format!("Failed to process {}", file.path), e))?;
     Ok(())
 }
 
-pub(crate) fn add_files(files: &[FileInfo], out_file: &Path) -> Result<(), Error> {
-    let mut writer = csv::Writer::from_path(out_file)?;
+pub(crate) fn add_files(context: &AppContext, files: &[FileInfo], out_file: &Path,
+                        genes: &mut GeneNormalizer, duplicates: DuplicatePolicy)
+    -> Result<(), Error> {
+    let mut rows = Deduplicator::new(duplicates);
     for file in files {
-        add_file(file, &mut writer)?;
+        add_file(context, file, &mut rows, genes)?;
     }
-    Ok(())
+    rows.write(out_file)
 }
@@ -1,24 +1,26 @@
+use crate::context::AppContext;
 use crate::error::Error;
-use crate::pigean::pgs::{FileInfo, PhenoGeneSet};
+use crate::pigean::dedup::{Deduplicator, DuplicatePolicy, Keyed, Weighted};
+use crate::ids::{GeneSetId, TraitGeneSetId, TraitId};
+use crate::pigean::pgs::FileInfo;
 use crate::s3;
 use crate::s3::FilePath;
 use crate::tsv::{TsvConsumer, TsvEater, TsvEaterMaker};
 use serde::Serialize;
-use std::io::Write;
 use std::path::Path;
 
 pub(crate) struct PhenoPgs {
-    pub(crate) pgs: PhenoGeneSet,
+    pub(crate) pgs: TraitGeneSetId,
     pub(crate) source: String,
     pub(crate) beta_uncorrected: f64,
     pub(crate) beta: f64,
 }
 
 impl PhenoPgs {
-    fn into_row(self, pheno: &str) -> PhenoPgsRow {
+    fn into_row(self, pheno: &TraitId) -> PhenoPgsRow {
         PhenoPgsRow {
-            pheno: pheno.to_string(),
-            pgs: self.pgs.to_string(),
+            pheno: pheno.clone(),
+            pgs: self.pgs,
             source: self.source,
             beta_uncorrected: self.beta_uncorrected,
             beta: self.beta,
@@ -28,25 +30,43 @@ impl PhenoPgs {
 
 #[derive(Serialize)]
 pub(crate) struct PhenoPgsRow {
-    pub(crate) pheno: String,
-    pub(crate) pgs: String,
+    pub(crate) pheno: TraitId,
+    pub(crate) pgs: TraitGeneSetId,
     pub(crate) source: String,
     pub(crate) beta_uncorrected: f64,
     pub(crate) beta: f64,
 }
 
-pub(crate) fn write_pheno_pgs<W: Write>(
-    writer: &mut csv::Writer<W>, pheno: &str, item: PhenoPgs, min_beta: f64,
+impl Keyed for PhenoPgsRow {
+    type First = TraitId;
+    type Second = TraitGeneSetId;
+    fn key(&self) -> (&TraitId, &TraitGeneSetId) {
+        (&self.pheno, &self.pgs)
+    }
+}
+
+impl Weighted for PhenoPgsRow {
+    fn weights(&self) -> Vec<f64> {
+        vec![self.beta_uncorrected, self.beta]
+    }
+    fn set_weights(&mut self, weights: &[f64]) {
+        self.beta_uncorrected = weights[0];
+        self.beta = weights[1];
+    }
+}
+
+pub(crate) fn add_pheno_pgs(
+    rows: &mut Deduplicator<PhenoPgsRow>, file: &FileInfo, item: PhenoPgs, min_beta: f64,
 ) -> Result<(), Error> {
     if item.beta > min_beta {
-        let row = item.into_row(pheno);
-        writer.serialize(row)?;
+        let row = item.into_row(&file.pheno);
+        rows.add(row, &file.path)?;
     }
     Ok(())
 }
 
 pub(crate) struct PhenosPgsTsvEater {
-    pheno: String,
+    pheno: TraitId,
     gene_set: Option<String>,
     label: Option<String>,
     beta_uncorrected: f64,
@@ -54,7 +74,7 @@ pub(crate) struct PhenosPgsTsvEater {
 }
 
 impl PhenosPgsTsvEater {
-    pub(crate) fn new(pheno: String) -> Self {
+    pub(crate) fn new(pheno: TraitId) -> Self {
         PhenosPgsTsvEater {
             pheno,
             gene_set: None,
@@ -83,19 +103,19 @@ impl TsvEater for PhenosPgsTsvEater {
         let PhenosPgsTsvEater {
             pheno, gene_set, label, beta_uncorrected, beta,
         } = self;
-        let gene_set = gene_set.ok_or_else(|| Error::from("Missing gene set"))?;
+        let gene_set = GeneSetId::new(gene_set.ok_or_else(|| Error::from("Missing gene set"))?);
         let label = label.ok_or_else(|| Error::from("Missing label"))?;
-        let pgs = PhenoGeneSet::new(pheno, gene_set);
+        let pgs = TraitGeneSetId::new(gene_set, pheno);
         Ok(PhenoPgs { pgs, source: label, beta_uncorrected, beta })
     }
 }
 
 struct PhenoPgsTsvEaterMaker {
-    pheno: String,
+    pheno: TraitId,
 }
 
 impl PhenoPgsTsvEaterMaker {
-    pub(crate) fn new(pheno: String) -> Self {
+    pub(crate) fn new(pheno: TraitId) -> Self {
         PhenoPgsTsvEaterMaker { pheno }
     }
 }
@@ -109,23 +129,25 @@ impl TsvEaterMaker for PhenoPgsTsvEaterMaker {
     }
 }
 
-fn add_file<W: Write>(file: &FileInfo, writer: &mut csv::Writer<W>) -> Result<(), Error> {
+fn add_file(context: &AppContext, file: &FileInfo, rows: &mut Deduplicator<PhenoPgsRow>)
+    -> Result<(), Error> {
     let tsv_eater_maker = PhenoPgsTsvEaterMaker::new(file.pheno.clone());
     let mut tsv_consumer =
         TsvConsumer::new('\t', tsv_eater_maker, |item| {
-            write_pheno_pgs(writer, &file.pheno, item, 0.01)
+            add_pheno_pgs(rows, file, item, 0.01)
         });
     let file_path = FilePath::from_path(&file.path)
         .map_err(|e| Error::wrap(format!("Could not use {} as path", file.path), e))?;
-    s3::process_file(&file_path, &mut tsv_consumer)
+    s3::process_file(context, &file_path, &mut tsv_consumer)
         .map_err(|e| Error::wrap(format!("Failed to process {}", file.path), e))?;
     Ok(())
 }
 
-pub(crate) fn add_files(files: &[FileInfo], out_file: &Path) -> Result<(), Error> {
-    let mut writer = csv::Writer::from_path(out_file)?;
+pub(crate) fn add_files(context: &AppContext, files: &[FileInfo], out_file: &Path,
+                        duplicates: DuplicatePolicy) -> Result<(), Error> {
+    let mut rows = Deduplicator::new(duplicates);
     for file in files {
-        add_file(file, &mut writer)?;
+        add_file(context, file, &mut rows)?;
     }
-    Ok(())
+    rows.write(out_file)
 }
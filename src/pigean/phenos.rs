@@ -1,7 +1,10 @@
 use std::path::Path;
 use log::info;
 use crate::config::PigeanConfig;
+use crate::context::AppContext;
 use crate::error::Error;
+use crate::hgnc::GeneNormalizer;
+use crate::ids::TraitId;
 use crate::{pigean, s3};
 mod pheno_genes;
 mod pheno_genesets;
@@ -13,15 +16,15 @@ enum FileKind {
 
 pub struct FileInfo {
     pub(crate) path: String,
-    pub(crate) pheno: String,
+    pub(crate) pheno: TraitId,
     kind: FileKind,
 }
 
-pub fn create_bulk_files(config: &PigeanConfig) -> Result<(), Error> {
+pub fn create_bulk_files(context: &AppContext, config: &PigeanConfig) -> Result<(), Error> {
     info!("Finding all files in {} for pheno-gene-geneset relations", config.data_dir);
     let mut pheno_gene_files: Vec<FileInfo> = Vec::new();
     let mut pheno_geneset_files: Vec<FileInfo> = Vec::new();
-    let data_files = s3::collect(&config.data_dir)?;
+    let data_files = s3::collect(context, &config.data_dir)?;
     for data_file in data_files {
         match classify_file(&data_file, &config.sub_dir) {
             Some(file_info) => match file_info.kind {
@@ -39,12 +42,17 @@ pub fn create_bulk_files(config: &PigeanConfig) -> Result<(), Error> {
     }
     info!("Found {} pheno-gene files and {} pheno-geneset files", pheno_gene_files.len(), 
         pheno_geneset_files.len());
+    let mut genes = GeneNormalizer::for_file(context, config.hgnc_file.as_deref())?;
     let pheno_gene_file = Path::new(&config.out).join("pheno_gene.csv");
     info!("Writing pheno-gene file to {}", pheno_gene_file.display());   
-    pheno_genes::add_files(&pheno_gene_files, &pheno_gene_file)?;
+    pheno_genes::add_files(context, &pheno_gene_files, &pheno_gene_file, &mut genes,
+                           config.duplicates)?;
+    genes.log_summary();
+    genes.write_ids(&Path::new(&config.out).join("pheno_gene_ids.csv"))?;
     let pheno_geneset_file = Path::new(&config.out).join("pheno_geneset.csv");
     info!("Writing pheno-genset file to {}", pheno_geneset_file.display());
-    pheno_genesets::add_files(&pheno_geneset_files, &pheno_geneset_file)?;
+    pheno_genesets::add_files(context, &pheno_geneset_files, &pheno_geneset_file,
+                              config.duplicates)?;
     info!("Finished writing pheno-gene and pheno-genset files");
     Ok(())
 }
@@ -52,7 +60,7 @@ pub fn create_bulk_files(config: &PigeanConfig) -> Result<(), Error> {
 fn classify_file(file: &str, sub_dir: &str) -> Option<FileInfo> {
     if let Some((pheno, sub, local)) = pigean::last_three_parts(file) {
         if sub == sub_dir {
-            let pheno = pheno.to_string();
+            let pheno = TraitId::new(pheno.to_string());
             let file_kind = match local {
                 "gss.out" => FileKind::PhenoGeneSet,
                 "gs.out" => FileKind::PhenoGene,
@@ -1,10 +1,13 @@
+use crate::context::AppContext;
 use crate::error::Error;
+use crate::hgnc::GeneNormalizer;
+use crate::ids::{GeneId, TraitId};
+use crate::pigean::dedup::{Deduplicator, DuplicatePolicy, Keyed, Weighted};
 use crate::pigean::phenos::FileInfo;
 use crate::s3;
 use crate::s3::FilePath;
 use crate::tsv::{TsvConsumer, TsvEater, TsvEaterMaker};
 use serde::Serialize;
-use std::io::Write;
 use std::path::Path;
 
 pub(crate) struct PhenoGene {
@@ -15,10 +18,10 @@ pub(crate) struct PhenoGene {
 }
 
 impl PhenoGene {
-    fn into_row(self, pheno: &str) -> PhenoGeneRow {
+    fn into_row(self, pheno: &TraitId) -> PhenoGeneRow {
         PhenoGeneRow {
-            pheno: pheno.to_string(),
-            gene: self.gene,
+            pheno: pheno.clone(),
+            gene: GeneId::new(self.gene),
             combined: self.combined,
             log_bf: self.log_bf,
             prior: self.prior,
@@ -28,18 +31,36 @@ impl PhenoGene {
 
 #[derive(Serialize)]
 struct PhenoGeneRow {
-    pub(crate) pheno: String,
-    pub(crate) gene: String,
+    pub(crate) pheno: TraitId,
+    pub(crate) gene: GeneId,
     pub(crate) combined: f64,
     pub(crate) log_bf: f64,
     pub(crate) prior: f64,
 }
 
-fn write_pheno_gene<W: Write>(writer: &mut csv::Writer<W>, pheno: &str, item: PhenoGene)
+impl Keyed for PhenoGeneRow {
+    type First = TraitId;
+    type Second = GeneId;
+    fn key(&self) -> (&TraitId, &GeneId) {
+        (&self.pheno, &self.gene)
+    }
+}
+
+impl Weighted for PhenoGeneRow {
+    fn weights(&self) -> Vec<f64> {
+        vec![self.combined, self.log_bf, self.prior]
+    }
+    fn set_weights(&mut self, weights: &[f64]) {
+        self.combined = weights[0];
+        self.log_bf = weights[1];
+        self.prior = weights[2];
+    }
+}
+
+fn add_pheno_gene(rows: &mut Deduplicator<PhenoGeneRow>, file: &FileInfo, item: PhenoGene)
     -> Result<(), Error> {
-    let row = item.into_row(pheno);
-    writer.serialize(row)?;
-    Ok(())
+    let row = item.into_row(&file.pheno);
+    rows.add(row, &file.path)
 }
 
 
@@ -99,28 +120,33 @@ impl TsvEaterMaker for PhenosGenesTsvEaterMaker {
         PhenosGenesTsvEater::new()
     }
 }
-fn add_file<W: Write>(
+fn add_file(
+    context: &AppContext,
     file: &FileInfo,
-    writer: &mut csv::Writer<W>,
+    rows: &mut Deduplicator<PhenoGeneRow>,
+    genes: &mut GeneNormalizer,
 ) -> Result<(), Error> {
     let mut tsv_consumer = 
-        TsvConsumer::new('\t', PhenosGenesTsvEaterMaker {}, |pheno_gene| {
+        TsvConsumer::new('\t', PhenosGenesTsvEaterMaker {}, |mut pheno_gene| {
         if pheno_gene.combined > 1.0 {
-            write_pheno_gene(writer, &file.pheno, pheno_gene)
+            pheno_gene.gene = genes.normalize(&pheno_gene.gene);
+            add_pheno_gene(rows, file, pheno_gene)
         } else {
             Ok(())
         }
     });
     let file_path = FilePath::from_path(&file.path)?;
-    s3::process_file(&file_path, &mut tsv_consumer)
+    s3::process_file(context, &file_path, &mut tsv_consumer)
         .map_err(|e| Error::wrap("Failed to process file".to_string(), e))?;
     Ok(())
 }
 
-pub(crate) fn add_files(files: &[FileInfo], out_file: &Path) -> Result<(), Error> {
-    let mut writer = csv::Writer::from_path(out_file)?;
+pub(crate) fn add_files(context: &AppContext, files: &[FileInfo], out_file: &Path,
+                        genes: &mut GeneNormalizer, duplicates: DuplicatePolicy)
+    -> Result<(), Error> {
+    let mut rows = Deduplicator::new(duplicates);
     for file in files {
-        add_file(file, &mut writer)?;
+        add_file(context, file, &mut rows, genes)?;
     }
-    Ok(())
+    rows.write(out_file)
 }
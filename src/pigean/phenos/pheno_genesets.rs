@@ -1,10 +1,12 @@
+use crate::context::AppContext;
 use crate::error::Error;
+use crate::ids::{GeneSetId, TraitId};
+use crate::pigean::dedup::{Deduplicator, DuplicatePolicy, Keyed, Weighted};
 use crate::pigean::phenos::FileInfo;
 use crate::s3;
 use crate::s3::FilePath;
 use crate::tsv::{TsvConsumer, TsvEater, TsvEaterMaker};
 use serde::Serialize;
-use std::io::Write;
 use std::path::Path;
 
 pub(crate) struct PhenoGeneset {
@@ -14,10 +16,10 @@ pub(crate) struct PhenoGeneset {
 }
 
 impl PhenoGeneset {
-    fn into_row(self, pheno: &str) -> PhenoGenesetRow {
+    fn into_row(self, pheno: &TraitId) -> PhenoGenesetRow {
         PhenoGenesetRow {
-            pheno: pheno.to_string(),
-            gene_set: self.gene_set,
+            pheno: pheno.clone(),
+            gene_set: GeneSetId::new(self.gene_set),
             beta_uncorrected: self.beta_uncorrected,
             beta: self.beta,
         }
@@ -26,20 +28,37 @@ impl PhenoGeneset {
 
 #[derive(Serialize)]
 struct PhenoGenesetRow {
-    pub(crate) pheno: String,
-    pub(crate) gene_set: String,
+    pub(crate) pheno: TraitId,
+    pub(crate) gene_set: GeneSetId,
     pub(crate) beta_uncorrected: f64,
     pub(crate) beta: f64,
 }
 
-fn write_pheno_geneset<W: Write>(
-    writer: &mut csv::Writer<W>,
-    pheno: &str,
+impl Keyed for PhenoGenesetRow {
+    type First = TraitId;
+    type Second = GeneSetId;
+    fn key(&self) -> (&TraitId, &GeneSetId) {
+        (&self.pheno, &self.gene_set)
+    }
+}
+
+impl Weighted for PhenoGenesetRow {
+    fn weights(&self) -> Vec<f64> {
+        vec![self.beta_uncorrected, self.beta]
+    }
+    fn set_weights(&mut self, weights: &[f64]) {
+        self.beta_uncorrected = weights[0];
+        self.beta = weights[1];
+    }
+}
+
+fn add_pheno_geneset(
+    rows: &mut Deduplicator<PhenoGenesetRow>,
+    file: &FileInfo,
     item: PhenoGeneset,
 ) -> Result<(), Error> {
-    let pheno_geneset_row = item.into_row(pheno);
-    writer.serialize(pheno_geneset_row)?;
-    Ok(())
+    let pheno_geneset_row = item.into_row(&file.pheno);
+    rows.add(pheno_geneset_row, &file.path)
 }
 
 struct PhenosGenesetTsvEater {
@@ -96,27 +115,29 @@ impl TsvEaterMaker for PhenosGenesetTsvEaterMaker {
     }
 }
 
-fn add_file<W: Write>(
+fn add_file(
+    context: &AppContext,
     file: &FileInfo,
-    writer: &mut csv::Writer<W>,
+    rows: &mut Deduplicator<PhenoGenesetRow>,
 ) -> Result<(), Error> {
     let mut tsv_consumer =
         TsvConsumer::new('\t', PhenosGenesetTsvEaterMaker {}, |item| {
             if item.beta_uncorrected > 0.01 {
-                write_pheno_geneset(writer, &file.pheno, item)
+                add_pheno_geneset(rows, file, item)
             } else {
                 Ok(())
             }
         });
     let file_path = FilePath::from_path(&file.path)?;
-    s3::process_file(&file_path, &mut tsv_consumer)?;
+    s3::process_file(context, &file_path, &mut tsv_consumer)?;
     Ok(())
 }
 
-pub(crate) fn add_files(files: &[FileInfo], out_file: &Path) -> Result<(), Error> {
-    let mut writer = csv::Writer::from_path(out_file)?;
+pub(crate) fn add_files(context: &AppContext, files: &[FileInfo], out_file: &Path,
+                        duplicates: DuplicatePolicy) -> Result<(), Error> {
+    let mut rows = Deduplicator::new(duplicates);
     for file in files {
-        add_file(file, &mut writer)?;
+        add_file(context, file, &mut rows)?;
     }
-    Ok(())
+    rows.write(out_file)
 }
\ No newline at end of file
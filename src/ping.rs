@@ -2,8 +2,9 @@ use std::time::{Duration, UNIX_EPOCH};
 use humantime::format_rfc3339_millis;
 use log::info;
 use crate::config::ClientConfig;
+use crate::context::AppContext;
 use crate::error::Error;
-use crate::neo::RowEater;
+use crate::neo::{Neo, RowEater};
 use neo4rs::{Query, Row};
 
 const PING_QUERY: &str = "RETURN timestamp()";
@@ -30,12 +31,18 @@ impl RowEater for PingRowEater {
     }
 }
 
-pub(crate) fn ping_neo4j(config: &ClientConfig) -> Result<(), Error> {
-    let neo = crate::neo::Neo::for_config(&config.neo4j)?;
+/// The server time of Neo4j, formatted as RFC 3339.
+pub(crate) fn neo4j_time(neo: &Neo) -> Result<String, Error> {
     let query = Query::new(PING_QUERY.to_string());
     let mut row_eater = PingRowEater::new();
-    let timestamp = neo.cypher(query, &mut row_eater)?;
-    info!("Neo4j is up and running as of {}",
-        format_rfc3339_millis(UNIX_EPOCH + Duration::from_millis(timestamp)));
+    let timestamp = neo.read(query, &mut row_eater)?;
+    Ok(format_rfc3339_millis(UNIX_EPOCH + Duration::from_millis(timestamp)).to_string())
+}
+
+pub(crate) fn ping_neo4j(context: &AppContext, config: &ClientConfig) -> Result<(), Error> {
+    let neo = context.neo(&config.neo4j)?;
+    info!("Neo4j is up and running as of {}", neo4j_time(&neo)?);
+    info!("Connected to {} as {} with password from {}.", config.neo4j.uri, config.neo4j.user,
+        config.neo4j.password_source);
     Ok(())
 }
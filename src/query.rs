@@ -0,0 +1,264 @@
+use crate::config::QueryConfig;
+use crate::context::AppContext;
+use crate::error::Error;
+use crate::neo::{Neo, RowEater};
+use crate::output;
+use crate::output::{OutputFormat, RecordWriter};
+use log::info;
+use neo4rs::{BoltMap, BoltType, Query, Row};
+use serde_json::Value;
+use std::io::Write;
+
+const RETURN: &str = "RETURN";
+const YIELD: &str = "YIELD";
+const AS: &str = "AS";
+const CLAUSES_AFTER_RETURN: [&str; 5] = ["ORDER", "SKIP", "LIMIT", "UNION", "WHERE"];
+const BROWSER_COMMAND_PREFIX: char = ':';
+
+/// Streams rows to a record writer. The columns are the keys of the first row, in the order of
+/// the `RETURN` or `YIELD` clause if that names the same columns, otherwise sorted.
+struct QueryRowEater<'a> {
+    format: OutputFormat,
+    statement_columns: Option<Vec<String>>,
+    columns: Option<Vec<String>>,
+    writer: Option<&'a mut dyn Write>,
+    record_writer: Option<RecordWriter<'a>>,
+    n_rows: usize,
+}
+
+impl<'a> QueryRowEater<'a> {
+    fn new(format: OutputFormat, statement_columns: Option<Vec<String>>,
+           writer: &'a mut dyn Write) -> QueryRowEater<'a> {
+        QueryRowEater {
+            format, statement_columns, columns: None, writer: Some(writer), record_writer: None,
+            n_rows: 0,
+        }
+    }
+}
+
+impl RowEater for QueryRowEater<'_> {
+    type Summary = usize;
+    fn eat(&mut self, row: Row) -> Result<(), Error> {
+        let columns = match &self.columns {
+            Some(columns) => columns.clone(),
+            None => {
+                let columns = row_columns(&row, self.statement_columns.take())?;
+                self.columns = Some(columns.clone());
+                columns
+            }
+        };
+        if let Some(writer) = self.writer.take() {
+            self.record_writer = Some(RecordWriter::new(self.format, columns.clone(), writer)?);
+        }
+        let values = columns.iter().map(|column| {
+            row.get::<Value>(column).map_err(Error::from)
+        }).collect::<Result<Vec<Value>, Error>>()?;
+        if let Some(record_writer) = &mut self.record_writer {
+            record_writer.write(&values)?;
+        }
+        self.n_rows += 1;
+        Ok(())
+    }
+    fn finish(&mut self) -> Result<usize, Error> {
+        if let Some(record_writer) = self.record_writer.take() {
+            record_writer.finish()?;
+        }
+        Ok(self.n_rows)
+    }
+}
+
+fn row_columns(row: &Row, statement_columns: Option<Vec<String>>) -> Result<Vec<String>, Error> {
+    let map: BoltMap = row.to()?;
+    let mut keys: Vec<String> = map.value.keys().map(|key| key.value.clone()).collect();
+    keys.sort();
+    if let Some(statement_columns) = statement_columns {
+        let mut sorted = statement_columns.clone();
+        sorted.sort();
+        if sorted == keys {
+            return Ok(statement_columns);
+        }
+    }
+    Ok(keys)
+}
+
+/// Splits Cypher text into statements at semicolons, skipping `//` comments and Neo4j Browser
+/// commands like `:auto`. Semicolons in strings and comments do not split.
+pub(crate) fn split_statements(text: &str) -> Vec<String> {
+    let mut statements: Vec<String> = Vec::new();
+    let mut statement = String::new();
+    for line in text.lines() {
+        if line.trim_start().starts_with(BROWSER_COMMAND_PREFIX) && statement.trim().is_empty() {
+            continue;
+        }
+        let mut quote: Option<char> = None;
+        let mut chars = line.chars().peekable();
+        while let Some(c) = chars.next() {
+            match quote {
+                Some(q) => {
+                    statement.push(c);
+                    if c == '\\' {
+                        if let Some(escaped) = chars.next() {
+                            statement.push(escaped);
+                        }
+                    } else if c == q {
+                        quote = None;
+                    }
+                }
+                None => match c {
+                    '\'' | '"' | '`' => {
+                        quote = Some(c);
+                        statement.push(c);
+                    }
+                    '/' if chars.peek() == Some(&'/') => break,
+                    ';' => {
+                        statements.push(std::mem::take(&mut statement));
+                    }
+                    _ => statement.push(c),
+                },
+            }
+        }
+        statement.push('\n');
+    }
+    statements.push(statement);
+    statements.into_iter().map(|statement| statement.trim().to_string())
+        .filter(|statement| !statement.is_empty()).collect()
+}
+
+/// Splits at top-level separators, outside brackets and quotes.
+fn split_top_level(text: &str, is_separator: impl Fn(char) -> bool) -> Vec<&str> {
+    let mut parts: Vec<&str> = Vec::new();
+    let mut depth: usize = 0;
+    let mut quote: Option<char> = None;
+    let mut start: usize = 0;
+    for (i, c) in text.char_indices() {
+        match quote {
+            Some(q) => {
+                if c == q {
+                    quote = None;
+                }
+            }
+            None => match c {
+                '\'' | '"' | '`' => quote = Some(c),
+                '(' | '[' | '{' => depth += 1,
+                ')' | ']' | '}' => depth = depth.saturating_sub(1),
+                _ if is_separator(c) && depth == 0 => {
+                    parts.push(&text[start..i]);
+                    start = i + c.len_utf8();
+                }
+                _ => {}
+            },
+        }
+    }
+    parts.push(&text[start..]);
+    parts
+}
+
+fn is_keyword(word: &str, keyword: &str) -> bool {
+    word.eq_ignore_ascii_case(keyword)
+}
+
+/// The column names of a statement, guessed from its last `RETURN` clause, or its last `YIELD`
+/// clause without one. Returns `None` for `RETURN *` and for statements without either clause.
+pub(crate) fn return_columns(statement: &str) -> Option<Vec<String>> {
+    let words: Vec<&str> = split_top_level(statement, char::is_whitespace);
+    let return_index = words.iter().rposition(|word| is_keyword(word, RETURN))
+        .or_else(|| words.iter().rposition(|word| is_keyword(word, YIELD)))?;
+    let mut items: Vec<&str> = Vec::new();
+    for word in &words[(return_index + 1)..] {
+        if CLAUSES_AFTER_RETURN.iter().any(|clause| is_keyword(word, clause)) {
+            break;
+        }
+        if !word.is_empty() && !is_keyword(word, "DISTINCT") {
+            items.push(word);
+        }
+    }
+    let clause = items.join(" ");
+    let columns: Vec<String> = split_top_level(&clause, |c| c == ',').into_iter().map(|item| {
+        let item = item.trim();
+        let words: Vec<&str> = split_top_level(item, char::is_whitespace);
+        match words.iter().rposition(|word| is_keyword(word, AS)) {
+            Some(i) if i + 1 < words.len() => words[i + 1..].join(" ").trim_matches('`').to_string(),
+            _ => item.to_string(),
+        }
+    }).collect();
+    if columns.is_empty() || columns.iter().any(|column| column.is_empty() || column == "*") {
+        None
+    } else {
+        Some(columns)
+    }
+}
+
+fn param_value(value: &str) -> BoltType {
+    if let Ok(int) = value.parse::<i64>() {
+        int.into()
+    } else if let Ok(float) = value.parse::<f64>() {
+        float.into()
+    } else if let Ok(boolean) = value.parse::<bool>() {
+        boolean.into()
+    } else {
+        value.into()
+    }
+}
+
+/// Runs one statement with the given parameters and writes the rows. Returns the row count.
+pub(crate) fn run_statement(neo: &Neo, statement: &str, params: &[(String, String)],
+                            format: OutputFormat, writer: &mut dyn Write)
+    -> Result<usize, Error> {
+    let mut query = Query::new(statement.to_string());
+    for (key, value) in params {
+        query = query.param(key, param_value(value));
+    }
+    let mut row_eater = QueryRowEater::new(format, return_columns(statement), writer);
+    neo.cypher(query, &mut row_eater)
+}
+
+pub(crate) fn query(context: &AppContext, config: &QueryConfig) -> Result<(), Error> {
+    let text = match (&config.cypher, &config.file) {
+        (Some(cypher), _) => cypher.clone(),
+        (None, Some(file)) => std::fs::read_to_string(file)
+            .map_err(|e| Error::wrap(format!("Failed to read {file}"), e))?,
+        (None, None) => Err(Error::from("No Cypher statement or file specified."))?,
+    };
+    let statements = split_statements(&text);
+    info!("Running {} statement(s).", statements.len());
+    let neo = context.neo(&config.neo4j)?;
+    let mut writer = output::open(config.out.as_deref())?;
+    for statement in &statements {
+        let n_rows =
+            run_statement(&neo, statement, &config.params, config.format, writer.as_mut())?;
+        info!("Statement returned {n_rows} row(s).");
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{return_columns, split_statements};
+
+    #[test]
+    fn split_at_semicolons() {
+        assert_eq!(split_statements("RETURN 1; RETURN 2;\n"), vec!["RETURN 1", "RETURN 2"]);
+        assert_eq!(split_statements(":auto\nRETURN 1"), vec!["RETURN 1"]);
+        assert!(split_statements(" ; \n;").is_empty());
+    }
+
+    #[test]
+    fn no_split_in_quotes_or_comments() {
+        let text = "RETURN 'a;b' AS x; // no; split\nRETURN \"c;\\\"d\" AS `e;f`;";
+        let statements = vec!["RETURN 'a;b' AS x", "RETURN \"c;\\\"d\" AS `e;f`"];
+        assert_eq!(split_statements(text), statements);
+        let text = "MATCH (n) // counts; all\nRETURN count(n)";
+        assert_eq!(split_statements(text), vec!["MATCH (n) \nRETURN count(n)"]);
+    }
+
+    #[test]
+    fn columns_from_return_or_yield() {
+        assert_eq!(return_columns("MATCH (n) RETURN n.id AS id, count(*) ORDER BY id"),
+                   Some(vec!["id".to_string(), "count(*)".to_string()]));
+        assert_eq!(return_columns("SHOW INDEXES YIELD name, state WHERE state = 'ONLINE'"),
+                   Some(vec!["name".to_string(), "state".to_string()]));
+        assert_eq!(return_columns("MATCH (n) RETURN *"), None);
+        assert_eq!(return_columns("CREATE (n)"), None);
+    }
+}
@@ -1,10 +1,13 @@
+use crate::cache::{ListedObject, Listing};
+use crate::context::AppContext;
 use crate::error::Error;
-use aws_config::BehaviorVersion;
+use log::info;
 use std::fmt::Display;
 use std::fs::File;
-use std::io::{BufRead, BufReader};
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
 use tokio::io::AsyncBufReadExt;
-use tokio::runtime::Runtime;
 
 #[derive(Clone)]
 pub(crate) enum FilePath {
@@ -28,6 +31,24 @@ pub(crate) struct S3Uri {
     pub(crate) key: String,
 }
 
+/// A file or, unless listing recursively, a sub-directory, with its path relative to the listed
+/// directory. Sub-directory paths end with '/' and have no size, time or ETag.
+pub(crate) struct Entry {
+    pub(crate) path: String,
+    pub(crate) size: Option<u64>,
+    pub(crate) last_modified: Option<SystemTime>,
+    pub(crate) etag: Option<String>,
+}
+
+impl Entry {
+    fn dir(path: String) -> Entry {
+        Entry { path: format!("{path}/"), size: None, last_modified: None, etag: None }
+    }
+    pub(crate) fn is_dir(&self) -> bool {
+        self.path.ends_with('/')
+    }
+}
+
 pub(crate) trait LineConsumer {
     fn consume(&mut self, line: String) -> Result<(), Error>;
 }
@@ -92,35 +113,34 @@ impl Display for S3Uri {
     }
 }
 
-pub(crate) fn cat(file: &str) -> Result<(), Error> {
+pub(crate) fn cat(context: &AppContext, file: &str) -> Result<(), Error> {
     let file = FilePath::from_path(file)?;
     let mut line_consumer = LinePrinter {};
-    process_file(&file, &mut line_consumer)
+    process_file(context, &file, &mut line_consumer)
 }
 
-pub(crate) fn ls(dir: &str) -> Result<(), Error> {
-    let dir = FilePath::from_path(dir)?;
-    let mut line_consumer = LinePrinter {};
-    process_entries(&dir, &mut line_consumer)
-}
-
-pub(crate) fn collect(dir: &str) -> Result<Vec<String>, Error> {
+pub(crate) fn collect(context: &AppContext, dir: &str) -> Result<Vec<String>, Error> {
     let dir = FilePath::from_path(dir)?;
     let mut file_collector = FileCollector::new(&dir);
-    process_entries(&dir, &mut file_collector)?;
+    process_entries(context, &dir, &mut file_collector)?;
     Ok(file_collector.files)
 }
 
-pub(crate) fn process_file<C: LineConsumer>(file: &FilePath, line_consumer: &mut C) -> Result<(), Error> {
+pub(crate) fn process_file<C: LineConsumer>(context: &AppContext, file: &FilePath,
+                                            line_consumer: &mut C) -> Result<(), Error> {
     match file {
         FilePath::S3(s3uri) => {
-            let runtime = Runtime::new()?;
-            let s3_client = create_s3_client(&runtime)?;
-            runtime.block_on(async {
+            if context.s3_cache().is_enabled()
+                && let Some(cached_file) = cached_file(context, s3uri)? {
+                return process_local_file(&cached_file, line_consumer);
+            }
+            let s3_client = context.s3_client();
+            context.runtime().block_on(async {
                 let resp = s3_client
                     .get_object()
                     .bucket(s3uri.bucket.clone())
                     .key(s3uri.key.clone())
+                    .set_request_payer(context.s3_request_payer())
                     .send()
                     .await?;
                 let body = resp.body;
@@ -134,15 +154,69 @@ pub(crate) fn process_file<C: LineConsumer>(file: &FilePath, line_consumer: &mut
             })?;
             Ok(())
         }
-        FilePath::Local(file) => {
-            let reader = BufReader::new(File::open(file)?);
-            for line in reader.lines() {
-                let line = line?;
-                line_consumer.consume(line)?;
-            }
-            Ok(())
-        }
+        FilePath::Local(file) => process_local_file(Path::new(file), line_consumer),
+    }
+}
+
+fn process_local_file<C: LineConsumer>(file: &Path, line_consumer: &mut C) -> Result<(), Error> {
+    let reader = BufReader::new(File::open(file)?);
+    for line in reader.lines() {
+        let line = line?;
+        line_consumer.consume(line)?;
+    }
+    Ok(())
+}
+
+/// The object in the cache, downloading it first unless it is cached already, or nothing if
+/// it has no ETag or is too big to cache.
+fn cached_file(context: &AppContext, s3uri: &S3Uri) -> Result<Option<PathBuf>, Error> {
+    let cache = context.s3_cache();
+    let s3_client = context.s3_client();
+    let head = context.runtime().block_on(
+        s3_client
+            .head_object()
+            .bucket(s3uri.bucket.clone())
+            .key(s3uri.key.clone())
+            .set_request_payer(context.s3_request_payer())
+            .send()
+    )?;
+    let Some(quoted_etag) = head.e_tag() else {
+        return Ok(None);
+    };
+    let etag = quoted_etag.trim_matches('"');
+    let size = head.content_length().unwrap_or(0).max(0) as u64;
+    if !cache.fits(size) {
+        info!("Not caching {s3uri}, it is bigger than the cache.");
+        return Ok(None);
+    }
+    if let Some(cached_file) = cache.object(&s3uri.bucket, &s3uri.key, etag)? {
+        info!("Reading {s3uri} from the cache");
+        return Ok(Some(cached_file));
     }
+    info!("Downloading {s3uri} into the cache");
+    let partial_file = cache.partial_file(&s3uri.bucket, &s3uri.key, etag)?;
+    let downloaded = context.runtime().block_on(async {
+        // Only the version with this ETag, in case the object changes in between.
+        let resp = s3_client
+            .get_object()
+            .bucket(s3uri.bucket.clone())
+            .key(s3uri.key.clone())
+            .if_match(quoted_etag)
+            .set_request_payer(context.s3_request_payer())
+            .send()
+            .await?;
+        let mut body = resp.body;
+        let mut writer = BufWriter::new(File::create(&partial_file)?);
+        while let Some(bytes) = body.try_next().await? {
+            writer.write_all(&bytes)?;
+        }
+        writer.flush()?;
+        Ok::<(), Error>(())
+    });
+    let cached_file = downloaded
+        .and_then(|()| cache.add_object(&s3uri.bucket, &s3uri.key, etag, &partial_file))
+        .inspect_err(|_| cache.discard_partial(&partial_file))?;
+    Ok(Some(cached_file))
 }
 
 enum Iteration {
@@ -151,44 +225,18 @@ enum Iteration {
     Complete,
 }
 
-fn process_entries<C: LineConsumer>(dir: &FilePath, line_consumer: &mut C) -> Result<(), Error> {
+fn process_entries<C: LineConsumer>(context: &AppContext, dir: &FilePath, line_consumer: &mut C)
+    -> Result<(), Error> {
     match dir {
         FilePath::S3(s3uri) => {
-            let runtime = Runtime::new()?;
-            let s3_client = create_s3_client(&runtime)?;
-            runtime.block_on(async {
-                let mut iteration = Iteration::Start;
-                loop {
-                    let request = s3_client
-                        .list_objects_v2()
-                        .bucket(s3uri.bucket.clone())
-                        .prefix(s3uri.key.clone());
-                    let request = match &iteration {
-                        Iteration::Start => request,
-                        Iteration::Continuation(token) => request.continuation_token(token.clone()),
-                        Iteration::Complete => break,
-                    };
-                    let response = request.send().await?;
-                    if response.is_truncated() == Some(true) {
-                        iteration =
-                            Iteration::Continuation(response.next_continuation_token.ok_or_else(
-                                || Error::from("No continuation token found in S3 response"),
-                            )?);
-                    } else {
-                        iteration = Iteration::Complete;
-                    }
-                    let contents = response
-                        .contents
-                        .ok_or_else(|| Error::from("No contents found in S3 response"))?;
-                    for obj in contents {
-                        let key = obj
-                            .key
-                            .ok_or_else(|| Error::from("No key found in S3 object"))?;
-                        line_consumer.consume(key)?;
-                    }
-                }
-                Ok::<(), Error>(())
-            })
+            let listing = list_objects(context, &s3uri.bucket, &s3uri.key, None)?;
+            if listing.objects.is_empty() {
+                Err(Error::from("No contents found in S3 response"))?
+            }
+            for object in listing.objects {
+                line_consumer.consume(object.key)?;
+            }
+            Ok(())
         }
         FilePath::Local(dir) => {
             for entry in std::fs::read_dir(dir)? {
@@ -213,10 +261,124 @@ fn process_entries<C: LineConsumer>(dir: &FilePath, line_consumer: &mut C) -> Re
     }
 }
 
-fn create_s3_client(runtime: &Runtime) -> Result<aws_sdk_s3::Client, Error> {
-    runtime.block_on(async {
-        let config = aws_config::load_defaults(BehaviorVersion::latest()).await;
-        let s3_client = aws_sdk_s3::Client::new(&config);
-        Ok(s3_client)
-    })
+/// The entries of a directory sorted by path, either its files and sub-directories or, if
+/// recursive, all files below it.
+pub(crate) fn list(context: &AppContext, dir: &FilePath, recursive: bool)
+    -> Result<Vec<Entry>, Error> {
+    let mut entries: Vec<Entry> = Vec::new();
+    match dir {
+        FilePath::S3(s3uri) => list_s3(context, s3uri, recursive, &mut entries)?,
+        FilePath::Local(dir) => list_local(Path::new(dir), "", recursive, &mut entries)?,
+    }
+    entries.sort_by(|entry1, entry2| entry1.path.cmp(&entry2.path));
+    Ok(entries)
+}
+
+fn list_s3(context: &AppContext, s3uri: &S3Uri, recursive: bool, entries: &mut Vec<Entry>)
+    -> Result<(), Error> {
+    let prefix = if s3uri.key.is_empty() || s3uri.key.ends_with('/') {
+        s3uri.key.clone()
+    } else {
+        format!("{}/", s3uri.key)
+    };
+    let relative = |key: &str| key.strip_prefix(&prefix).map(|path| path.to_string());
+    let delimiter = if recursive { None } else { Some("/") };
+    let listing = list_objects(context, &s3uri.bucket, &prefix, delimiter)?;
+    for common_prefix in &listing.prefixes {
+        if let Some(path) = relative(common_prefix) {
+            entries.push(Entry::dir(path.trim_end_matches('/').to_string()));
+        }
+    }
+    for object in listing.objects {
+        // Skips the empty objects some tools create as directory markers.
+        let Some(path) = relative(&object.key).filter(|path| !path.is_empty()) else {
+            continue;
+        };
+        let ListedObject { size, last_modified, etag, .. } = object;
+        entries.push(Entry { path, size, last_modified, etag });
+    }
+    Ok(())
+}
+
+/// All objects under the prefix and, with a delimiter, the common prefixes, from the cache if
+/// listed recently.
+fn list_objects(context: &AppContext, bucket: &str, prefix: &str, delimiter: Option<&str>)
+    -> Result<Listing, Error> {
+    let cache = context.s3_cache();
+    if let Some(listing) = cache.listing(bucket, prefix, delimiter) {
+        return Ok(listing);
+    }
+    let s3_client = context.s3_client();
+    let listing = context.runtime().block_on(async {
+        let mut listing = Listing::default();
+        let mut iteration = Iteration::Start;
+        loop {
+            let request = s3_client
+                .list_objects_v2()
+                .bucket(bucket)
+                .prefix(prefix)
+                .set_delimiter(delimiter.map(|delimiter| delimiter.to_string()))
+                .set_request_payer(context.s3_request_payer());
+            let request = match &iteration {
+                Iteration::Start => request,
+                Iteration::Continuation(token) => request.continuation_token(token.clone()),
+                Iteration::Complete => break,
+            };
+            let response = request.send().await?;
+            if response.is_truncated() == Some(true) {
+                iteration =
+                    Iteration::Continuation(response.next_continuation_token.ok_or_else(
+                        || Error::from("No continuation token found in S3 response"),
+                    )?);
+            } else {
+                iteration = Iteration::Complete;
+            }
+            for common_prefix in response.common_prefixes.unwrap_or_default() {
+                listing.prefixes.extend(common_prefix.prefix);
+            }
+            for obj in response.contents.unwrap_or_default() {
+                let key = obj.key.ok_or_else(|| Error::from("No key found in S3 object"))?;
+                listing.objects.push(ListedObject {
+                    key,
+                    size: obj.size.map(|size| size as u64),
+                    last_modified: obj.last_modified
+                        .and_then(|last_modified| SystemTime::try_from(last_modified).ok()),
+                    etag: obj.e_tag.map(|etag| etag.trim_matches('"').to_string()),
+                });
+            }
+        }
+        Ok::<Listing, Error>(listing)
+    })?;
+    cache.add_listing(bucket, prefix, delimiter, &listing);
+    Ok(listing)
+}
+
+fn list_local(dir: &Path, relative: &str, recursive: bool, entries: &mut Vec<Entry>)
+    -> Result<(), Error> {
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        let file_name = entry.file_name();
+        let file_name = file_name.to_str().ok_or_else(|| {
+            Error::from(format!(
+                "Failed to convert file name to string: '{}'.", file_name.to_string_lossy()
+            ))
+        })?;
+        let path = format!("{relative}{file_name}");
+        let metadata = std::fs::metadata(entry.path())?;
+        if metadata.is_dir() {
+            if recursive {
+                list_local(&entry.path(), &format!("{path}/"), recursive, entries)?;
+            } else {
+                entries.push(Entry::dir(path));
+            }
+        } else {
+            entries.push(Entry {
+                path,
+                size: Some(metadata.len()),
+                last_modified: metadata.modified().ok(),
+                etag: None,
+            });
+        }
+    }
+    Ok(())
 }
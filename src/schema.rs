@@ -0,0 +1,46 @@
+pub(crate) const VECTOR_PROPERTY: &str = "vector";
+pub(crate) const ID_PROPERTY: &str = "id";
+const VECTOR_INDEX_SUFFIX: &str = "_vector";
+
+pub(crate) mod labels {
+    pub(crate) const TRAIT: &str = "Trait";
+    pub(crate) const GENE: &str = "Gene";
+    pub(crate) const GENE_SET: &str = "GeneSet";
+    pub(crate) const FACTOR: &str = "Factor";
+    pub(crate) const TRAIT_GENE_SET: &str = "TraitGeneSet";
+    pub(crate) const META_FACTOR: &str = "MetaFactor";
+    /// The labels of nodes that are unique by id, as required by the upload scripts.
+    pub(crate) const UNIQUE_ID: [&str; 6] =
+        [TRAIT, GENE, GENE_SET, FACTOR, TRAIT_GENE_SET, META_FACTOR];
+    pub(crate) const EMBEDDED: [&str; 4] = [TRAIT, FACTOR, GENE, GENE_SET];
+}
+
+pub(crate) mod rel_types {
+    pub(crate) const TRAIT_GENE: &str = "TRAIT_GENE";
+    pub(crate) const TRAIT_GENE_SET: &str = "TRAIT_GENE_SET";
+    pub(crate) const TRAIT_FACTOR: &str = "TRAIT_FACTOR";
+    pub(crate) const FACTOR_GENE: &str = "FACTOR_GENE";
+    pub(crate) const FACTOR_GENE_SET: &str = "FACTOR_GENE_SET";
+    pub(crate) const GENE_GENE_SET: &str = "GENE_GENE_SET";
+}
+
+/// Properties recording where a node came from, for loads that set them.
+pub(crate) mod provenance {
+    pub(crate) const RUN_ID: &str = "run_id";
+    pub(crate) const SUB_DIR: &str = "sub_dir";
+    pub(crate) const LOADED_AT: &str = "loaded_at";
+}
+
+/// The name of the vector index on the embedding property of nodes with the given label, e.g.
+/// `gene_set_vector` for `GeneSet`.
+pub(crate) fn vector_index_name(label: &str) -> String {
+    let mut name = String::new();
+    for (i, c) in label.chars().enumerate() {
+        if c.is_uppercase() && i > 0 {
+            name.push('_');
+        }
+        name.extend(c.to_lowercase());
+    }
+    name.push_str(VECTOR_INDEX_SUFFIX);
+    name
+}
@@ -0,0 +1,463 @@
+use crate::compare_traits;
+use crate::config::ServeConfig;
+use crate::context::AppContext;
+use crate::error::Error;
+use crate::ids::TraitId;
+use crate::lookup::{LookupKind, Page};
+use crate::schema::labels;
+use crate::store;
+use crate::store::GraphStore;
+use log::{error, info, warn};
+use serde_json::{json, Map, Value};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tiny_http::{Header, Method, Request, Response, Server};
+
+const DEFAULT_PAGE_SIZE: usize = 10;
+const MAX_PAGE_SIZE: usize = 1000;
+const CONTENT_TYPE_JSON: &[u8] = b"application/json";
+
+mod paths {
+    pub(crate) const HEALTH: &str = "health";
+    pub(crate) const COMPARE: &str = "compare";
+    pub(crate) const TRAITS: &str = "traits";
+    pub(crate) const GENES: &str = "genes";
+    pub(crate) const GENE_SETS: &str = "gene-sets";
+    pub(crate) const FACTORS: &str = "factors";
+    pub(crate) const NEIGHBORS: &str = "neighbors";
+}
+
+mod params {
+    pub(crate) const LIMIT: &str = "limit";
+    pub(crate) const OFFSET: &str = "offset";
+    pub(crate) const SORT: &str = "sort";
+    pub(crate) const ORDER: &str = "order";
+    pub(crate) const TYPE: &str = "type";
+    pub(crate) const TRAITS: &str = "traits";
+    pub(crate) const ASC: &str = "asc";
+    pub(crate) const DESC: &str = "desc";
+}
+
+enum Route {
+    Health,
+    Compare,
+    Node(&'static str, String),
+    Lookup(LookupKind, String),
+    Neighbors(&'static str, String),
+}
+
+enum ApiError {
+    BadRequest(String),
+    NotFound(String),
+    MethodNotAllowed,
+    Internal(Error),
+}
+
+struct CachedResponse {
+    created: Instant,
+    body: String,
+}
+
+/// Successful responses by URL, dropped after the time to live. When full, expired entries are
+/// removed first, then the oldest entry.
+struct ResponseCache {
+    ttl: Duration,
+    capacity: usize,
+    entries: Mutex<HashMap<String, CachedResponse>>,
+}
+
+struct Api {
+    store: Box<dyn GraphStore>,
+    cache: ResponseCache,
+}
+
+impl From<Error> for ApiError {
+    fn from(error: Error) -> Self {
+        ApiError::Internal(error)
+    }
+}
+
+impl ApiError {
+    fn status(&self) -> u16 {
+        match self {
+            ApiError::BadRequest(_) => 400,
+            ApiError::NotFound(_) => 404,
+            ApiError::MethodNotAllowed => 405,
+            ApiError::Internal(_) => 500,
+        }
+    }
+    fn message(&self) -> String {
+        match self {
+            ApiError::BadRequest(message) | ApiError::NotFound(message) => message.clone(),
+            ApiError::MethodNotAllowed => "Only GET requests are supported.".to_string(),
+            // The details go to the server log, not to the client.
+            ApiError::Internal(_) => "Internal server error.".to_string(),
+        }
+    }
+}
+
+impl ResponseCache {
+    fn new(ttl: Duration, capacity: usize) -> ResponseCache {
+        ResponseCache { ttl, capacity, entries: Mutex::new(HashMap::new()) }
+    }
+    fn get(&self, url: &str) -> Option<String> {
+        let entries = self.entries.lock().ok()?;
+        entries.get(url).filter(|cached| cached.created.elapsed() < self.ttl)
+            .map(|cached| cached.body.clone())
+    }
+    fn insert(&self, url: String, body: String) {
+        if self.capacity == 0 {
+            return;
+        }
+        let Ok(mut entries) = self.entries.lock() else {
+            return;
+        };
+        if entries.len() >= self.capacity {
+            entries.retain(|_, cached| cached.created.elapsed() < self.ttl);
+        }
+        if entries.len() >= self.capacity {
+            let oldest = entries.iter().min_by_key(|(_, cached)| cached.created)
+                .map(|(url, _)| url.clone());
+            if let Some(oldest) = oldest {
+                entries.remove(&oldest);
+            }
+        }
+        entries.insert(url, CachedResponse { created: Instant::now(), body });
+    }
+}
+
+fn hex_value(byte: u8) -> Option<u8> {
+    match byte {
+        b'0'..=b'9' => Some(byte - b'0'),
+        b'a'..=b'f' => Some(byte - b'a' + 10),
+        b'A'..=b'F' => Some(byte - b'A' + 10),
+        _ => None,
+    }
+}
+
+fn percent_decode(text: &str, plus_as_space: bool) -> Result<String, ApiError> {
+    let bytes = text.as_bytes();
+    let mut decoded: Vec<u8> = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'%' => {
+                let byte = bytes.get(i + 1).copied().and_then(hex_value)
+                    .zip(bytes.get(i + 2).copied().and_then(hex_value))
+                    .map(|(high, low)| high * 16 + low)
+                    .ok_or_else(|| ApiError::BadRequest(format!("Bad escape in {text}.")))?;
+                decoded.push(byte);
+                i += 3;
+            }
+            b'+' if plus_as_space => {
+                decoded.push(b' ');
+                i += 1;
+            }
+            byte => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8(decoded)
+        .map_err(|_| ApiError::BadRequest(format!("{text} is not valid UTF-8.")))
+}
+
+fn parse_url(url: &str) -> Result<(Vec<String>, HashMap<String, String>), ApiError> {
+    let (path, query) = url.split_once('?').unwrap_or((url, ""));
+    let segments = path.split('/').filter(|segment| !segment.is_empty())
+        .map(|segment| percent_decode(segment, false))
+        .collect::<Result<Vec<String>, ApiError>>()?;
+    let mut params: HashMap<String, String> = HashMap::new();
+    for pair in query.split('&').filter(|pair| !pair.is_empty()) {
+        let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+        params.insert(percent_decode(key, true)?, percent_decode(value, true)?);
+    }
+    Ok((segments, params))
+}
+
+fn collection_label(collection: &str) -> Option<&'static str> {
+    match collection {
+        paths::TRAITS => Some(labels::TRAIT),
+        paths::GENES => Some(labels::GENE),
+        paths::GENE_SETS => Some(labels::GENE_SET),
+        paths::FACTORS => Some(labels::FACTOR),
+        _ => None,
+    }
+}
+
+fn route(segments: &[String]) -> Result<Route, ApiError> {
+    let not_found = || ApiError::NotFound(format!("No such resource: /{}", segments.join("/")));
+    let segments: Vec<&str> = segments.iter().map(|segment| segment.as_str()).collect();
+    match segments.as_slice() {
+        [paths::HEALTH] => Ok(Route::Health),
+        [paths::COMPARE] => Ok(Route::Compare),
+        [collection, id] => {
+            let label = collection_label(collection).ok_or_else(not_found)?;
+            Ok(Route::Node(label, id.to_string()))
+        }
+        [collection, id, paths::NEIGHBORS] => {
+            let label = collection_label(collection).ok_or_else(not_found)?;
+            Ok(Route::Neighbors(label, id.to_string()))
+        }
+        [collection, id, relation] => {
+            let kind = match (*collection, *relation) {
+                (paths::TRAITS, paths::GENES) => LookupKind::GenesForTrait,
+                (paths::TRAITS, paths::GENE_SETS) => LookupKind::GeneSetsForTrait,
+                (paths::TRAITS, paths::FACTORS) => LookupKind::FactorsForTrait,
+                (paths::GENES, paths::FACTORS) => LookupKind::FactorsForGene,
+                (paths::GENE_SETS, paths::FACTORS) => LookupKind::FactorsForGeneSet,
+                _ => Err(not_found())?,
+            };
+            Ok(Route::Lookup(kind, id.to_string()))
+        }
+        _ => Err(not_found()),
+    }
+}
+
+fn usize_param(params: &HashMap<String, String>, key: &str, default: usize)
+    -> Result<usize, ApiError> {
+    match params.get(key) {
+        None => Ok(default),
+        Some(value) => value.parse::<usize>().map_err(|_| {
+            ApiError::BadRequest(format!("Parameter {key} needs to be a non-negative integer."))
+        }),
+    }
+}
+
+fn page(params: &HashMap<String, String>) -> Result<Page<'_>, ApiError> {
+    let limit = usize_param(params, params::LIMIT, DEFAULT_PAGE_SIZE)?;
+    if limit > MAX_PAGE_SIZE {
+        Err(ApiError::BadRequest(format!("Parameter limit may be at most {MAX_PAGE_SIZE}.")))?
+    }
+    let skip = usize_param(params, params::OFFSET, 0)?;
+    let ascending = match params.get(params::ORDER).map(|order| order.as_str()) {
+        None | Some(params::DESC) => false,
+        Some(params::ASC) => true,
+        Some(order) => Err(ApiError::BadRequest(format!(
+            "Unknown order {order}. Use {} or {}.", params::ASC, params::DESC
+        )))?,
+    };
+    let sort = params.get(params::SORT).map(|sort| sort.as_str());
+    Ok(Page { sort, ascending, skip, limit })
+}
+
+fn paged(id: &str, page: &Page, items: Vec<Value>) -> Value {
+    let next_offset =
+        if items.len() == page.limit { Some(page.skip + page.limit) } else { None };
+    json!({
+        "id": id,
+        "offset": page.skip,
+        "limit": page.limit,
+        "next_offset": next_offset,
+        "items": items,
+    })
+}
+
+impl Api {
+    fn node(&self, label: &str, id: &str) -> Result<Value, ApiError> {
+        let properties = self.store.node(label, id)?
+            .ok_or_else(|| ApiError::NotFound(format!("No {label} with id {id}.")))?;
+        Ok(json!({ "label": label, "properties": properties }))
+    }
+    fn lookup(&self, kind: LookupKind, id: &str, params: &HashMap<String, String>)
+        -> Result<Value, ApiError> {
+        let page = page(params)?;
+        if let Some(sort) = page.sort && !kind.columns().contains(&sort) {
+            Err(ApiError::BadRequest(format!(
+                "Cannot sort by {sort}. Possible columns are {}.", kind.columns().join(", ")
+            )))?
+        }
+        let items = self.store.lookup(kind, id, &page)?;
+        Ok(paged(id, &page, items))
+    }
+    fn neighbors(&self, label: &str, id: &str, params: &HashMap<String, String>)
+        -> Result<Value, ApiError> {
+        let page = page(params)?;
+        if page.sort.is_some() {
+            Err(ApiError::BadRequest("Neighbors are always sorted by weight.".to_string()))?
+        }
+        let rel_type = params.get(params::TYPE).map(|rel_type| rel_type.as_str());
+        let items = self.store.neighbors(label, id, rel_type, &page)?;
+        Ok(paged(id, &page, items))
+    }
+    fn compare(&self, params: &HashMap<String, String>) -> Result<Value, ApiError> {
+        let traits: Vec<TraitId> = params.get(params::TRAITS).map(|traits| {
+            traits.split(',').map(|id| id.trim()).filter(|id| !id.is_empty())
+                .map(|id| TraitId::new(id.to_string())).collect()
+        }).unwrap_or_default();
+        if traits.len() < 2 {
+            Err(ApiError::BadRequest(
+                "Parameter traits needs at least two comma-separated trait ids.".to_string()
+            ))?
+        }
+        let limit = usize_param(params, params::LIMIT, DEFAULT_PAGE_SIZE)?.min(MAX_PAGE_SIZE);
+        Ok(compare_traits::comparison_json(self.store.as_ref(), &traits, limit)?)
+    }
+    fn health(&self) -> Value {
+        match self.store.health() {
+            Ok(details) => {
+                let mut health = Map::new();
+                health.insert("status".to_string(), json!("ok"));
+                health.extend(details);
+                Value::Object(health)
+            }
+            Err(error) => json!({ "status": "error", "error": error.to_string() }),
+        }
+    }
+    fn get(&self, url: &str) -> Result<(u16, String), ApiError> {
+        let (segments, params) = parse_url(url)?;
+        let route = route(&segments)?;
+        let is_health = matches!(route, Route::Health);
+        if !is_health && let Some(body) = self.cache.get(url) {
+            return Ok((200, body));
+        }
+        let value = match route {
+            Route::Health => {
+                let health = self.health();
+                let status = if health["status"] == "ok" { 200 } else { 503 };
+                return Ok((status, health.to_string()));
+            }
+            Route::Compare => self.compare(&params)?,
+            Route::Node(label, id) => self.node(label, &id)?,
+            Route::Lookup(kind, id) => self.lookup(kind, &id, &params)?,
+            Route::Neighbors(label, id) => self.neighbors(label, &id, &params)?,
+        };
+        let body = value.to_string();
+        self.cache.insert(url.to_string(), body.clone());
+        Ok((200, body))
+    }
+    fn handle(&self, request: Request) {
+        let result = if *request.method() == Method::Get {
+            self.get(request.url())
+        } else {
+            Err(ApiError::MethodNotAllowed)
+        };
+        let (status, body) = match result {
+            Ok(response) => response,
+            Err(api_error) => {
+                if let ApiError::Internal(error) = &api_error {
+                    error!("{} {}: {error}", request.method(), request.url());
+                }
+                (api_error.status(), json!({ "error": api_error.message() }).to_string())
+            }
+        };
+        let mut response = Response::from_string(body).with_status_code(status);
+        if let Ok(header) = Header::from_bytes(&b"Content-Type"[..], CONTENT_TYPE_JSON) {
+            response = response.with_header(header);
+        }
+        if let Err(error) = request.respond(response) {
+            warn!("Failed to send response: {error}");
+        }
+    }
+}
+
+pub(crate) fn serve(context: &AppContext, config: &ServeConfig) -> Result<(), Error> {
+    let store = store::open(context, &config.store)?;
+    let cache = ResponseCache::new(config.cache_ttl, config.cache_entries);
+    let api = Api { store, cache };
+    let server = Server::http(&config.address).map_err(|error| {
+        Error::from(format!("Could not listen on {}: {error}", config.address))
+    })?;
+    info!("Serving on http://{} with {} workers.", config.address, config.workers);
+    std::thread::scope(|scope| {
+        for _ in 0..config.workers {
+            scope.spawn(|| {
+                for request in server.incoming_requests() {
+                    api.handle(request);
+                }
+            });
+        }
+    });
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{page, paged, parse_url, percent_decode, route, ResponseCache, Route};
+    use crate::lookup::LookupKind;
+    use crate::schema::labels;
+    use serde_json::json;
+    use std::collections::HashMap;
+    use std::time::Duration;
+
+    fn decode(text: &str, plus_as_space: bool) -> Option<String> {
+        percent_decode(text, plus_as_space).ok()
+    }
+
+    fn route_of(url: &str) -> Option<Route> {
+        parse_url(url).ok().and_then(|(segments, _)| route(&segments).ok())
+    }
+
+    fn params(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs.iter().map(|(key, value)| (key.to_string(), value.to_string())).collect()
+    }
+
+    #[test]
+    fn percent_decoding() {
+        assert_eq!(decode("GO%3A0008150", false).as_deref(), Some("GO:0008150"));
+        assert_eq!(decode("caf%C3%a9", false).as_deref(), Some("café"));
+        assert_eq!(decode("a+b", true).as_deref(), Some("a b"));
+        assert_eq!(decode("a+b", false).as_deref(), Some("a+b"));
+        for bad in ["%", "%4", "%zz", "%FF"] {
+            assert_eq!(percent_decode(bad, false).err().map(|error| error.status()), Some(400));
+        }
+    }
+
+    #[test]
+    fn routing() {
+        assert!(matches!(route_of("/health"), Some(Route::Health)));
+        assert!(matches!(route_of("/compare?traits=T2D,AD"), Some(Route::Compare)));
+        assert!(matches!(route_of("/traits/T2D"),
+            Some(Route::Node(labels::TRAIT, id)) if id == "T2D"));
+        assert!(matches!(route_of("/gene-sets/GO%3A0008150/factors"),
+            Some(Route::Lookup(LookupKind::FactorsForGeneSet, id)) if id == "GO:0008150"));
+        assert!(matches!(route_of("/traits/T2D/genes/"),
+            Some(Route::Lookup(LookupKind::GenesForTrait, _))));
+        assert!(matches!(route_of("/genes/APOE/neighbors"),
+            Some(Route::Neighbors(labels::GENE, id)) if id == "APOE"));
+        for url in ["/", "/pathways/X", "/genes/APOE/genes", "/traits/T2D/genes/extra"] {
+            let (segments, _) = parse_url(url).ok().unwrap();
+            assert_eq!(route(&segments).err().map(|error| error.status()), Some(404));
+        }
+    }
+
+    #[test]
+    fn paging_bounds() {
+        let no_params = HashMap::new();
+        let default = page(&no_params).ok().unwrap();
+        assert_eq!((default.skip, default.limit, default.ascending), (0, 10, false));
+        let params_ok = params(&[("limit", "1000"), ("offset", "20"), ("order", "asc")]);
+        let custom = page(&params_ok).ok().unwrap();
+        assert_eq!((custom.skip, custom.limit, custom.ascending), (20, 1000, true));
+        for pair in [("limit", "1001"), ("limit", "-1"), ("offset", "x"), ("order", "up")] {
+            assert_eq!(page(&params(&[pair])).err().map(|error| error.status()), Some(400));
+        }
+        let params_two = params(&[("limit", "2")]);
+        let two = page(&params_two).ok().unwrap();
+        assert_eq!(paged("T2D", &two, vec![json!(1), json!(2)])["next_offset"], 2);
+        assert!(paged("T2D", &two, vec![json!(1)])["next_offset"].is_null());
+    }
+
+    #[test]
+    fn response_cache_expiry_and_capacity() {
+        let cache = ResponseCache::new(Duration::from_millis(50), 2);
+        cache.insert("a".to_string(), "A".to_string());
+        assert_eq!(cache.get("a").as_deref(), Some("A"));
+        std::thread::sleep(Duration::from_millis(60));
+        assert!(cache.get("a").is_none());
+        // When full, the expired entry makes room first, then the oldest one does.
+        cache.insert("b".to_string(), "B".to_string());
+        std::thread::sleep(Duration::from_millis(2));
+        cache.insert("c".to_string(), "C".to_string());
+        assert_eq!(cache.entries.lock().unwrap().len(), 2);
+        cache.insert("d".to_string(), "D".to_string());
+        assert!(cache.get("b").is_none());
+        assert_eq!(cache.get("c").as_deref(), Some("C"));
+        assert_eq!(cache.get("d").as_deref(), Some("D"));
+        let off = ResponseCache::new(Duration::from_secs(60), 0);
+        off.insert("a".to_string(), "A".to_string());
+        assert!(off.get("a").is_none());
+    }
+}
@@ -0,0 +1,170 @@
+use crate::config::ShellConfig;
+use crate::context::AppContext;
+use crate::error::Error;
+use crate::neo::Neo;
+use crate::output::OutputFormat;
+use crate::query;
+use log::{info, warn};
+use rustyline::error::ReadlineError;
+use rustyline::DefaultEditor;
+use std::io::Write;
+use std::time::Instant;
+
+const PROMPT: &str = "ygramul> ";
+const CONTINUATION_PROMPT: &str = "     ..> ";
+const META_PREFIX: char = ':';
+const STATEMENT_TERMINATOR: char = ';';
+const LABELS_QUERY: &str = "CALL db.labels() YIELD label RETURN label ORDER BY label";
+const TYPES_QUERY: &str =
+    "CALL db.relationshipTypes() YIELD relationshipType AS type RETURN type ORDER BY type";
+
+mod commands {
+    pub(crate) const HELP: &str = "help";
+    pub(crate) const EXIT: &str = "exit";
+    pub(crate) const QUIT: &str = "quit";
+    pub(crate) const PARAM: &str = "param";
+    pub(crate) const UNSET: &str = "unset";
+    pub(crate) const LABELS: &str = "labels";
+    pub(crate) const TYPES: &str = "types";
+    pub(crate) const TIMING: &str = "timing";
+}
+
+const HELP: &str = "\
+Enter Cypher statements ending with a semicolon. Statements may span several lines.
+Commands:
+  :help               Show this help.
+  :exit, :quit        Leave the shell (so does Ctrl-D).
+  :param              List the query parameters.
+  :param key=value    Set a query parameter, used as $key.
+  :unset key          Remove a query parameter.
+  :labels             List the node labels.
+  :types              List the relationship types.
+  :timing [on|off]    Show or set whether statements are timed.";
+
+struct Shell {
+    neo: Neo,
+    params: Vec<(String, String)>,
+    timing: bool,
+}
+
+enum Flow {
+    Continue,
+    Exit,
+}
+
+impl Shell {
+    fn run(&self, statement: &str) {
+        let start = Instant::now();
+        let mut stdout = std::io::stdout().lock();
+        match query::run_statement(&self.neo, statement, &self.params, OutputFormat::Table,
+                                   &mut stdout) {
+            Ok(n_rows) => {
+                if self.timing {
+                    println!("{n_rows} row(s) in {:.3} s", start.elapsed().as_secs_f64());
+                } else {
+                    println!("{n_rows} row(s)");
+                }
+            }
+            Err(error) => println!("Error: {error}"),
+        }
+        if let Err(error) = stdout.flush() {
+            warn!("Failed to flush output: {error}");
+        }
+    }
+    fn set_param(&mut self, assignment: &str) {
+        match assignment.split_once('=') {
+            Some((key, value)) => {
+                let key = key.trim().trim_start_matches('$').to_string();
+                let value = value.trim().to_string();
+                self.params.retain(|(existing, _)| *existing != key);
+                self.params.push((key, value));
+            }
+            None => println!("Use :{} key=value", commands::PARAM),
+        }
+    }
+    fn list_params(&self) {
+        if self.params.is_empty() {
+            println!("No parameters set.");
+        }
+        for (key, value) in &self.params {
+            println!("${key} = {value}");
+        }
+    }
+    fn meta_command(&mut self, line: &str) -> Flow {
+        let line = line.trim_start_matches(META_PREFIX).trim();
+        let (command, argument) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        let argument = argument.trim();
+        match command {
+            commands::HELP => println!("{HELP}"),
+            commands::EXIT | commands::QUIT => return Flow::Exit,
+            commands::PARAM if argument.is_empty() => self.list_params(),
+            commands::PARAM => self.set_param(argument),
+            commands::UNSET => self.params.retain(|(key, _)| key != argument),
+            commands::LABELS => self.run(LABELS_QUERY),
+            commands::TYPES => self.run(TYPES_QUERY),
+            commands::TIMING => {
+                match argument {
+                    "on" => self.timing = true,
+                    "off" => self.timing = false,
+                    "" => {}
+                    _ => println!("Use :{} on or :{} off", commands::TIMING, commands::TIMING),
+                }
+                println!("Timing is {}.", if self.timing { "on" } else { "off" });
+            }
+            _ => println!("Unknown command :{command}. Type :{} for help.", commands::HELP),
+        }
+        Flow::Continue
+    }
+}
+
+fn readline_error(error: ReadlineError) -> Error {
+    Error::wrap("Failed to read line".to_string(), error)
+}
+
+pub(crate) fn shell(context: &AppContext, config: &ShellConfig) -> Result<(), Error> {
+    let neo = context.neo(&config.neo4j)?;
+    let mut shell = Shell { neo, params: Vec::new(), timing: true };
+    let mut editor = DefaultEditor::new().map_err(readline_error)?;
+    if let Some(history_file) = &config.history_file && history_file.exists()
+        && let Err(error) = editor.load_history(history_file) {
+        warn!("Could not load history from {}: {error}", history_file.display());
+    }
+    println!("Connected to {}. Type :{} for help.", config.neo4j.uri, commands::HELP);
+    let mut buffer = String::new();
+    loop {
+        let prompt = if buffer.is_empty() { PROMPT } else { CONTINUATION_PROMPT };
+        let line = match editor.readline(prompt) {
+            Ok(line) => line,
+            Err(ReadlineError::Interrupted) => {
+                buffer.clear();
+                continue;
+            }
+            Err(ReadlineError::Eof) => break,
+            Err(error) => Err(readline_error(error))?,
+        };
+        let trimmed = line.trim();
+        if trimmed.is_empty() {
+            continue;
+        }
+        editor.add_history_entry(trimmed).map_err(readline_error)?;
+        if buffer.is_empty() && trimmed.starts_with(META_PREFIX) {
+            match shell.meta_command(trimmed) {
+                Flow::Continue => continue,
+                Flow::Exit => break,
+            }
+        }
+        buffer.push_str(&line);
+        buffer.push('\n');
+        if trimmed.ends_with(STATEMENT_TERMINATOR) {
+            for statement in query::split_statements(&buffer) {
+                shell.run(&statement);
+            }
+            buffer.clear();
+        }
+    }
+    if let Some(history_file) = &config.history_file {
+        editor.save_history(history_file).map_err(readline_error)?;
+        info!("Saved history to {}", history_file.display());
+    }
+    Ok(())
+}
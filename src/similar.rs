@@ -0,0 +1,155 @@
+use crate::config::{SimilarConfig, VectorIndexConfig};
+use crate::context::AppContext;
+use crate::error::Error;
+use crate::neo::{Neo, RowEater};
+use crate::schema::{labels, vector_index_name, VECTOR_PROPERTY};
+use crate::table::Table;
+use log::{info, warn};
+use neo4rs::{query, Row};
+
+const SIMILARITY_FUNCTION: &str = "cosine";
+const KEY_VECTOR: &str = "vector";
+const KEY_ID: &str = "id";
+const KEY_NAME: &str = "name";
+const KEY_SCORE: &str = "score";
+const KEY_STATE: &str = "state";
+const SHOW_VECTOR_INDEX: &str =
+    "SHOW VECTOR INDEXES YIELD name, state WHERE name = $name RETURN name, state";
+
+struct Neighbor {
+    label: &'static str,
+    id: String,
+    name: Option<String>,
+    score: f64,
+}
+
+struct IndexRowEater {}
+
+struct VectorRowEater {
+    vector: Option<Vec<f64>>,
+}
+
+struct NeighborRowEater {
+    label: &'static str,
+    neighbors: Vec<Neighbor>,
+}
+
+impl RowEater for IndexRowEater {
+    type Summary = ();
+    fn eat(&mut self, row: Row) -> Result<(), Error> {
+        let name: String = row.get(KEY_NAME)?;
+        let state: String = row.get(KEY_STATE)?;
+        info!("Vector index {name} is {state}.");
+        Ok(())
+    }
+    fn finish(&mut self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+impl RowEater for VectorRowEater {
+    type Summary = Option<Vec<f64>>;
+    fn eat(&mut self, row: Row) -> Result<(), Error> {
+        self.vector = Some(row.get(KEY_VECTOR)?);
+        Ok(())
+    }
+    fn finish(&mut self) -> Result<Self::Summary, Error> {
+        Ok(self.vector.take())
+    }
+}
+
+impl RowEater for NeighborRowEater {
+    type Summary = Vec<Neighbor>;
+    fn eat(&mut self, row: Row) -> Result<(), Error> {
+        self.neighbors.push(Neighbor {
+            label: self.label,
+            id: row.get(KEY_ID)?,
+            name: row.get(KEY_NAME)?,
+            score: row.get(KEY_SCORE)?,
+        });
+        Ok(())
+    }
+    fn finish(&mut self) -> Result<Self::Summary, Error> {
+        Ok(std::mem::take(&mut self.neighbors))
+    }
+}
+
+pub(crate) fn create_vector_indexes(context: &AppContext, config: &VectorIndexConfig)
+    -> Result<(), Error> {
+    let neo = context.neo(&config.neo4j)?;
+    for label in labels::EMBEDDED {
+        let index = vector_index_name(label);
+        info!("Creating vector index {index} on {label}.{VECTOR_PROPERTY} with {} dimensions",
+            config.dims);
+        let statement = format!(
+            "CREATE VECTOR INDEX {index} IF NOT EXISTS FOR (n:{label}) ON n.{VECTOR_PROPERTY} \
+            OPTIONS {{ indexConfig: {{ `vector.dimensions`: {}, \
+            `vector.similarity_function`: '{SIMILARITY_FUNCTION}' }} }}", config.dims
+        );
+        neo.cypher(query(&statement), &mut IndexRowEater {})?;
+        neo.cypher(query(SHOW_VECTOR_INDEX).param("name", index), &mut IndexRowEater {})?;
+    }
+    Ok(())
+}
+
+fn find_vector(neo: &Neo, id: &str) -> Result<(&'static str, Vec<f64>), Error> {
+    let mut found: Option<(&'static str, Vec<f64>)> = None;
+    for label in labels::EMBEDDED {
+        let statement = format!(
+            "MATCH (n:{label} {{ id: $id }}) WHERE n.{VECTOR_PROPERTY} IS NOT NULL \
+            RETURN n.{VECTOR_PROPERTY} AS {KEY_VECTOR}"
+        );
+        let mut row_eater = VectorRowEater { vector: None };
+        if let Some(vector) = neo.read(query(&statement).param(KEY_ID, id), &mut row_eater)? {
+            match &found {
+                None => found = Some((label, vector)),
+                Some((found_label, _)) => {
+                    warn!("Both a {found_label} and a {label} have id {id}, using the \
+                    {found_label}.")
+                }
+            }
+        }
+    }
+    found.ok_or_else(|| Error::from(format!("No node with id {id} and a vector found.")))
+}
+
+fn nearest(neo: &Neo, label: &'static str, source_label: &str, id: &str, vector: &[f64],
+           limit: usize) -> Result<Vec<Neighbor>, Error> {
+    let statement = format!(
+        "CALL db.index.vector.queryNodes($index, $k, $vector) YIELD node, score\n\
+        WHERE NOT (node:{source_label} AND node.id = $id)\n\
+        RETURN node.id AS {KEY_ID}, node.label AS {KEY_NAME}, score AS {KEY_SCORE}"
+    );
+    let query = query(&statement)
+        .param("index", vector_index_name(label))
+        .param("k", (limit + 1) as i64)
+        .param(KEY_VECTOR, vector.to_vec())
+        .param(KEY_ID, id);
+    let mut row_eater = NeighborRowEater { label, neighbors: Vec::new() };
+    neo.read(query, &mut row_eater)
+}
+
+pub(crate) fn similar(context: &AppContext, config: &SimilarConfig) -> Result<(), Error> {
+    let neo = context.neo(&config.neo4j)?;
+    let (source_label, vector) = find_vector(&neo, &config.id)?;
+    info!("Found {source_label} {} with a vector of {} dimensions.", config.id, vector.len());
+    let mut neighbors: Vec<Neighbor> = Vec::new();
+    for label in labels::EMBEDDED {
+        neighbors.extend(nearest(&neo, label, source_label, &config.id, &vector, config.limit)?);
+    }
+    neighbors.sort_by(|neighbor1, neighbor2| neighbor2.score.total_cmp(&neighbor1.score));
+    neighbors.truncate(config.limit);
+    let headers: Vec<String> =
+        ["label", "id", "name", "score"].iter().map(|header| header.to_string()).collect();
+    let mut table = Table::new(headers);
+    for neighbor in neighbors {
+        table.add_row(vec![
+            neighbor.label.to_string(),
+            neighbor.id,
+            neighbor.name.unwrap_or_default(),
+            format!("{:.4}", neighbor.score),
+        ]);
+    }
+    print!("{table}");
+    Ok(())
+}
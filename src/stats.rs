@@ -0,0 +1,312 @@
+use crate::config::StatsConfig;
+use crate::context::AppContext;
+use crate::error::Error;
+use crate::output;
+use crate::s3;
+use crate::s3::FilePath;
+use crate::table::Table;
+use crate::tsv::CsvConsumer;
+use log::info;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashSet};
+use std::io::Write;
+
+const PHENO_COLUMN: &str = "pheno";
+const QUANTILES: [f64; 9] = [0.0, 0.01, 0.05, 0.25, 0.5, 0.75, 0.95, 0.99, 1.0];
+const N_BINS: usize = 10;
+const N_TOP_PHENOS: usize = 10;
+
+#[derive(Serialize)]
+struct StatsReport {
+    dir: String,
+    relations: Vec<RelationStats>,
+}
+
+#[derive(Serialize)]
+struct RelationStats {
+    relation: String,
+    file: String,
+    n_rows: usize,
+    columns: Vec<ColumnReport>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    edges_per_pheno: Option<BTreeMap<String, usize>>,
+}
+
+#[derive(Serialize)]
+struct ColumnReport {
+    name: String,
+    kind: &'static str,
+    n_missing: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    n_distinct: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    weights: Option<WeightReport>,
+}
+
+#[derive(Serialize)]
+struct WeightReport {
+    n_finite: usize,
+    n_nan: usize,
+    n_inf: usize,
+    n_invalid: usize,
+    quantiles: Vec<Quantile>,
+    histogram: Vec<Bin>,
+}
+
+#[derive(Serialize)]
+struct Quantile {
+    q: f64,
+    value: f64,
+}
+
+#[derive(Serialize)]
+struct Bin {
+    from: f64,
+    to: f64,
+    count: usize,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+enum ColumnKind {
+    Unknown,
+    Entity,
+    Weight,
+}
+
+struct ColumnStats {
+    name: String,
+    kind: ColumnKind,
+    distinct: HashSet<String>,
+    weights: Vec<f64>,
+    n_missing: usize,
+    n_nan: usize,
+    n_inf: usize,
+    n_invalid: usize,
+}
+
+struct RelationAccumulator {
+    relation: String,
+    file: String,
+    n_rows: usize,
+    columns: Vec<ColumnStats>,
+    edges_per_pheno: BTreeMap<String, usize>,
+}
+
+impl ColumnStats {
+    fn new(name: String) -> ColumnStats {
+        ColumnStats {
+            name,
+            kind: ColumnKind::Unknown,
+            distinct: HashSet::new(),
+            weights: Vec::new(),
+            n_missing: 0,
+            n_nan: 0,
+            n_inf: 0,
+            n_invalid: 0,
+        }
+    }
+    fn add(&mut self, value: &str) {
+        if value.is_empty() {
+            self.n_missing += 1;
+            return;
+        }
+        if self.kind == ColumnKind::Unknown {
+            self.kind = if value.parse::<f64>().is_ok() {
+                ColumnKind::Weight
+            } else {
+                ColumnKind::Entity
+            };
+        }
+        match self.kind {
+            ColumnKind::Entity => {
+                if !self.distinct.contains(value) {
+                    self.distinct.insert(value.to_string());
+                }
+            }
+            ColumnKind::Weight => match value.parse::<f64>() {
+                Ok(weight) if weight.is_nan() => self.n_nan += 1,
+                Ok(weight) if weight.is_infinite() => self.n_inf += 1,
+                Ok(weight) => self.weights.push(weight),
+                Err(_) => self.n_invalid += 1,
+            },
+            ColumnKind::Unknown => {}
+        }
+    }
+    fn into_report(self) -> ColumnReport {
+        let ColumnStats {
+            name, kind, distinct, mut weights, n_missing, n_nan, n_inf, n_invalid
+        } = self;
+        match kind {
+            ColumnKind::Weight => {
+                weights.sort_by(|a, b| a.total_cmp(b));
+                let quantiles = quantiles(&weights);
+                let histogram = histogram(&weights);
+                let weights = WeightReport {
+                    n_finite: weights.len(), n_nan, n_inf, n_invalid, quantiles, histogram,
+                };
+                ColumnReport {
+                    name, kind: "weight", n_missing, n_distinct: None, weights: Some(weights),
+                }
+            }
+            ColumnKind::Entity | ColumnKind::Unknown => ColumnReport {
+                name, kind: "entity", n_missing, n_distinct: Some(distinct.len()), weights: None,
+            },
+        }
+    }
+}
+
+fn quantiles(sorted: &[f64]) -> Vec<Quantile> {
+    if sorted.is_empty() {
+        return Vec::new();
+    }
+    QUANTILES.iter().map(|&q| {
+        let i = (q * ((sorted.len() - 1) as f64)).round() as usize;
+        Quantile { q, value: sorted[i] }
+    }).collect()
+}
+
+fn histogram(sorted: &[f64]) -> Vec<Bin> {
+    let (Some(&min), Some(&max)) = (sorted.first(), sorted.last()) else {
+        return Vec::new();
+    };
+    if min == max {
+        return vec![Bin { from: min, to: max, count: sorted.len() }];
+    }
+    let width = (max - min) / (N_BINS as f64);
+    let mut bins: Vec<Bin> = (0..N_BINS).map(|i| {
+        let from = min + width * (i as f64);
+        let to = if i + 1 == N_BINS { max } else { min + width * ((i + 1) as f64) };
+        Bin { from, to, count: 0 }
+    }).collect();
+    for weight in sorted {
+        let i = (((weight - min) / width) as usize).min(N_BINS - 1);
+        bins[i].count += 1;
+    }
+    bins
+}
+
+impl RelationAccumulator {
+    fn new(file: &str) -> RelationAccumulator {
+        let name = file.rsplit('/').next().unwrap_or(file);
+        let relation = name.strip_suffix(".csv").unwrap_or(name).to_string();
+        RelationAccumulator {
+            relation,
+            file: file.to_string(),
+            n_rows: 0,
+            columns: Vec::new(),
+            edges_per_pheno: BTreeMap::new(),
+        }
+    }
+    fn add(&mut self, fields: Vec<(String, String)>) {
+        self.n_rows += 1;
+        for (i, (name, value)) in fields.into_iter().enumerate() {
+            if self.columns.len() <= i {
+                self.columns.push(ColumnStats::new(name.clone()));
+            }
+            self.columns[i].add(&value);
+            if name == PHENO_COLUMN {
+                *self.edges_per_pheno.entry(value).or_default() += 1;
+            }
+        }
+    }
+    fn into_stats(self) -> RelationStats {
+        let RelationAccumulator { relation, file, n_rows, columns, edges_per_pheno } = self;
+        let columns = columns.into_iter().map(|column| column.into_report()).collect();
+        let edges_per_pheno =
+            if edges_per_pheno.is_empty() { None } else { Some(edges_per_pheno) };
+        RelationStats { relation, file, n_rows, columns, edges_per_pheno }
+    }
+}
+
+fn relation_stats(context: &AppContext, file: &str) -> Result<RelationStats, Error> {
+    info!("Reading {file}");
+    let mut accumulator = RelationAccumulator::new(file);
+    let mut csv_consumer = CsvConsumer::new(|record| {
+        accumulator.add(record.into_fields());
+        Ok(())
+    });
+    let file_path = FilePath::from_path(file)?;
+    s3::process_file(context, &file_path, &mut csv_consumer)
+        .map_err(|e| Error::wrap(format!("Failed to process {file}"), e))?;
+    Ok(accumulator.into_stats())
+}
+
+fn format_weight(weight: f64) -> String {
+    format!("{weight:.4}")
+}
+
+fn print_relation(stats: &RelationStats) {
+    println!("{} ({}): {} rows", stats.relation, stats.file, stats.n_rows);
+    let mut headers: Vec<String> =
+        ["column", "kind", "missing", "distinct", "finite", "NaN", "inf", "invalid"]
+            .iter().map(|header| header.to_string()).collect();
+    headers.extend(QUANTILES.iter().map(|q| format!("q{}", (q * 100.0).round())));
+    let mut table = Table::new(headers);
+    for column in &stats.columns {
+        let mut row = vec![
+            column.name.clone(),
+            column.kind.to_string(),
+            column.n_missing.to_string(),
+            column.n_distinct.map(|n| n.to_string()).unwrap_or_default(),
+        ];
+        if let Some(weights) = &column.weights {
+            row.push(weights.n_finite.to_string());
+            row.push(weights.n_nan.to_string());
+            row.push(weights.n_inf.to_string());
+            row.push(weights.n_invalid.to_string());
+            row.extend(weights.quantiles.iter().map(|q| format_weight(q.value)));
+        }
+        table.add_row(row);
+    }
+    print!("{table}");
+    for column in &stats.columns {
+        if let Some(weights) = &column.weights && !weights.histogram.is_empty() {
+            println!("Histogram of {}:", column.name);
+            let headers = vec!["from".to_string(), "to".to_string(), "count".to_string()];
+            let mut table = Table::new(headers);
+            for bin in &weights.histogram {
+                table.add_row(vec![
+                    format_weight(bin.from), format_weight(bin.to), bin.count.to_string(),
+                ]);
+            }
+            print!("{table}");
+        }
+    }
+    if let Some(edges_per_pheno) = &stats.edges_per_pheno {
+        let mut counts: Vec<(&String, &usize)> = edges_per_pheno.iter().collect();
+        counts.sort_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(b.0)));
+        let n_phenos = counts.len();
+        let max = counts.first().map(|(_, n)| **n).unwrap_or(0);
+        let min = counts.last().map(|(_, n)| **n).unwrap_or(0);
+        let median = counts.get(n_phenos / 2).map(|(_, n)| **n).unwrap_or(0);
+        println!("Edges per phenotype: {n_phenos} phenotypes, min {min}, median {median}, \
+        max {max}");
+        let headers = vec![PHENO_COLUMN.to_string(), "edges".to_string()];
+        let mut table = Table::new(headers);
+        for (pheno, count) in counts.into_iter().take(N_TOP_PHENOS) {
+            table.add_row(vec![pheno.clone(), count.to_string()]);
+        }
+        print!("{table}");
+    }
+    println!();
+}
+
+pub(crate) fn stats(context: &AppContext, config: &StatsConfig) -> Result<(), Error> {
+    let files: Vec<String> =
+        s3::collect(context, &config.dir)?.into_iter().filter(|file| file.ends_with(".csv"))
+            .collect();
+    info!("Found {} CSV files in {}", files.len(), config.dir);
+    let mut relations: Vec<RelationStats> = Vec::new();
+    for file in &files {
+        let stats = relation_stats(context, file)?;
+        print_relation(&stats);
+        relations.push(stats);
+    }
+    let report = StatsReport { dir: config.dir.clone(), relations };
+    info!("Writing statistics to {}", config.out);
+    let mut writer = output::open(Some(&config.out))?;
+    serde_json::to_writer_pretty(&mut writer, &report)?;
+    writeln!(writer)?;
+    writer.flush()?;
+    Ok(())
+}
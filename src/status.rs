@@ -0,0 +1,304 @@
+use crate::config::StatusConfig;
+use crate::context::AppContext;
+use crate::error::Error;
+use crate::neo::{Neo, TypedRowEater};
+use crate::output;
+use crate::output::OutputFormat;
+use crate::schema::{labels, provenance, vector_index_name, ID_PROPERTY};
+use crate::table::Table;
+use log::info;
+use neo4rs::query;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::io::Write;
+
+const COMPONENTS_QUERY: &str = "\
+CALL dbms.components() YIELD name, versions, edition\n\
+RETURN name, versions[0] AS version, edition";
+const DATABASE_QUERY: &str = "CALL db.info() YIELD name RETURN name";
+const LABELS_QUERY: &str = "CALL db.labels() YIELD label RETURN label AS name";
+const REL_TYPES_QUERY: &str =
+    "CALL db.relationshipTypes() YIELD relationshipType RETURN relationshipType AS name";
+const CONSTRAINTS_QUERY: &str =
+    "SHOW CONSTRAINTS YIELD name, type, labelsOrTypes, properties";
+const INDEXES_QUERY: &str =
+    "SHOW INDEXES YIELD name, type, state, labelsOrTypes, properties, populationPercent";
+const STORE_SIZE_QUERY: &str =
+    "CALL apoc.monitor.store() YIELD totalStoreSize RETURN totalStoreSize AS n";
+const MAX_RUNS: usize = 10;
+
+/// Counts by label or relationship type.
+type Counts = BTreeMap<String, i64>;
+
+mod constraint_types {
+    pub(crate) const UNIQUENESS: &str = "UNIQUENESS";
+    pub(crate) const NODE_KEY: &str = "NODE_KEY";
+}
+
+mod schema_states {
+    pub(crate) const OK: &str = "ok";
+    pub(crate) const MISSING: &str = "missing";
+    pub(crate) const UNUSED: &str = "not needed, no nodes";
+    pub(crate) const NOT_CREATED: &str = "not created";
+    pub(crate) const UNEXPECTED: &str = "unexpected";
+}
+
+#[derive(Deserialize, Serialize)]
+struct Component {
+    name: String,
+    version: String,
+    edition: String,
+}
+
+#[derive(Deserialize)]
+struct Name {
+    name: String,
+}
+
+#[derive(Deserialize)]
+struct Count {
+    n: i64,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Constraint {
+    name: String,
+    #[serde(rename = "type")]
+    constraint_type: String,
+    labels_or_types: Option<Vec<String>>,
+    properties: Option<Vec<String>>,
+}
+
+#[derive(Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Index {
+    name: String,
+    #[serde(rename = "type")]
+    index_type: String,
+    state: String,
+    labels_or_types: Option<Vec<String>>,
+    properties: Option<Vec<String>>,
+    population_percent: Option<f64>,
+}
+
+/// An expected or unexpected constraint or index and how it compares to the expected schema.
+#[derive(Serialize)]
+struct SchemaCheck {
+    kind: &'static str,
+    description: String,
+    name: Option<String>,
+    state: &'static str,
+}
+
+#[derive(Deserialize, Serialize)]
+struct Run {
+    run_id: String,
+    sub_dirs: Vec<String>,
+    nodes: i64,
+    loaded_at: Option<String>,
+}
+
+#[derive(Serialize)]
+struct Status {
+    components: Vec<Component>,
+    database: Option<String>,
+    nodes: Counts,
+    relationships: Counts,
+    schema: Vec<SchemaCheck>,
+    indexes: Vec<Index>,
+    runs: Vec<Run>,
+    store_size: Option<i64>,
+}
+
+fn names(neo: &Neo, statement: &str) -> Result<Vec<String>, Error> {
+    let names: Vec<Name> = neo.read(query(statement), &mut TypedRowEater::new())?;
+    Ok(names.into_iter().map(|name| name.name).collect())
+}
+
+fn count(neo: &Neo, statement: &str) -> Result<i64, Error> {
+    let counts: Vec<Count> = neo.read(query(statement), &mut TypedRowEater::new())?;
+    Ok(counts.first().map(|count| count.n).unwrap_or(0))
+}
+
+/// Counts per label and per relationship type, one query each so Neo4j can use its count store.
+fn counts(neo: &Neo) -> Result<(Counts, Counts), Error> {
+    let mut nodes: Counts = BTreeMap::new();
+    for label in names(neo, LABELS_QUERY)? {
+        let n = count(neo, &format!("MATCH (n:`{label}`) RETURN count(n) AS n"))?;
+        nodes.insert(label, n);
+    }
+    let mut relationships: Counts = BTreeMap::new();
+    for rel_type in names(neo, REL_TYPES_QUERY)? {
+        let n = count(neo, &format!("MATCH ()-[r:`{rel_type}`]->() RETURN count(r) AS n"))?;
+        relationships.insert(rel_type, n);
+    }
+    Ok((nodes, relationships))
+}
+
+fn is_unique_id(constraint: &Constraint, label: &str) -> bool {
+    matches!(constraint.constraint_type.as_str(),
+        constraint_types::UNIQUENESS | constraint_types::NODE_KEY)
+        && constraint.labels_or_types.as_deref() == Some(&[label.to_string()])
+        && constraint.properties.as_deref() == Some(&[ID_PROPERTY.to_string()])
+}
+
+fn check_schema(constraints: &[Constraint], indexes: &[Index], nodes: &Counts)
+    -> Vec<SchemaCheck> {
+    let mut checks: Vec<SchemaCheck> = Vec::new();
+    let mut expected_constraints: Vec<&str> = Vec::new();
+    for label in labels::UNIQUE_ID {
+        let constraint =
+            constraints.iter().find(|constraint| is_unique_id(constraint, label));
+        let state = match constraint {
+            Some(_) => schema_states::OK,
+            None if nodes.get(label).copied().unwrap_or(0) > 0 => schema_states::MISSING,
+            None => schema_states::UNUSED,
+        };
+        let name = constraint.map(|constraint| constraint.name.clone());
+        if let Some(constraint) = constraint {
+            expected_constraints.push(&constraint.name);
+        }
+        let description = format!("{label}.{ID_PROPERTY} is unique");
+        checks.push(SchemaCheck { kind: "constraint", description, name, state });
+    }
+    for constraint in constraints {
+        if !expected_constraints.contains(&constraint.name.as_str()) {
+            checks.push(SchemaCheck {
+                kind: "constraint",
+                description: constraint.constraint_type.clone(),
+                name: Some(constraint.name.clone()),
+                state: schema_states::UNEXPECTED,
+            });
+        }
+    }
+    for label in labels::EMBEDDED {
+        let name = vector_index_name(label);
+        let index = indexes.iter().find(|index| index.name == name);
+        let state = if index.is_some() { schema_states::OK } else { schema_states::NOT_CREATED };
+        checks.push(SchemaCheck {
+            kind: "vector index",
+            description: format!("{label} embeddings"),
+            name: Some(name),
+            state,
+        });
+    }
+    checks
+}
+
+fn runs(neo: &Neo) -> Result<Vec<Run>, Error> {
+    let statement = format!(
+        "MATCH (n) WHERE n.{run_id} IS NOT NULL\n\
+        WITH n.{run_id} AS run_id, collect(DISTINCT n.{sub_dir}) AS sub_dirs, count(n) AS nodes,\n\
+            max(n.{loaded_at}) AS loaded_at\n\
+        RETURN toString(run_id) AS run_id, [sub_dir IN sub_dirs | toString(sub_dir)] AS sub_dirs,\n\
+            nodes, toString(loaded_at) AS loaded_at\n\
+        ORDER BY loaded_at DESC, run_id DESC\n\
+        LIMIT {MAX_RUNS}",
+        run_id = provenance::RUN_ID, sub_dir = provenance::SUB_DIR,
+        loaded_at = provenance::LOADED_AT
+    );
+    neo.read(query(&statement), &mut TypedRowEater::new())
+}
+
+/// The total store size as reported by APOC, if installed.
+fn store_size(neo: &Neo) -> Option<i64> {
+    match count(neo, STORE_SIZE_QUERY) {
+        Ok(size) => Some(size),
+        Err(error) => {
+            info!("Store size is not available: {error}");
+            None
+        }
+    }
+}
+
+fn collect_status(neo: &Neo) -> Result<Status, Error> {
+    let components: Vec<Component> =
+        neo.read(query(COMPONENTS_QUERY), &mut TypedRowEater::new())?;
+    let database = names(neo, DATABASE_QUERY)?.into_iter().next();
+    let (nodes, relationships) = counts(neo)?;
+    let constraints: Vec<Constraint> =
+        neo.read(query(CONSTRAINTS_QUERY), &mut TypedRowEater::new())?;
+    let indexes: Vec<Index> = neo.read(query(INDEXES_QUERY), &mut TypedRowEater::new())?;
+    let schema = check_schema(&constraints, &indexes, &nodes);
+    let runs = runs(neo)?;
+    let store_size = store_size(neo);
+    Ok(Status { components, database, nodes, relationships, schema, indexes, runs, store_size })
+}
+
+fn strings(strings: &Option<Vec<String>>) -> String {
+    strings.as_deref().unwrap_or_default().join(", ")
+}
+
+fn table<const N: usize>(headers: [&str; N], rows: Vec<[String; N]>) -> Table {
+    let mut table = Table::new(headers.iter().map(|header| header.to_string()).collect());
+    for row in rows {
+        table.add_row(row.to_vec());
+    }
+    table
+}
+
+fn write_text(status: &Status, writer: &mut dyn Write) -> Result<(), Error> {
+    for component in &status.components {
+        writeln!(writer, "Server: {} {} {}", component.name, component.version,
+                 component.edition)?;
+    }
+    writeln!(writer, "Database: {}", status.database.as_deref().unwrap_or("unknown"))?;
+    match status.store_size {
+        Some(size) => writeln!(writer, "Store size: {size} bytes")?,
+        None => writeln!(writer, "Store size: unknown (needs APOC)")?,
+    }
+    writeln!(writer, "\nNodes:")?;
+    let rows = status.nodes.iter().map(|(label, n)| [label.clone(), n.to_string()]).collect();
+    write!(writer, "{}", table(["label", "count"], rows))?;
+    writeln!(writer, "\nRelationships:")?;
+    let rows = status.relationships.iter()
+        .map(|(rel_type, n)| [rel_type.clone(), n.to_string()]).collect();
+    write!(writer, "{}", table(["type", "count"], rows))?;
+    writeln!(writer, "\nSchema:")?;
+    let rows = status.schema.iter().map(|check| [
+        check.kind.to_string(),
+        check.description.clone(),
+        check.name.clone().unwrap_or_default(),
+        check.state.to_string(),
+    ]).collect();
+    write!(writer, "{}", table(["kind", "description", "name", "state"], rows))?;
+    writeln!(writer, "\nIndexes:")?;
+    let rows = status.indexes.iter().map(|index| [
+        index.name.clone(),
+        index.index_type.clone(),
+        strings(&index.labels_or_types),
+        strings(&index.properties),
+        index.state.clone(),
+        index.population_percent.map(|percent| format!("{percent}%")).unwrap_or_default(),
+    ]).collect();
+    write!(writer, "{}", table(["name", "type", "on", "properties", "state", "populated"], rows))?;
+    writeln!(writer, "\nRecent runs:")?;
+    if status.runs.is_empty() {
+        writeln!(writer, "No nodes with provenance ({}).", provenance::RUN_ID)?;
+    } else {
+        let rows = status.runs.iter().map(|run| [
+            run.run_id.clone(),
+            run.sub_dirs.join(", "),
+            run.nodes.to_string(),
+            run.loaded_at.clone().unwrap_or_default(),
+        ]).collect();
+        write!(writer, "{}", table(["run", "sub-directories", "nodes", "loaded at"], rows))?;
+    }
+    Ok(())
+}
+
+pub(crate) fn status(context: &AppContext, config: &StatusConfig) -> Result<(), Error> {
+    let neo = context.neo(&config.neo4j)?;
+    let status = collect_status(&neo)?;
+    let mut writer = output::open(config.out.as_deref())?;
+    match config.format {
+        OutputFormat::Json => {
+            serde_json::to_writer_pretty(&mut writer, &status)?;
+            writeln!(writer)?;
+        }
+        _ => write_text(&status, &mut writer)?,
+    }
+    writer.flush()?;
+    Ok(())
+}
@@ -0,0 +1,94 @@
+mod memory;
+mod neo_store;
+
+use crate::config::StoreConfig;
+use crate::context::AppContext;
+use crate::error::Error;
+use crate::ids::TraitId;
+use crate::lookup::{LookupKind, Page};
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+
+use memory::MemoryGraph;
+
+/// The genes or gene sets a trait is associated with.
+#[derive(Copy, Clone)]
+pub(crate) enum MemberKind {
+    Genes,
+    GeneSets,
+}
+
+/// A node as seen by a query, with an opaque key that is unique within the store.
+#[derive(Clone, Deserialize)]
+pub(crate) struct StoreNode {
+    pub(crate) key: String,
+    pub(crate) labels: Vec<String>,
+    pub(crate) id: Option<String>,
+    pub(crate) label: Option<String>,
+}
+
+pub(crate) struct StoreEdge {
+    pub(crate) key: String,
+    pub(crate) rel_type: String,
+    pub(crate) weight: Option<f64>,
+    pub(crate) source: StoreNode,
+    pub(crate) target: StoreNode,
+}
+
+/// Which edges to follow when expanding from nodes.
+pub(crate) struct EdgeFilter<'a> {
+    pub(crate) rel_types: Option<&'a [String]>,
+    pub(crate) min_weight: Option<f64>,
+}
+
+/// Which trait gene sets to export as terms.
+pub(crate) struct TermFilter<'a> {
+    pub(crate) traits: &'a [TraitId],
+    pub(crate) sources: Option<&'a [String]>,
+    pub(crate) min_beta: Option<f64>,
+}
+
+/// A trait gene set with the edge from its trait.
+#[derive(Deserialize)]
+pub(crate) struct Term {
+    pub(crate) label: Option<String>,
+    pub(crate) phenotype: String,
+    pub(crate) gene_set: String,
+    pub(crate) source: Option<String>,
+    pub(crate) beta: Option<f64>,
+    pub(crate) beta_uncorrected: Option<f64>,
+}
+
+/// The read-only queries used by the query-side actions, answered either by Neo4j or by a
+/// graph loaded into memory from the bulk files.
+pub(crate) trait GraphStore: Send + Sync {
+    /// The rows of a built-in lookup as JSON objects with the lookup's columns as keys.
+    fn lookup(&self, kind: LookupKind, id: &str, page: &Page) -> Result<Vec<Value>, Error>;
+    /// The properties of the node with the given label and id, without embeddings.
+    fn node(&self, label: &str, id: &str) -> Result<Option<Map<String, Value>>, Error>;
+    /// All nodes with any of the given ids, regardless of label.
+    fn find_nodes(&self, ids: &[String]) -> Result<Vec<StoreNode>, Error>;
+    /// All edges touching any of the nodes with the given keys that pass the filter.
+    fn expand(&self, keys: &[String], filter: &EdgeFilter) -> Result<Vec<StoreEdge>, Error>;
+    /// The neighbors of a node with the connecting edges, sorted by weight, as JSON objects.
+    fn neighbors(&self, label: &str, id: &str, rel_type: Option<&str>, page: &Page)
+        -> Result<Vec<Value>, Error>;
+    /// The members of a trait with their primary and secondary scores.
+    fn trait_members(&self, kind: MemberKind, trait_id: &TraitId)
+        -> Result<HashMap<String, (f64, f64)>, Error>;
+    /// The number of genes or gene sets associated with any trait.
+    fn n_members(&self, kind: MemberKind) -> Result<usize, Error>;
+    /// The trait gene sets passing the filter, sorted by trait and gene set.
+    fn terms(&self, filter: &TermFilter) -> Result<Vec<Term>, Error>;
+    /// A few facts about the store for health checks, failing if the store is unavailable.
+    fn health(&self) -> Result<Map<String, Value>, Error>;
+}
+
+pub(crate) fn open(context: &AppContext, config: &StoreConfig)
+    -> Result<Box<dyn GraphStore>, Error> {
+    match config {
+        StoreConfig::Neo4j(neo4j) => Ok(Box::new(context.neo(neo4j)?)),
+        StoreConfig::Memory { bulk_dir } => Ok(Box::new(MemoryGraph::load(context, bulk_dir)?)),
+    }
+}
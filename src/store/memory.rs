@@ -0,0 +1,574 @@
+use crate::bulk;
+use crate::bulk::{
+    AnyPhenoGeneSet, BulkRow, FactorGene, FactorGeneSet, FactorPheno, GenePgs, PhenoGene,
+    PhenotypeName,
+};
+use crate::context::AppContext;
+use crate::error::Error;
+use crate::ids::TraitId;
+use crate::lookup::{Column, LookupKind, Page, Pattern};
+use crate::s3;
+use crate::schema::{labels, rel_types};
+use crate::store::{EdgeFilter, GraphStore, MemberKind, StoreEdge, StoreNode, TermFilter, Term};
+use log::info;
+use serde_json::{json, Map, Number, Value};
+use std::cmp::Ordering;
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+const NODE_KEY_PREFIX: char = 'n';
+const EDGE_KEY_PREFIX: char = 'e';
+
+mod properties {
+    pub(crate) const ID: &str = "id";
+    pub(crate) const LABEL: &str = "label";
+    pub(crate) const SOURCE: &str = "source";
+    pub(crate) const WEIGHT: &str = "weight";
+    pub(crate) const COMBINED: &str = "combined";
+    pub(crate) const PRIOR: &str = "prior";
+    pub(crate) const BETA: &str = "beta";
+    pub(crate) const BETA_UNCORRECTED: &str = "beta_uncorrected";
+}
+
+mod directions {
+    pub(crate) const OUT: &str = "out";
+    pub(crate) const IN: &str = "in";
+}
+
+struct MemoryNode {
+    label: &'static str,
+    properties: Map<String, Value>,
+}
+
+struct MemoryEdge {
+    rel_type: &'static str,
+    source: usize,
+    target: usize,
+    properties: Map<String, Value>,
+}
+
+/// The graph the upload scripts would create from the bulk files, held in memory. Nodes are
+/// merged by label and id, and edges by type and end nodes, as `MERGE` does.
+pub(crate) struct MemoryGraph {
+    bulk_dir: String,
+    nodes: Vec<MemoryNode>,
+    edges: Vec<MemoryEdge>,
+    node_index: HashMap<&'static str, HashMap<String, usize>>,
+    edge_index: HashMap<(&'static str, usize, usize), usize>,
+    outgoing: Vec<Vec<usize>>,
+    incoming: Vec<Vec<usize>>,
+}
+
+/// A JSON number, or null for NaN, which is how missing values end up in the bulk files.
+fn number(value: f64) -> Value {
+    Number::from_f64(value).map(Value::Number).unwrap_or(Value::Null)
+}
+
+fn compare_values(value1: &Value, value2: &Value) -> Ordering {
+    match (value1.as_f64(), value2.as_f64()) {
+        (Some(number1), Some(number2)) => number1.total_cmp(&number2),
+        _ => value1.as_str().cmp(&value2.as_str()),
+    }
+}
+
+/// Orders like `ORDER BY value IS NULL, value`, so nulls come last in either direction.
+fn compare_nulls_last(value1: &Value, value2: &Value, ascending: bool) -> Ordering {
+    match (value1.is_null(), value2.is_null()) {
+        (true, true) => Ordering::Equal,
+        (true, false) => Ordering::Greater,
+        (false, true) => Ordering::Less,
+        (false, false) if ascending => compare_values(value1, value2),
+        (false, false) => compare_values(value2, value1),
+    }
+}
+
+fn sort_rows(rows: &mut [Map<String, Value>], sort: &str, ascending: bool, tie_breaker: &str) {
+    rows.sort_by(|row1, row2| {
+        let value = |row: &Map<String, Value>, key: &str| row.get(key).cloned()
+            .unwrap_or(Value::Null);
+        compare_nulls_last(&value(row1, sort), &value(row2, sort), ascending)
+            .then_with(|| compare_values(&value(row1, tie_breaker), &value(row2, tie_breaker)))
+    });
+}
+
+fn page_of(rows: Vec<Map<String, Value>>, page: &Page) -> Vec<Value> {
+    rows.into_iter().skip(page.skip).take(page.limit).map(Value::Object).collect()
+}
+
+fn parse_key(key: &str, prefix: char) -> Option<usize> {
+    key.strip_prefix(prefix).and_then(|index| index.parse().ok())
+}
+
+/// The names of the files in a local or S3 directory.
+fn file_names(context: &AppContext, dir: &str) -> Result<HashSet<String>, Error> {
+    let files = s3::collect(context, dir)?;
+    Ok(files.iter().filter_map(|file| file.rsplit('/').next()).map(|name| name.to_string())
+        .collect())
+}
+
+impl MemoryNode {
+    fn id(&self) -> Option<&str> {
+        self.properties.get(properties::ID).and_then(|id| id.as_str())
+    }
+}
+
+impl MemoryEdge {
+    fn weight(&self) -> Option<f64> {
+        self.properties.get(properties::WEIGHT).and_then(|weight| weight.as_f64())
+    }
+}
+
+impl MemoryGraph {
+    fn new(bulk_dir: &str) -> MemoryGraph {
+        MemoryGraph {
+            bulk_dir: bulk_dir.to_string(),
+            nodes: Vec::new(),
+            edges: Vec::new(),
+            node_index: HashMap::new(),
+            edge_index: HashMap::new(),
+            outgoing: Vec::new(),
+            incoming: Vec::new(),
+        }
+    }
+    pub(crate) fn load(context: &AppContext, bulk_dir: &str) -> Result<MemoryGraph, Error> {
+        let present = file_names(context, bulk_dir)?;
+        let mut graph = MemoryGraph::new(bulk_dir);
+        // Trait names come first, since trait gene set labels are made from them.
+        graph.load_rows(context, &present, MemoryGraph::add_phenotype_name)?;
+        let loaded = [
+            graph.load_rows(context, &present, MemoryGraph::add_pheno_gene)?,
+            graph.load_rows(context, &present, MemoryGraph::add_pheno_gene_set)?,
+            graph.load_rows(context, &present, MemoryGraph::add_gene_pgs)?,
+            graph.load_rows(context, &present, MemoryGraph::add_factor_gene)?,
+            graph.load_rows(context, &present, MemoryGraph::add_factor_gene_set)?,
+            graph.load_rows(context, &present, MemoryGraph::add_factor_pheno)?,
+        ];
+        if !loaded.contains(&true) {
+            Err(Error::from(format!("No bulk files found in {bulk_dir}.")))?
+        }
+        info!("Loaded {} nodes and {} edges from {bulk_dir} into memory.", graph.nodes.len(),
+            graph.edges.len());
+        Ok(graph)
+    }
+    /// Adds the rows of a bulk file if it is present, returning whether it was.
+    fn load_rows<R: BulkRow>(&mut self, context: &AppContext, present: &HashSet<String>,
+                             add: fn(&mut MemoryGraph, R)) -> Result<bool, Error> {
+        if !present.contains(R::FILE) {
+            info!("No {} in {}, skipping.", R::FILE, self.bulk_dir);
+            return Ok(false);
+        }
+        let bulk_dir = self.bulk_dir.clone();
+        bulk::for_each_row(context, &bulk_dir, |row: R| {
+            add(self, row);
+            Ok(())
+        })?;
+        Ok(true)
+    }
+    fn merge_node(&mut self, label: &'static str, id: &str) -> usize {
+        if let Some(index) = self.find(label, id) {
+            return index;
+        }
+        let index = self.nodes.len();
+        let mut properties = Map::new();
+        properties.insert(properties::ID.to_string(), json!(id));
+        self.nodes.push(MemoryNode { label, properties });
+        self.outgoing.push(Vec::new());
+        self.incoming.push(Vec::new());
+        self.node_index.entry(label).or_default().insert(id.to_string(), index);
+        index
+    }
+    fn merge_edge(&mut self, rel_type: &'static str, source: usize, target: usize,
+                  properties: Vec<(&str, Value)>) {
+        let index = match self.edge_index.get(&(rel_type, source, target)) {
+            Some(&index) => index,
+            None => {
+                let index = self.edges.len();
+                self.edges.push(MemoryEdge { rel_type, source, target, properties: Map::new() });
+                self.outgoing[source].push(index);
+                self.incoming[target].push(index);
+                self.edge_index.insert((rel_type, source, target), index);
+                index
+            }
+        };
+        for (key, value) in properties {
+            self.edges[index].properties.insert(key.to_string(), value);
+        }
+    }
+    fn set_property(&mut self, node: usize, key: &str, value: Value) {
+        self.nodes[node].properties.insert(key.to_string(), value);
+    }
+    fn add_phenotype_name(&mut self, row: PhenotypeName) {
+        let pheno = self.merge_node(labels::TRAIT, row.phenotype.as_str());
+        self.set_property(pheno, properties::LABEL, json!(row.name));
+    }
+    fn add_pheno_gene(&mut self, row: PhenoGene) {
+        let pheno = self.merge_node(labels::TRAIT, row.pheno.as_str());
+        let gene = self.merge_node(labels::GENE, row.gene.as_str());
+        self.merge_edge(rel_types::TRAIT_GENE, pheno, gene, vec![
+            (properties::COMBINED, number(row.combined)),
+            (properties::PRIOR, number(row.prior)),
+        ]);
+    }
+    fn add_pheno_gene_set(&mut self, row: AnyPhenoGeneSet) {
+        match row {
+            AnyPhenoGeneSet::GeneSet(row) => {
+                let pheno = self.merge_node(labels::TRAIT, row.pheno.as_str());
+                let gene_set = self.merge_node(labels::GENE_SET, row.gene_set.as_str());
+                self.merge_edge(rel_types::TRAIT_GENE_SET, pheno, gene_set, vec![
+                    (properties::BETA_UNCORRECTED, number(row.beta_uncorrected)),
+                    (properties::BETA, number(row.beta)),
+                ]);
+            }
+            AnyPhenoGeneSet::TraitGeneSet(row) => {
+                let pheno = self.merge_node(labels::TRAIT, row.pheno.as_str());
+                let pgs = self.merge_node(labels::TRAIT_GENE_SET, &row.pgs.to_string());
+                // Same label as upload_light gives trait gene sets, named after the trait's
+                // label if it has one.
+                let trait_label = self.nodes[pheno].properties.get(properties::LABEL)
+                    .and_then(|label| label.as_str()).unwrap_or(row.pheno.as_str());
+                let label = format!("{} for {trait_label}", row.pgs.gene_set).replace('_', " ")
+                    .to_lowercase();
+                self.set_property(pgs, properties::SOURCE, json!(row.source));
+                self.set_property(pgs, properties::LABEL, json!(label));
+                self.merge_edge(rel_types::TRAIT_GENE_SET, pheno, pgs, vec![
+                    (properties::BETA_UNCORRECTED, number(row.beta_uncorrected)),
+                    (properties::BETA, number(row.beta)),
+                ]);
+            }
+        }
+    }
+    fn add_gene_pgs(&mut self, row: GenePgs) {
+        let gene = self.merge_node(labels::GENE, row.gene.as_str());
+        let pgs = self.merge_node(labels::TRAIT_GENE_SET, &row.pgs.to_string());
+        self.merge_edge(rel_types::GENE_GENE_SET, gene, pgs,
+                        vec![(properties::BETA, number(row.beta))]);
+    }
+    fn add_factor_gene(&mut self, row: FactorGene) {
+        let factor = self.merge_node(labels::FACTOR, &row.factor.to_string());
+        let gene = self.merge_node(labels::GENE, row.gene.as_str());
+        self.merge_edge(rel_types::FACTOR_GENE, factor, gene,
+                        vec![(properties::WEIGHT, number(row.weight))]);
+    }
+    fn add_factor_gene_set(&mut self, row: FactorGeneSet) {
+        let factor = self.merge_node(labels::FACTOR, &row.factor.to_string());
+        let gene_set = self.merge_node(labels::GENE_SET, row.gene_set.as_str());
+        self.merge_edge(rel_types::FACTOR_GENE_SET, factor, gene_set,
+                        vec![(properties::WEIGHT, number(row.weight))]);
+    }
+    fn add_factor_pheno(&mut self, row: FactorPheno) {
+        let factor = self.merge_node(labels::FACTOR, &row.factor.to_string());
+        let pheno = self.merge_node(labels::TRAIT, row.pheno.as_str());
+        self.set_property(factor, properties::LABEL, json!(row.label));
+        self.merge_edge(rel_types::TRAIT_FACTOR, pheno, factor,
+                        vec![(properties::WEIGHT, number(row.any_relevance))]);
+    }
+    fn find(&self, label: &str, id: &str) -> Option<usize> {
+        self.node_index.get(label).and_then(|ids| ids.get(id)).copied()
+    }
+    fn store_node(&self, index: usize) -> StoreNode {
+        let node = &self.nodes[index];
+        StoreNode {
+            key: format!("{NODE_KEY_PREFIX}{index}"),
+            labels: vec![node.label.to_string()],
+            id: node.id().map(|id| id.to_string()),
+            label: node.properties.get(properties::LABEL).and_then(|label| label.as_str())
+                .map(|label| label.to_string()),
+        }
+    }
+    /// The edges matching the pattern from the given node, with the node at the other end.
+    fn matching<'a>(&'a self, pattern: &'a Pattern, node: usize)
+        -> impl Iterator<Item = (&'a MemoryEdge, &'a MemoryNode)> + 'a {
+        let edges = if pattern.outgoing { &self.outgoing[node] } else { &self.incoming[node] };
+        edges.iter().map(|&index| &self.edges[index])
+            .filter(|edge| edge.rel_type == pattern.rel_type)
+            .map(|edge| {
+                let other = if pattern.outgoing { edge.target } else { edge.source };
+                (edge, &self.nodes[other])
+            })
+            .filter(|(_, other)| other.label == pattern.other_label)
+    }
+    fn pattern_row(kind: LookupKind, edge: &MemoryEdge, other: &MemoryNode) -> Map<String, Value> {
+        kind.columns().iter().zip(kind.pattern().columns).map(|(column, source)| {
+            let value = match source {
+                Column::OtherId => other.properties.get(properties::ID),
+                Column::OtherProperty(key) => other.properties.get(*key),
+                Column::EdgeProperty(key) => edge.properties.get(*key),
+            };
+            (column.to_string(), value.cloned().unwrap_or(Value::Null))
+        }).collect()
+    }
+}
+
+fn member_lookup(kind: MemberKind) -> LookupKind {
+    match kind {
+        MemberKind::Genes => LookupKind::GenesForTrait,
+        MemberKind::GeneSets => LookupKind::GeneSetsForTrait,
+    }
+}
+
+impl GraphStore for MemoryGraph {
+    fn lookup(&self, kind: LookupKind, id: &str, page: &Page) -> Result<Vec<Value>, Error> {
+        let sort = kind.sort_column(page.sort)?;
+        let pattern = kind.pattern();
+        let Some(node) = self.find(pattern.label, id) else {
+            return Ok(Vec::new());
+        };
+        let mut rows: Vec<Map<String, Value>> = self.matching(pattern, node)
+            .map(|(edge, other)| MemoryGraph::pattern_row(kind, edge, other)).collect();
+        sort_rows(&mut rows, sort, page.ascending, kind.columns()[0]);
+        Ok(page_of(rows, page))
+    }
+    fn node(&self, label: &str, id: &str) -> Result<Option<Map<String, Value>>, Error> {
+        Ok(self.find(label, id).map(|index| self.nodes[index].properties.clone()))
+    }
+    fn find_nodes(&self, ids: &[String]) -> Result<Vec<StoreNode>, Error> {
+        let ids: HashSet<&str> = ids.iter().map(|id| id.as_str()).collect();
+        Ok(self.nodes.iter().enumerate()
+            .filter(|(_, node)| node.id().is_some_and(|id| ids.contains(id)))
+            .map(|(index, _)| self.store_node(index)).collect())
+    }
+    fn expand(&self, keys: &[String], filter: &EdgeFilter) -> Result<Vec<StoreEdge>, Error> {
+        let mut edges: BTreeSet<usize> = BTreeSet::new();
+        for node in keys.iter().filter_map(|key| parse_key(key, NODE_KEY_PREFIX)) {
+            if node < self.nodes.len() {
+                edges.extend(&self.outgoing[node]);
+                edges.extend(&self.incoming[node]);
+            }
+        }
+        Ok(edges.into_iter().filter_map(|index| {
+            let edge = &self.edges[index];
+            let weight = edge.weight();
+            let type_passes = filter.rel_types
+                .is_none_or(|rel_types| rel_types.iter().any(|rel_type| rel_type == edge.rel_type));
+            let weight_passes = match (weight, filter.min_weight) {
+                (Some(weight), Some(min_weight)) => weight >= min_weight,
+                _ => true,
+            };
+            (type_passes && weight_passes).then(|| StoreEdge {
+                key: format!("{EDGE_KEY_PREFIX}{index}"),
+                rel_type: edge.rel_type.to_string(),
+                weight,
+                source: self.store_node(edge.source),
+                target: self.store_node(edge.target),
+            })
+        }).collect())
+    }
+    fn neighbors(&self, label: &str, id: &str, rel_type: Option<&str>, page: &Page)
+        -> Result<Vec<Value>, Error> {
+        let Some(node) = self.find(label, id) else {
+            return Ok(Vec::new());
+        };
+        let outgoing = self.outgoing[node].iter().map(|&index| (index, directions::OUT));
+        let incoming = self.incoming[node].iter().map(|&index| (index, directions::IN));
+        let mut neighbors: Vec<Map<String, Value>> = outgoing.chain(incoming)
+            .map(|(index, direction)| (&self.edges[index], direction))
+            .filter(|(edge, _)| rel_type.is_none_or(|rel_type| rel_type == edge.rel_type))
+            .map(|(edge, direction)| {
+                let other = if direction == directions::OUT { edge.target } else { edge.source };
+                let other = self.store_node(other);
+                let mut neighbor = Map::new();
+                neighbor.insert("type".to_string(), json!(edge.rel_type));
+                neighbor.insert("direction".to_string(), json!(direction));
+                neighbor.insert("labels".to_string(), json!(other.labels));
+                neighbor.insert("id".to_string(), json!(other.id));
+                neighbor.insert("label".to_string(), json!(other.label));
+                neighbor.insert("weight".to_string(), json!(edge.weight()));
+                neighbor
+            }).collect();
+        sort_rows(&mut neighbors, properties::WEIGHT, page.ascending, properties::ID);
+        Ok(page_of(neighbors, page))
+    }
+    fn trait_members(&self, kind: MemberKind, trait_id: &TraitId)
+        -> Result<HashMap<String, (f64, f64)>, Error> {
+        let lookup = member_lookup(kind);
+        let Some(node) = self.find(labels::TRAIT, trait_id.as_str()) else {
+            return Ok(HashMap::new());
+        };
+        let columns = lookup.columns();
+        Ok(self.matching(lookup.pattern(), node).filter_map(|(edge, other)| {
+            let row = MemoryGraph::pattern_row(lookup, edge, other);
+            let score = |column: &str| row[column].as_f64().unwrap_or(f64::NAN);
+            let member = other.id()?.to_string();
+            Some((member, (score(columns[1]), score(columns[2]))))
+        }).collect())
+    }
+    fn n_members(&self, kind: MemberKind) -> Result<usize, Error> {
+        let pattern = member_lookup(kind).pattern();
+        Ok(self.nodes.iter().enumerate()
+            .filter(|(_, node)| node.label == pattern.other_label)
+            .filter(|(index, _)| self.incoming[*index].iter().any(|&edge| {
+                let edge = &self.edges[edge];
+                edge.rel_type == pattern.rel_type && self.nodes[edge.source].label == pattern.label
+            }))
+            .count())
+    }
+    fn terms(&self, filter: &TermFilter) -> Result<Vec<Term>, Error> {
+        let traits: HashSet<&str> = filter.traits.iter().map(|id| id.as_str()).collect();
+        let mut terms: Vec<Term> = self.edges.iter()
+            .filter(|edge| edge.rel_type == rel_types::TRAIT_GENE_SET)
+            .map(|edge| (edge, &self.nodes[edge.source], &self.nodes[edge.target]))
+            .filter(|(_, pheno, pgs)| {
+                pheno.label == labels::TRAIT && pgs.label == labels::TRAIT_GENE_SET
+            })
+            .filter_map(|(edge, pheno, pgs)| {
+                let text = |node: &MemoryNode, key: &str| node.properties.get(key)
+                    .and_then(|value| value.as_str()).map(|value| value.to_string());
+                Some(Term {
+                    label: text(pgs, properties::LABEL),
+                    phenotype: pheno.id()?.to_string(),
+                    gene_set: pgs.id()?.to_string(),
+                    source: text(pgs, properties::SOURCE),
+                    beta: edge.properties.get(properties::BETA).and_then(|beta| beta.as_f64()),
+                    beta_uncorrected: edge.properties.get(properties::BETA_UNCORRECTED)
+                        .and_then(|beta| beta.as_f64()),
+                })
+            })
+            .filter(|term| traits.is_empty() || traits.contains(term.phenotype.as_str()))
+            .filter(|term| filter.sources.is_none_or(|sources| {
+                term.source.as_ref().is_some_and(|source| sources.contains(source))
+            }))
+            .filter(|term| filter.min_beta.is_none_or(|min_beta| {
+                term.beta.is_some_and(|beta| beta >= min_beta)
+            }))
+            .collect();
+        terms.sort_by(|term1, term2| {
+            term1.phenotype.cmp(&term2.phenotype).then_with(|| term1.gene_set.cmp(&term2.gene_set))
+        });
+        Ok(terms)
+    }
+    fn health(&self) -> Result<Map<String, Value>, Error> {
+        let mut health = Map::new();
+        health.insert("bulk_dir".to_string(), json!(self.bulk_dir));
+        health.insert("nodes".to_string(), json!(self.nodes.len()));
+        health.insert("edges".to_string(), json!(self.edges.len()));
+        Ok(health)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::MemoryGraph;
+    use crate::config::{CacheMode, S3CacheConfig, S3Config};
+    use crate::context::AppContext;
+    use crate::ids::TraitId;
+    use crate::lookup::{LookupKind, Page};
+    use crate::schema::{labels, rel_types};
+    use crate::store::{EdgeFilter, GraphStore, MemberKind, TermFilter};
+    use serde_json::Value;
+    use std::time::Duration;
+
+    const BULK_FILES: [(&str, &str); 6] = [
+        ("phenotype_names.csv", "phenotype,phenotype_name\nT2D,Type_2 Diabetes\n"),
+        ("pheno_gene.csv",
+         "pheno,gene,combined,prior\nT2D,APOE,2.5,0.3\nT2D,LDLR,1.5,\nT2D,PCSK9,0.5,0.9\n\
+         AD,APOE,3,0.5\n"),
+        ("pheno_geneset.csv",
+         "pheno,pgs,source,beta_uncorrected,beta\nT2D,LIPID_TRANSPORT:T2D,msig,0.2,0.1\n\
+         T2D,GO%3A0001:T2D,go,0.1,0.05\nAD,LIPID_TRANSPORT:AD,msig,0.4,0.3\n"),
+        ("gene_geneset.csv", "gene,pgs,beta\nAPOE,LIPID_TRANSPORT:T2D,0.7\n"),
+        ("factor_gene.csv", "factor,gene,weight\nF1_T2D,APOE,1\nF1_T2D,LDLR,0.2\n"),
+        ("factor_phenos.csv", "factor,label,pheno,any_relevance\nF1_T2D,lipid,T2D,0.9\n"),
+    ];
+
+    fn load(name: &str) -> MemoryGraph {
+        let bulk_dir =
+            std::env::temp_dir().join(format!("ygramul-memory-{name}-{}", std::process::id()));
+        std::fs::create_dir_all(&bulk_dir).unwrap();
+        for (file, content) in BULK_FILES {
+            std::fs::write(bulk_dir.join(file), content).unwrap();
+        }
+        let s3_config = S3Config {
+            endpoint_url: None, region: None, profile: None, force_path_style: false,
+            anonymous: false, request_payer: false,
+        };
+        let s3_cache_config = S3CacheConfig {
+            dir: None, mode: CacheMode::Off, max_bytes: 0, listing_ttl: Duration::ZERO
+        };
+        let context = AppContext::new(s3_config, s3_cache_config).unwrap();
+        let graph = MemoryGraph::load(&context, &bulk_dir.to_string_lossy()).unwrap();
+        std::fs::remove_dir_all(&bulk_dir).unwrap();
+        graph
+    }
+
+    fn page(sort: Option<&str>, ascending: bool, skip: usize, limit: usize) -> Page<'_> {
+        Page { sort, ascending, skip, limit }
+    }
+
+    fn column<'a>(rows: &'a [Value], column: &str) -> Vec<&'a str> {
+        rows.iter().map(|row| row[column].as_str().unwrap()).collect()
+    }
+
+    #[test]
+    fn lookup_sorts_nulls_last() {
+        let graph = load("lookup");
+        let kind = LookupKind::GenesForTrait;
+        let rows = graph.lookup(kind, "T2D", &page(None, false, 0, 10)).unwrap();
+        assert_eq!(column(&rows, "gene"), ["PCSK9", "APOE", "LDLR"]);
+        assert!(rows[2]["prior"].is_null());
+        let rows = graph.lookup(kind, "T2D", &page(Some("prior"), true, 0, 10)).unwrap();
+        assert_eq!(column(&rows, "gene"), ["APOE", "PCSK9", "LDLR"]);
+        let rows = graph.lookup(kind, "T2D", &page(Some("combined"), false, 1, 1)).unwrap();
+        assert_eq!(column(&rows, "gene"), ["LDLR"]);
+        assert!(graph.lookup(kind, "T2D", &page(Some("beta"), false, 0, 10)).is_err());
+        assert!(graph.lookup(kind, "T1D", &page(None, false, 0, 10)).unwrap().is_empty());
+    }
+
+    #[test]
+    fn neighbors_in_both_directions() {
+        let graph = load("neighbors");
+        let all = graph.neighbors(labels::GENE, "APOE", None, &page(None, false, 0, 10))
+            .unwrap();
+        assert_eq!(column(&all, "id"), ["F1_T2D", "AD", "LIPID_TRANSPORT:T2D", "T2D"]);
+        assert_eq!(column(&all, "direction"), ["in", "in", "out", "in"]);
+        assert_eq!(all[0]["weight"], 1.0);
+        let factors = graph.neighbors(labels::GENE, "APOE", Some(rel_types::FACTOR_GENE),
+                                      &page(None, false, 0, 10)).unwrap();
+        assert_eq!(column(&factors, "label"), ["lipid"]);
+    }
+
+    #[test]
+    fn expand_filters_by_min_weight() {
+        let graph = load("expand");
+        let keys: Vec<String> = graph.find_nodes(&["F1_T2D".to_string()]).unwrap().into_iter()
+            .map(|node| node.key).collect();
+        assert_eq!(keys.len(), 1);
+        let filter = EdgeFilter { rel_types: None, min_weight: Some(0.5) };
+        let mut edges: Vec<(String, Option<String>, Option<f64>)> =
+            graph.expand(&keys, &filter).unwrap().into_iter()
+                .map(|edge| (edge.rel_type, edge.target.id, edge.weight)).collect();
+        edges.sort_by(|edge1, edge2| edge1.0.cmp(&edge2.0));
+        assert_eq!(edges, [
+            (rel_types::FACTOR_GENE.to_string(), Some("APOE".to_string()), Some(1.0)),
+            (rel_types::TRAIT_FACTOR.to_string(), Some("F1_T2D".to_string()), Some(0.9)),
+        ]);
+        let rel_types = [rel_types::FACTOR_GENE.to_string()];
+        let filter = EdgeFilter { rel_types: Some(&rel_types), min_weight: None };
+        assert_eq!(graph.expand(&keys, &filter).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn terms_are_labeled_with_trait_names() {
+        let graph = load("terms");
+        let filter = TermFilter { traits: &[], sources: None, min_beta: None };
+        let terms = graph.terms(&filter).unwrap();
+        let labels: Vec<&str> =
+            terms.iter().map(|term| term.label.as_deref().unwrap()).collect();
+        assert_eq!(labels, ["lipid transport for ad", "go:0001 for type 2 diabetes",
+                            "lipid transport for type 2 diabetes"]);
+        let traits = [TraitId::new("T2D".to_string())];
+        let filter = TermFilter { traits: &traits, sources: None, min_beta: Some(0.1) };
+        let terms = graph.terms(&filter).unwrap();
+        assert_eq!(terms.len(), 1);
+        assert_eq!(terms[0].gene_set, "LIPID_TRANSPORT:T2D");
+        let sources = ["go".to_string()];
+        let filter = TermFilter { traits: &[], sources: Some(&sources), min_beta: None };
+        assert_eq!(graph.terms(&filter).unwrap()[0].gene_set, "GO%3A0001:T2D");
+    }
+
+    #[test]
+    fn n_members_counts_linked_nodes() {
+        let graph = load("members");
+        assert_eq!(graph.n_members(MemberKind::Genes).unwrap(), 3);
+        // Trait gene sets are not gene sets.
+        assert_eq!(graph.n_members(MemberKind::GeneSets).unwrap(), 0);
+    }
+}
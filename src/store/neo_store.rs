@@ -0,0 +1,296 @@
+use crate::error::Error;
+use crate::ids::TraitId;
+use crate::lookup::{LookupKind, Page};
+use crate::neo::{Neo, RowEater, TypedRowEater};
+use crate::ping;
+use crate::schema::VECTOR_PROPERTY;
+use crate::store::{EdgeFilter, GraphStore, MemberKind, StoreEdge, StoreNode, Term, TermFilter};
+use neo4rs::{query, Row};
+use serde::Deserialize;
+use serde_json::{json, Map, Value};
+use std::collections::HashMap;
+
+const TRAIT_GENES_QUERY: &str = "\
+MATCH (t:Trait { id: $trait_id })-[e:TRAIT_GENE]->(g:Gene)\n\
+RETURN g.id AS member, e.prior AS score, e.combined AS secondary";
+const TRAIT_GENE_SETS_QUERY: &str = "\
+MATCH (t:Trait { id: $trait_id })-[e:TRAIT_GENE_SET]->(gs:GeneSet)\n\
+RETURN gs.id AS member, e.beta AS score, e.beta_uncorrected AS secondary";
+const N_GENES_QUERY: &str = "\
+MATCH (:Trait)-[:TRAIT_GENE]->(g:Gene)\n\
+RETURN count(DISTINCT g) AS n";
+const N_GENE_SETS_QUERY: &str = "\
+MATCH (:Trait)-[:TRAIT_GENE_SET]->(gs:GeneSet)\n\
+RETURN count(DISTINCT gs) AS n";
+
+mod keys {
+    pub(crate) const ID: &str = "id";
+    pub(crate) const IDS: &str = "ids";
+    pub(crate) const KEYS: &str = "keys";
+    pub(crate) const TYPE: &str = "type";
+    pub(crate) const TYPES: &str = "types";
+    pub(crate) const MIN_WEIGHT: &str = "min_weight";
+    pub(crate) const SKIP: &str = "skip";
+    pub(crate) const LIMIT: &str = "limit";
+    pub(crate) const PROPERTIES: &str = "properties";
+    pub(crate) const TRAIT_ID: &str = "trait_id";
+    pub(crate) const MEMBER: &str = "member";
+    pub(crate) const SCORE: &str = "score";
+    pub(crate) const SECONDARY: &str = "secondary";
+    pub(crate) const N: &str = "n";
+    pub(crate) const TRAITS: &str = "traits";
+    pub(crate) const SOURCES: &str = "sources";
+    pub(crate) const MIN_BETA: &str = "min_beta";
+}
+
+const NEIGHBOR_KEYS: [&str; 6] = ["type", "direction", "labels", "id", "label", "weight"];
+
+#[derive(Deserialize)]
+struct EdgeRow {
+    key: String,
+    rel_type: String,
+    weight: Option<f64>,
+    source_key: String,
+    source_labels: Vec<String>,
+    source_id: Option<String>,
+    source_label: Option<String>,
+    target_key: String,
+    target_labels: Vec<String>,
+    target_id: Option<String>,
+    target_label: Option<String>,
+}
+
+struct JsonRowEater {
+    keys: &'static [&'static str],
+    values: Vec<Map<String, Value>>,
+}
+
+struct MembersRowEater {
+    members: HashMap<String, (f64, f64)>,
+}
+
+struct CountRowEater {
+    count: Option<i64>,
+}
+
+impl RowEater for JsonRowEater {
+    type Summary = Vec<Map<String, Value>>;
+    fn eat(&mut self, row: Row) -> Result<(), Error> {
+        let mut object = Map::new();
+        for key in self.keys {
+            object.insert(key.to_string(), row.get(key)?);
+        }
+        self.values.push(object);
+        Ok(())
+    }
+    fn finish(&mut self) -> Result<Self::Summary, Error> {
+        Ok(std::mem::take(&mut self.values))
+    }
+}
+
+impl RowEater for MembersRowEater {
+    type Summary = HashMap<String, (f64, f64)>;
+    fn eat(&mut self, row: Row) -> Result<(), Error> {
+        let member: String = row.get(keys::MEMBER)?;
+        let score: Option<f64> = row.get(keys::SCORE)?;
+        let secondary: Option<f64> = row.get(keys::SECONDARY)?;
+        self.members.insert(
+            member, (score.unwrap_or(f64::NAN), secondary.unwrap_or(f64::NAN))
+        );
+        Ok(())
+    }
+    fn finish(&mut self) -> Result<Self::Summary, Error> {
+        Ok(std::mem::take(&mut self.members))
+    }
+}
+
+impl RowEater for CountRowEater {
+    type Summary = usize;
+    fn eat(&mut self, row: Row) -> Result<(), Error> {
+        self.count = Some(row.get(keys::N)?);
+        Ok(())
+    }
+    fn finish(&mut self) -> Result<Self::Summary, Error> {
+        let count = self.count.ok_or(Error::from("No count"))?;
+        Ok(count as usize)
+    }
+}
+
+impl EdgeRow {
+    fn into_edge(self) -> StoreEdge {
+        StoreEdge {
+            key: self.key,
+            rel_type: self.rel_type,
+            weight: self.weight,
+            source: StoreNode {
+                key: self.source_key,
+                labels: self.source_labels,
+                id: self.source_id,
+                label: self.source_label,
+            },
+            target: StoreNode {
+                key: self.target_key,
+                labels: self.target_labels,
+                id: self.target_id,
+                label: self.target_label,
+            },
+        }
+    }
+}
+
+fn edge_filter(filter: &EdgeFilter) -> String {
+    let mut conditions: Vec<String> = Vec::new();
+    if filter.rel_types.is_some() {
+        conditions.push(format!("type(r) IN ${}", keys::TYPES));
+    }
+    if filter.min_weight.is_some() {
+        conditions.push(format!(
+            "(r.weight IS NULL OR toFloat(r.weight) >= ${})", keys::MIN_WEIGHT
+        ));
+    }
+    if conditions.is_empty() {
+        String::new()
+    } else {
+        format!("WHERE {}\n", conditions.join(" AND "))
+    }
+}
+
+fn terms_statement(filter: &TermFilter) -> String {
+    let mut conditions: Vec<String> = Vec::new();
+    if !filter.traits.is_empty() {
+        conditions.push(format!("t.id IN ${}", keys::TRAITS));
+    }
+    if filter.sources.is_some() {
+        conditions.push(format!("tgs.source IN ${}", keys::SOURCES));
+    }
+    if filter.min_beta.is_some() {
+        conditions.push(format!("e.beta >= ${}", keys::MIN_BETA));
+    }
+    let filter = if conditions.is_empty() {
+        String::new()
+    } else {
+        format!("WHERE {}\n", conditions.join(" AND "))
+    };
+    format!(
+        "MATCH (t:Trait)-[e:TRAIT_GENE_SET]->(tgs:TraitGeneSet)\n{filter}\
+        RETURN tgs.label AS label, t.id AS phenotype, tgs.id AS gene_set, tgs.source AS source,\n\
+        e.beta AS beta, e.beta_uncorrected AS beta_uncorrected\n\
+        ORDER BY phenotype, gene_set"
+    )
+}
+
+impl GraphStore for Neo {
+    fn lookup(&self, kind: LookupKind, id: &str, page: &Page) -> Result<Vec<Value>, Error> {
+        kind.rows(self, id, page)
+    }
+    fn node(&self, label: &str, id: &str) -> Result<Option<Map<String, Value>>, Error> {
+        let statement = format!(
+            "MATCH (n:{label} {{ id: ${} }}) RETURN properties(n) AS {}", keys::ID,
+            keys::PROPERTIES
+        );
+        let mut row_eater = JsonRowEater { keys: &[keys::PROPERTIES], values: Vec::new() };
+        let mut rows = self.read(query(&statement).param(keys::ID, id), &mut row_eater)?;
+        let properties = rows.pop().and_then(|mut row| row.remove(keys::PROPERTIES));
+        match properties {
+            Some(Value::Object(mut properties)) => {
+                properties.remove(VECTOR_PROPERTY);
+                Ok(Some(properties))
+            }
+            _ => Ok(None),
+        }
+    }
+    fn find_nodes(&self, ids: &[String]) -> Result<Vec<StoreNode>, Error> {
+        let statement = format!(
+            "MATCH (n) WHERE n.id IN ${}\n\
+            RETURN elementId(n) AS key, labels(n) AS labels, n.id AS id, n.label AS label",
+            keys::IDS
+        );
+        self.read(
+            query(&statement).param(keys::IDS, ids.to_vec()),
+            &mut TypedRowEater::new(),
+        )
+    }
+    fn expand(&self, node_keys: &[String], filter: &EdgeFilter) -> Result<Vec<StoreEdge>, Error> {
+        let statement = format!(
+            "MATCH (n)-[r]-(m) WHERE elementId(n) IN ${}\n\
+            WITH DISTINCT r\n\
+            {}\
+            WITH r, startNode(r) AS a, endNode(r) AS b\n\
+            RETURN elementId(r) AS key, type(r) AS rel_type, toFloat(r.weight) AS weight,\n\
+            elementId(a) AS source_key, labels(a) AS source_labels, a.id AS source_id,\n\
+            a.label AS source_label, elementId(b) AS target_key, labels(b) AS target_labels,\n\
+            b.id AS target_id, b.label AS target_label",
+            keys::KEYS, edge_filter(filter)
+        );
+        let mut query = query(&statement).param(keys::KEYS, node_keys.to_vec());
+        if let Some(rel_types) = filter.rel_types {
+            query = query.param(keys::TYPES, rel_types.to_vec());
+        }
+        if let Some(min_weight) = filter.min_weight {
+            query = query.param(keys::MIN_WEIGHT, min_weight);
+        }
+        let rows: Vec<EdgeRow> = self.read(query, &mut TypedRowEater::new())?;
+        Ok(rows.into_iter().map(EdgeRow::into_edge).collect())
+    }
+    fn neighbors(&self, label: &str, id: &str, rel_type: Option<&str>, page: &Page)
+        -> Result<Vec<Value>, Error> {
+        let filter =
+            if rel_type.is_some() { format!("WHERE type(r) = ${}\n", keys::TYPE) } else {
+                String::new()
+            };
+        let direction = if page.ascending { "ASC" } else { "DESC" };
+        let statement = format!(
+            "MATCH (n:{label} {{ id: ${} }})-[r]-(m)\n{filter}\
+            RETURN type(r) AS type, CASE WHEN startNode(r) = n THEN 'out' ELSE 'in' END AS \
+            direction, labels(m) AS labels, m.id AS id, m.label AS label, \
+            toFloat(r.weight) AS weight\n\
+            ORDER BY weight IS NULL, weight {direction}, id\n\
+            SKIP ${} LIMIT ${}", keys::ID, keys::SKIP, keys::LIMIT
+        );
+        let mut query = query(&statement)
+            .param(keys::ID, id)
+            .param(keys::SKIP, page.skip as i64)
+            .param(keys::LIMIT, page.limit as i64);
+        if let Some(rel_type) = rel_type {
+            query = query.param(keys::TYPE, rel_type);
+        }
+        let neighbors =
+            self.read(query, &mut JsonRowEater { keys: &NEIGHBOR_KEYS, values: Vec::new() })?;
+        Ok(neighbors.into_iter().map(Value::Object).collect())
+    }
+    fn trait_members(&self, kind: MemberKind, trait_id: &TraitId)
+        -> Result<HashMap<String, (f64, f64)>, Error> {
+        let members_query = match kind {
+            MemberKind::Genes => TRAIT_GENES_QUERY,
+            MemberKind::GeneSets => TRAIT_GENE_SETS_QUERY,
+        };
+        let query = query(members_query).param(keys::TRAIT_ID, trait_id.as_str());
+        self.read(query, &mut MembersRowEater { members: HashMap::new() })
+    }
+    fn n_members(&self, kind: MemberKind) -> Result<usize, Error> {
+        let count_query = match kind {
+            MemberKind::Genes => N_GENES_QUERY,
+            MemberKind::GeneSets => N_GENE_SETS_QUERY,
+        };
+        self.read(query(count_query), &mut CountRowEater { count: None })
+    }
+    fn terms(&self, filter: &TermFilter) -> Result<Vec<Term>, Error> {
+        let mut query = query(&terms_statement(filter));
+        if !filter.traits.is_empty() {
+            let traits: Vec<String> = filter.traits.iter().map(|id| id.to_string()).collect();
+            query = query.param(keys::TRAITS, traits);
+        }
+        if let Some(sources) = filter.sources {
+            query = query.param(keys::SOURCES, sources.to_vec());
+        }
+        if let Some(min_beta) = filter.min_beta {
+            query = query.param(keys::MIN_BETA, min_beta);
+        }
+        self.read(query, &mut TypedRowEater::new())
+    }
+    fn health(&self) -> Result<Map<String, Value>, Error> {
+        let mut health = Map::new();
+        health.insert("neo4j_time".to_string(), json!(ping::neo4j_time(self)?));
+        Ok(health)
+    }
+}
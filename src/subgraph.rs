@@ -0,0 +1,278 @@
+use crate::config::ExportSubgraphConfig;
+use crate::context::AppContext;
+use crate::error::Error;
+use crate::store;
+use crate::store::{EdgeFilter, GraphStore, StoreNode};
+use log::info;
+use serde_json::json;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+const LABEL_SEPARATOR: &str = ":";
+
+mod formats {
+    pub(crate) const GRAPHML: &str = "graphml";
+    pub(crate) const GEXF: &str = "gexf";
+    pub(crate) const JSON: &str = "json";
+}
+
+struct Edge {
+    source: String,
+    target: String,
+    rel_type: String,
+    weight: Option<f64>,
+}
+
+/// Nodes and edges keyed by the store's node and edge keys, so nodes of different labels
+/// sharing an `id` property stay apart.
+struct Subgraph {
+    nodes: BTreeMap<String, StoreNode>,
+    edges: BTreeMap<String, Edge>,
+}
+
+impl StoreNode {
+    /// A readable node id for the exported file, like `Gene:FADS1`, unique within the export.
+    fn export_id(&self) -> String {
+        match (self.labels.first(), &self.id) {
+            (Some(label), Some(id)) => format!("{label}{LABEL_SEPARATOR}{id}"),
+            _ => self.key.clone(),
+        }
+    }
+    fn labels_string(&self) -> String {
+        self.labels.join(LABEL_SEPARATOR)
+    }
+}
+
+impl Subgraph {
+    fn new() -> Subgraph {
+        Subgraph { nodes: BTreeMap::new(), edges: BTreeMap::new() }
+    }
+    fn add_node(&mut self, node: StoreNode) -> bool {
+        if self.nodes.contains_key(&node.key) {
+            false
+        } else {
+            self.nodes.insert(node.key.clone(), node);
+            true
+        }
+    }
+    fn export_ids(&self) -> BTreeMap<&str, String> {
+        let mut export_ids: BTreeMap<&str, String> = BTreeMap::new();
+        let mut used: BTreeSet<String> = BTreeSet::new();
+        for node in self.nodes.values() {
+            let export_id = node.export_id();
+            let export_id =
+                if used.contains(&export_id) { node.key.clone() } else { export_id };
+            used.insert(export_id.clone());
+            export_ids.insert(node.key.as_str(), export_id);
+        }
+        export_ids
+    }
+}
+
+fn find_seeds(store: &dyn GraphStore, seeds: &[String], subgraph: &mut Subgraph)
+    -> Result<Vec<String>, Error> {
+    let nodes = store.find_nodes(seeds)?;
+    let found: BTreeSet<&str> = nodes.iter().filter_map(|node| node.id.as_deref()).collect();
+    let missing: Vec<&str> =
+        seeds.iter().map(|seed| seed.as_str()).filter(|seed| !found.contains(seed)).collect();
+    if !missing.is_empty() {
+        Err(Error::from(format!("No nodes found for seeds {}.", missing.join(", "))))?
+    }
+    let mut keys: Vec<String> = Vec::new();
+    for node in nodes {
+        keys.push(node.key.clone());
+        subgraph.add_node(node);
+    }
+    Ok(keys)
+}
+
+/// Adds all edges of the frontier nodes that pass the filters, and returns the nodes reached
+/// for the first time.
+fn expand(store: &dyn GraphStore, config: &ExportSubgraphConfig, frontier: Vec<String>,
+          subgraph: &mut Subgraph) -> Result<Vec<String>, Error> {
+    let filter =
+        EdgeFilter { rel_types: config.rel_types.as_deref(), min_weight: config.min_weight };
+    let edges = store.expand(&frontier, &filter)?;
+    let mut reached: Vec<String> = Vec::new();
+    for edge in edges {
+        let source = edge.source.key.clone();
+        let target = edge.target.key.clone();
+        if subgraph.add_node(edge.source) {
+            reached.push(source.clone());
+        }
+        if subgraph.add_node(edge.target) {
+            reached.push(target.clone());
+        }
+        subgraph.edges.insert(edge.key, Edge {
+            source,
+            target,
+            rel_type: edge.rel_type,
+            weight: edge.weight,
+        });
+    }
+    Ok(reached)
+}
+
+fn escape_xml(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn write_graphml(subgraph: &Subgraph, writer: &mut dyn Write) -> Result<(), Error> {
+    let export_ids = subgraph.export_ids();
+    writeln!(writer, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+    writeln!(writer, r#"<graphml xmlns="http://graphml.graphdrawing.org/xmlns">"#)?;
+    writeln!(writer, r#"  <key id="labels" for="node" attr.name="labels" attr.type="string"/>"#)?;
+    writeln!(writer, r#"  <key id="id" for="node" attr.name="id" attr.type="string"/>"#)?;
+    writeln!(writer, r#"  <key id="label" for="node" attr.name="label" attr.type="string"/>"#)?;
+    writeln!(writer, r#"  <key id="type" for="edge" attr.name="type" attr.type="string"/>"#)?;
+    writeln!(writer, r#"  <key id="weight" for="edge" attr.name="weight" attr.type="double"/>"#)?;
+    writeln!(writer, r#"  <graph id="G" edgedefault="directed">"#)?;
+    for node in subgraph.nodes.values() {
+        writeln!(writer, r#"    <node id="{}">"#, escape_xml(&export_ids[node.key.as_str()]))?;
+        writeln!(writer, r#"      <data key="labels">{}</data>"#,
+                 escape_xml(&node.labels_string()))?;
+        if let Some(id) = &node.id {
+            writeln!(writer, r#"      <data key="id">{}</data>"#, escape_xml(id))?;
+        }
+        if let Some(label) = &node.label {
+            writeln!(writer, r#"      <data key="label">{}</data>"#, escape_xml(label))?;
+        }
+        writeln!(writer, "    </node>")?;
+    }
+    for (i, edge) in subgraph.edges.values().enumerate() {
+        writeln!(writer, r#"    <edge id="e{i}" source="{}" target="{}">"#,
+                 escape_xml(&export_ids[edge.source.as_str()]),
+                 escape_xml(&export_ids[edge.target.as_str()]))?;
+        writeln!(writer, r#"      <data key="type">{}</data>"#, escape_xml(&edge.rel_type))?;
+        if let Some(weight) = edge.weight {
+            writeln!(writer, r#"      <data key="weight">{weight}</data>"#)?;
+        }
+        writeln!(writer, "    </edge>")?;
+    }
+    writeln!(writer, "  </graph>")?;
+    writeln!(writer, "</graphml>")?;
+    Ok(())
+}
+
+fn write_gexf(subgraph: &Subgraph, writer: &mut dyn Write) -> Result<(), Error> {
+    let export_ids = subgraph.export_ids();
+    writeln!(writer, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+    writeln!(writer, r#"<gexf xmlns="http://gexf.net/1.3" version="1.3">"#)?;
+    writeln!(writer, r#"  <graph defaultedgetype="directed">"#)?;
+    writeln!(writer, r#"    <attributes class="node">"#)?;
+    writeln!(writer, r#"      <attribute id="labels" title="labels" type="string"/>"#)?;
+    writeln!(writer, r#"      <attribute id="id" title="id" type="string"/>"#)?;
+    writeln!(writer, "    </attributes>")?;
+    writeln!(writer, r#"    <attributes class="edge">"#)?;
+    writeln!(writer, r#"      <attribute id="type" title="type" type="string"/>"#)?;
+    writeln!(writer, "    </attributes>")?;
+    writeln!(writer, "    <nodes>")?;
+    for node in subgraph.nodes.values() {
+        let label = node.label.as_ref().or(node.id.as_ref()).unwrap_or(&node.key);
+        writeln!(writer, r#"      <node id="{}" label="{}">"#,
+                 escape_xml(&export_ids[node.key.as_str()]), escape_xml(label))?;
+        writeln!(writer, "        <attvalues>")?;
+        writeln!(writer, r#"          <attvalue for="labels" value="{}"/>"#,
+                 escape_xml(&node.labels_string()))?;
+        if let Some(id) = &node.id {
+            writeln!(writer, r#"          <attvalue for="id" value="{}"/>"#, escape_xml(id))?;
+        }
+        writeln!(writer, "        </attvalues>")?;
+        writeln!(writer, "      </node>")?;
+    }
+    writeln!(writer, "    </nodes>")?;
+    writeln!(writer, "    <edges>")?;
+    for (i, edge) in subgraph.edges.values().enumerate() {
+        let weight =
+            edge.weight.map(|weight| format!(r#" weight="{weight}""#)).unwrap_or_default();
+        writeln!(writer, r#"      <edge id="e{i}" source="{}" target="{}" label="{}"{weight}>"#,
+                 escape_xml(&export_ids[edge.source.as_str()]),
+                 escape_xml(&export_ids[edge.target.as_str()]), escape_xml(&edge.rel_type))?;
+        writeln!(writer, "        <attvalues>")?;
+        writeln!(writer, r#"          <attvalue for="type" value="{}"/>"#,
+                 escape_xml(&edge.rel_type))?;
+        writeln!(writer, "        </attvalues>")?;
+        writeln!(writer, "      </edge>")?;
+    }
+    writeln!(writer, "    </edges>")?;
+    writeln!(writer, "  </graph>")?;
+    writeln!(writer, "</gexf>")?;
+    Ok(())
+}
+
+/// Writes the elements format that Cytoscape.js and Cytoscape's JSON import understand.
+fn write_cytoscape_json(subgraph: &Subgraph, writer: &mut dyn Write) -> Result<(), Error> {
+    let export_ids = subgraph.export_ids();
+    let nodes: Vec<serde_json::Value> = subgraph.nodes.values().map(|node| {
+        json!({ "data": {
+            "id": export_ids[node.key.as_str()],
+            "name": node.id,
+            "label": node.label,
+            "labels": node.labels,
+        }})
+    }).collect();
+    let edges: Vec<serde_json::Value> = subgraph.edges.values().enumerate().map(|(i, edge)| {
+        json!({ "data": {
+            "id": format!("e{i}"),
+            "source": export_ids[edge.source.as_str()],
+            "target": export_ids[edge.target.as_str()],
+            "type": edge.rel_type,
+            "weight": edge.weight,
+        }})
+    }).collect();
+    let elements = json!({ "elements": { "nodes": nodes, "edges": edges } });
+    serde_json::to_writer_pretty(&mut *writer, &elements)?;
+    writeln!(writer)?;
+    Ok(())
+}
+
+type SubgraphWriter = fn(&Subgraph, &mut dyn Write) -> Result<(), Error>;
+
+fn subgraph_writer(out: &str) -> Result<SubgraphWriter, Error> {
+    let extension =
+        Path::new(out).extension().map(|extension| extension.to_string_lossy().to_lowercase());
+    match extension.as_deref() {
+        Some(formats::GRAPHML) => Ok(write_graphml),
+        Some(formats::GEXF) => Ok(write_gexf),
+        Some(formats::JSON) => Ok(write_cytoscape_json),
+        _ => Err(Error::from(format!(
+            "Unsupported output format for {out}. Supported formats are .{}, .{} and .{} \
+            (Cytoscape.js).", formats::GRAPHML, formats::GEXF, formats::JSON
+        ))),
+    }
+}
+
+pub(crate) fn export_subgraph(context: &AppContext, config: &ExportSubgraphConfig)
+    -> Result<(), Error> {
+    let write = subgraph_writer(&config.out)?;
+    let store = store::open(context, &config.store)?;
+    let mut subgraph = Subgraph::new();
+    let mut frontier = find_seeds(store.as_ref(), &config.seeds, &mut subgraph)?;
+    for hop in 1..=config.hops {
+        if frontier.is_empty() {
+            break;
+        }
+        frontier = expand(store.as_ref(), config, frontier, &mut subgraph)?;
+        info!("Hop {hop}: {} nodes and {} edges, {} new nodes.", subgraph.nodes.len(),
+            subgraph.edges.len(), frontier.len());
+    }
+    info!("Writing {} nodes and {} edges to {}", subgraph.nodes.len(), subgraph.edges.len(),
+        config.out);
+    let mut writer = BufWriter::new(File::create(&config.out)?);
+    write(&subgraph, &mut writer)?;
+    writer.flush()?;
+    Ok(())
+}
@@ -0,0 +1,57 @@
+use std::fmt::{Display, Formatter};
+
+pub(crate) struct Table {
+    headers: Vec<String>,
+    rows: Vec<Vec<String>>,
+}
+
+impl Table {
+    pub(crate) fn new(headers: Vec<String>) -> Table {
+        Table { headers, rows: Vec::new() }
+    }
+    pub(crate) fn add_row(&mut self, row: Vec<String>) {
+        self.rows.push(row);
+    }
+    fn widths(&self) -> Vec<usize> {
+        let mut widths: Vec<usize> =
+            self.headers.iter().map(|header| header.chars().count()).collect();
+        for row in &self.rows {
+            for (i, cell) in row.iter().enumerate() {
+                let width = cell.chars().count();
+                match widths.get_mut(i) {
+                    Some(max_width) => *max_width = (*max_width).max(width),
+                    None => widths.push(width),
+                }
+            }
+        }
+        widths
+    }
+}
+
+fn write_row(f: &mut Formatter<'_>, cells: &[String], widths: &[usize]) -> std::fmt::Result {
+    for (i, width) in widths.iter().enumerate() {
+        let cell = cells.get(i).map(|cell| cell.as_str()).unwrap_or("");
+        if i > 0 {
+            write!(f, " | ")?;
+        }
+        if i + 1 < widths.len() {
+            write!(f, "{cell:<width$}")?;
+        } else {
+            write!(f, "{cell}")?;
+        }
+    }
+    writeln!(f)
+}
+
+impl Display for Table {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let widths = self.widths();
+        write_row(f, &self.headers, &widths)?;
+        let rule: Vec<String> = widths.iter().map(|width| "-".repeat(*width)).collect();
+        writeln!(f, "{}", rule.join("-+-"))?;
+        for row in &self.rows {
+            write_row(f, row, &widths)?;
+        }
+        Ok(())
+    }
+}
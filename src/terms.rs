@@ -0,0 +1,39 @@
+use crate::config::ExportTermsConfig;
+use crate::context::AppContext;
+use crate::error::Error;
+use crate::output;
+use crate::output::{OutputFormat, RecordWriter};
+use crate::store;
+use crate::store::TermFilter;
+use log::info;
+use serde_json::Value;
+
+const FOR: &str = " for ";
+const ASSOCIATED_WITH: &str = " associated with ";
+const COLUMNS: [&str; 5] = ["term", "phenotype", "gene_set", "source", "beta_uncorrected"];
+
+pub(crate) fn export_terms(context: &AppContext, config: &ExportTermsConfig) -> Result<(), Error> {
+    let filter = TermFilter {
+        traits: &config.traits,
+        sources: config.sources.as_deref(),
+        min_beta: config.min_beta,
+    };
+    let store = store::open(context, &config.store)?;
+    let terms = store.terms(&filter)?;
+    let headers: Vec<String> = COLUMNS.iter().map(|column| column.to_string()).collect();
+    let mut writer = output::open(config.out.as_deref())?;
+    let mut record_writer = RecordWriter::new(OutputFormat::Tsv, headers, writer.as_mut())?;
+    for term in &terms {
+        let label = term.label.as_ref().map(|label| label.replace(FOR, ASSOCIATED_WITH));
+        record_writer.write(&[
+            label.map(Value::from).unwrap_or(Value::Null),
+            Value::from(term.phenotype.as_str()),
+            Value::from(term.gene_set.as_str()),
+            term.source.as_deref().map(Value::from).unwrap_or(Value::Null),
+            term.beta_uncorrected.map(Value::from).unwrap_or(Value::Null),
+        ])?;
+    }
+    record_writer.finish()?;
+    info!("Exported {} terms.", terms.len());
+    Ok(())
+}
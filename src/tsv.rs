@@ -75,4 +75,133 @@ impl<M: TsvEaterMaker, F: FnMut(M::Row) -> Result<(), Error>> LineConsumer for T
         }
         Ok(())
     }
-}
\ No newline at end of file
+}
+
+pub(crate) struct Record {
+    fields: Vec<(String, String)>,
+}
+
+impl Record {
+    pub(crate) fn get(&self, name: &str) -> Option<&str> {
+        self.fields.iter().find(|(key, _)| key == name).map(|(_, value)| value.as_str())
+    }
+    pub(crate) fn require(&self, name: &str) -> Result<&str, Error> {
+        self.get(name).ok_or_else(|| Error::from(format!("Missing {name}")))
+    }
+    pub(crate) fn weight(&self, name: &str) -> f64 {
+        self.get(name).and_then(|value| value.parse().ok()).unwrap_or(f64::NAN)
+    }
+    pub(crate) fn into_fields(self) -> Vec<(String, String)> {
+        self.fields
+    }
+}
+
+/// Splits a line of a CSV file as written by the csv crate, which puts fields containing commas
+/// or quotes in quotes and doubles the quotes inside.
+pub(crate) fn split_csv_line(line: &str) -> Result<Vec<String>, Error> {
+    let mut reader =
+        csv::ReaderBuilder::new().has_headers(false).from_reader(line.as_bytes());
+    match reader.records().next() {
+        Some(record) => Ok(record?.iter().map(|field| field.to_string()).collect()),
+        None => Ok(Vec::new()),
+    }
+}
+
+/// Consumes the lines of a CSV file with a header, one record per line.
+pub(crate) struct CsvConsumer<F: FnMut(Record) -> Result<(), Error>> {
+    columns: Option<Vec<String>>,
+    consumer: F,
+}
+
+impl<F: FnMut(Record) -> Result<(), Error>> CsvConsumer<F> {
+    pub(crate) fn new(consumer: F) -> Self {
+        CsvConsumer { columns: None, consumer }
+    }
+}
+
+impl<F: FnMut(Record) -> Result<(), Error>> LineConsumer for CsvConsumer<F> {
+    fn consume(&mut self, line: String) -> Result<(), Error> {
+        if line.is_empty() {
+            return Ok(());
+        }
+        let values = split_csv_line(&line)?;
+        if let Some(columns) = &self.columns {
+            if values.len() != columns.len() {
+                Err(Error::from(format!(
+                    "Expected {} fields, but found {} in line: {line}", columns.len(), values.len()
+                )))?
+            }
+            let fields = columns.iter().cloned().zip(values).collect();
+            (self.consumer)(Record { fields })?;
+        } else {
+            self.columns = Some(values);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{split_csv_line, CsvConsumer, Record, TsvConsumer, TsvEater, TsvEaterMaker};
+    use crate::error::Error;
+    use crate::s3::LineConsumer;
+
+    struct ValuesEater {
+        values: Vec<String>,
+    }
+
+    struct ValuesEaterMaker {}
+
+    impl TsvEater for ValuesEater {
+        type Row = Vec<String>;
+        fn field(&mut self, _name: &str, value: &str) -> Result<(), Error> {
+            self.values.push(value.to_string());
+            Ok(())
+        }
+        fn finish(self) -> Result<Self::Row, Error> {
+            Ok(self.values)
+        }
+    }
+
+    impl TsvEaterMaker for ValuesEaterMaker {
+        type Row = Vec<String>;
+        type Eater = ValuesEater;
+        fn make(&self) -> Self::Eater {
+            ValuesEater { values: Vec::new() }
+        }
+    }
+
+    #[test]
+    fn csv_quoted_fields() {
+        assert_eq!(split_csv_line("a,b,c").unwrap(), vec!["a", "b", "c"]);
+        assert_eq!(split_csv_line(r#""a,b",c"#).unwrap(), vec!["a,b", "c"]);
+        assert_eq!(split_csv_line(r#""say ""hi""",x"#).unwrap(), vec![r#"say "hi""#, "x"]);
+        assert_eq!(split_csv_line("a,,c").unwrap(), vec!["a", "", "c"]);
+    }
+
+    #[test]
+    fn csv_unmatched_quote_is_an_error() {
+        let mut records: Vec<Record> = Vec::new();
+        let mut consumer = CsvConsumer::new(|record| {
+            records.push(record);
+            Ok(())
+        });
+        consumer.consume("a,b,c".to_string()).unwrap();
+        consumer.consume(r#"1,"2,3"#.to_string()).unwrap_err();
+        consumer.consume(r#"1,"2,3",4"#.to_string()).unwrap();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].get("b"), Some("2,3"));
+    }
+
+    #[test]
+    fn tsv_quotes_are_plain_characters() {
+        let mut rows: Vec<Vec<String>> = Vec::new();
+        let mut consumer = TsvConsumer::new('\t', ValuesEaterMaker {}, |row| {
+            rows.push(row);
+            Ok(())
+        });
+        consumer.consume("a\tb\tc".to_string()).unwrap();
+        consumer.consume("\"x\ty\"\tz".to_string()).unwrap();
+        assert_eq!(rows, vec![vec!["\"x", "y\"", "z"]]);
+    }
+}
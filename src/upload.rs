@@ -1,11 +1,14 @@
-use crate::config::ClientConfig;
+use crate::config::UploadConfig;
+use crate::context::AppContext;
 use crate::error::Error;
 use crate::file_info::{FileGroup, FileKind};
+use crate::hgnc::GeneNormalizer;
 use crate::survey::survey;
 use std::fs::File;
 use std::io::BufReader;
 use std::path::Path;
 use crate::neo::{Neo, RowEater};
+use crate::upload::cypher::Provenance;
 use crate::upload::f::upload_f;
 use crate::upload::gc::upload_gc;
 use crate::upload::pc::upload_pc;
@@ -37,28 +40,35 @@ impl RowEater for UploadRowEater {
     }
 }
 
-pub(crate) fn upload_data(config: &ClientConfig) -> Result<(), Error> {
-    let file_infos = survey(&config.local_config)?;
-    let neo = Neo::for_config(&config.neo4j)?;
+pub(crate) fn upload_data(context: &AppContext, config: &UploadConfig) -> Result<(), Error> {
+    let file_infos = survey(&config.client_config.local_config)?;
+    let neo = context.neo(&config.client_config.neo4j)?;
+    let mut genes = GeneNormalizer::for_file(context, config.hgnc_file.as_deref())?;
     let mut row_eater = UploadRowEater::new();
+    let provenance =
+        Provenance { run_id: config.run_id.clone(), sub_dir: config.sub_dir.clone() };
+    log::info!("Uploading as run {} of sub-directory {}.", provenance.run_id, provenance.sub_dir);
     for (key, group) in file_infos.groups {
-        upload_group(&key, &group, config, &neo, &mut row_eater)?
+        upload_group(&key, &group, config, &neo, &mut row_eater, &mut genes, &provenance)?
     }
+    genes.log_summary();
     Ok(())
 }
 
-fn upload_group(key: &[String], group: &FileGroup, config: &ClientConfig, neo: &Neo,
-                row_eater: &mut UploadRowEater) -> Result<(), Error> {
+fn upload_group(key: &[String], group: &FileGroup, config: &UploadConfig, neo: &Neo,
+                row_eater: &mut UploadRowEater, genes: &mut GeneNormalizer,
+                provenance: &Provenance) -> Result<(), Error> {
     for kind in &group.kinds {
-        upload_kind(key, *kind, config, neo, row_eater)?
+        upload_kind(key, *kind, config, neo, row_eater, genes, provenance)?
     }
     Ok(())
 }
 
-fn upload_kind(key: &[String], kind: FileKind, config: &ClientConfig, neo: &Neo,
-               row_eater: &mut UploadRowEater) -> Result<(), Error> {
+fn upload_kind(key: &[String], kind: FileKind, config: &UploadConfig, neo: &Neo,
+               row_eater: &mut UploadRowEater, genes: &mut GeneNormalizer,
+               provenance: &Provenance) -> Result<(), Error> {
     let name = kind.create_name(key);
-    let path = config.local_config.data_dir.join(&name);
+    let path = config.client_config.local_config.data_dir.join(&name);
     let file = File::open(&path).map_err(|io_error|
         Error::wrap(path.display().to_string(), io_error)
     )?;
@@ -66,11 +76,11 @@ fn upload_kind(key: &[String], kind: FileKind, config: &ClientConfig, neo: &Neo,
     match kind {
         FileKind::Gss => { ignore_file(&path) }
         FileKind::Gs => { ignore_file(&path) }
-        FileKind::F => { upload_f(key, reader, neo, row_eater)? }
+        FileKind::F => { upload_f(key, reader, neo, row_eater, provenance)? }
         FileKind::GscOut => { ignore_file(&path) }
         FileKind::GscList => { ignore_file(&path) }
-        FileKind::Gc => { upload_gc(key, reader, neo, row_eater)? }
-        FileKind::Pc => { upload_pc(key, reader, neo, row_eater)? }
+        FileKind::Gc => { upload_gc(key, reader, neo, row_eater, genes, provenance)? }
+        FileKind::Pc => { upload_pc(key, reader, neo, row_eater, provenance)? }
         FileKind::Pc1 => { ignore_file(&path) }
         FileKind::Pc2 => { ignore_file(&path) }
         FileKind::Pc3 => { ignore_file(&path) }
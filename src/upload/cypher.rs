@@ -1,3 +1,4 @@
+use crate::schema::provenance;
 use neo4rs::{Query, query};
 
 const CREATE_FACTOR_NODE: &str = "\
@@ -15,12 +16,33 @@ MERGE (n2:Factor { id: $factor_id })\n\
 MERGE (n1)-[e:CONTROLS]->(n2)\n\
 SET e += { weight: $weight }";
 
+/// The load run, recorded on every node the upload merges so that wipe and status can find it.
+pub(crate) struct Provenance {
+    pub(crate) run_id: String,
+    pub(crate) sub_dir: String,
+}
+
+impl Provenance {
+    /// The statement followed by setting the provenance of the nodes bound to the variables.
+    fn query(&self, statement: &str, variables: &[&str]) -> Query {
+        let sets: Vec<String> = variables.iter().map(|variable| format!(
+            "SET {variable}.{run_id} = ${run_id}, {variable}.{sub_dir} = ${sub_dir}, \
+            {variable}.{loaded_at} = datetime()",
+            run_id = provenance::RUN_ID, sub_dir = provenance::SUB_DIR,
+            loaded_at = provenance::LOADED_AT
+        )).collect();
+        query(&format!("{statement}\n{}", sets.join("\n")))
+            .param(provenance::RUN_ID, self.run_id.as_str())
+            .param(provenance::SUB_DIR, self.sub_dir.as_str())
+    }
+}
+
 pub(crate) struct CreateFactorNodeQueryBuilder {
     query: Query,
 }
 
 pub(crate) trait CreateEntityEdgeQueryBuilder {
-    fn new() -> Self;
+    fn new(provenance: &Provenance) -> Self;
     fn create_query(&self, entity_id: &str, factor_id: &str, weight: f64) -> Query;
 }
 pub(crate) struct CreateGeneEdgeQueryBuilder {
@@ -30,9 +52,9 @@ pub(crate) struct CreatePhenoEdgeQueryBuilder {
     query: Query,
 }
 impl CreateFactorNodeQueryBuilder {
-    pub(crate) fn new() -> Self {
+    pub(crate) fn new(provenance: &Provenance) -> Self {
         CreateFactorNodeQueryBuilder {
-            query: query(CREATE_FACTOR_NODE),
+            query: provenance.query(CREATE_FACTOR_NODE, &["n"]),
         }
     }
     pub(crate) fn create_query(&self, id: &str, name: &str) -> Query {
@@ -41,9 +63,9 @@ impl CreateFactorNodeQueryBuilder {
 }
 
 impl CreateEntityEdgeQueryBuilder for CreateGeneEdgeQueryBuilder {
-    fn new() -> Self {
+    fn new(provenance: &Provenance) -> Self {
         CreateGeneEdgeQueryBuilder {
-            query: query(CREATE_GENE_EDGE),
+            query: provenance.query(CREATE_GENE_EDGE, &["n1", "n2"]),
         }
     }
     fn create_query(&self, entity_id: &str, factor_id: &str, weight: f64) -> Query {
@@ -55,9 +77,9 @@ impl CreateEntityEdgeQueryBuilder for CreateGeneEdgeQueryBuilder {
     }
 }
 impl CreateEntityEdgeQueryBuilder for CreatePhenoEdgeQueryBuilder {
-    fn new() -> Self {
+    fn new(provenance: &Provenance) -> Self {
         CreatePhenoEdgeQueryBuilder {
-            query: query(CREATE_PHENO_EDGE),
+            query: provenance.query(CREATE_PHENO_EDGE, &["n1", "n2"]),
         }
     }
     fn create_query(&self, entity_id: &str, factor_id: &str, weight: f64) -> Query {
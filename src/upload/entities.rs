@@ -1,4 +1,5 @@
 use crate::error::Error;
+use crate::hgnc::GeneNormalizer;
 use crate::neo::Neo;
 use crate::tsv::{TsvEater, TsvEaterMaker, TsvReader};
 use crate::upload::cypher::CreateEntityEdgeQueryBuilder;
@@ -47,9 +48,9 @@ impl TsvEater for EntityUploadEater {
     fn field(&mut self, name: &str, value: &str) -> Result<(), Error> {
         if name == self.entity_class {
             self.entity = Some(value.to_string())
-        } else if let Some(subkey) = name.strip_prefix("Factor") {
+        } else if name.starts_with("Factor") {
             let weight = value.parse::<f64>()?;
-            self.subkeys.push(subkey.to_string());
+            self.subkeys.push(name.to_string());
             self.weights.push(weight);
         }
         Ok(())
@@ -73,6 +74,7 @@ impl TsvEater for EntityUploadEater {
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn upload_rows<R: Read, B: CreateEntityEdgeQueryBuilder>(
     key: &[String],
     reader: BufReader<R>,
@@ -80,12 +82,17 @@ pub fn upload_rows<R: Read, B: CreateEntityEdgeQueryBuilder>(
     query_builder: &B,
     row_eater: &mut UploadRowEater,
     eater_maker: EntityUploadEaterMaker,
-    threshold: f64
+    threshold: f64,
+    mut genes: Option<&mut GeneNormalizer>,
 ) -> Result<(), Error> {
     let tsv_reader: TsvReader<_, EntityUploadEaterMaker> = 
         TsvReader::new(reader, '\t', eater_maker)?;
     for row in tsv_reader {
-        upload_row(key, neo, query_builder, row_eater, row?, threshold)?;
+        let mut row = row?;
+        if let Some(genes) = genes.as_deref_mut() {
+            row.entity = genes.normalize(&row.entity);
+        }
+        upload_row(key, neo, query_builder, row_eater, row, threshold)?;
     }
     Ok(())
 }
@@ -102,7 +109,8 @@ fn upload_row<B: CreateEntityEdgeQueryBuilder>(
         if weight > row.weight_max * threshold {
             let factor_id = factor_id(key, subkey);
             println!("{}, {}, {}", &row.entity, &factor_id, weight);
-            let query = query_builder.create_query(&row.entity, &factor_id, weight);
+            let query =
+                query_builder.create_query(&row.entity, &factor_id.to_string(), weight);
             neo.cypher(query, row_eater)?;
         }
     }
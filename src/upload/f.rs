@@ -1,7 +1,7 @@
 use crate::error::Error;
 use crate::neo::Neo;
 use crate::tsv::{TsvEater, TsvEaterMaker};
-use crate::upload::cypher::CreateFactorNodeQueryBuilder;
+use crate::upload::cypher::{CreateFactorNodeQueryBuilder, Provenance};
 use crate::upload::UploadRowEater;
 use std::io::{BufReader, Read};
 use crate::upload::factor::factor_id;
@@ -31,7 +31,7 @@ impl TsvEater for FUploadEater {
     fn field(&mut self, name: &str, value: &str) -> Result<(), Error> {
         match name {
             FACTOR =>
-                self.subkey = value.strip_prefix("Factor").map(|s| s.to_string()),
+                self.subkey = Some(value.to_string()).filter(|s| s.starts_with("Factor")),
             LABEL => self.label = Some(value.to_string()),
             _ => {}
         }
@@ -45,11 +45,11 @@ impl TsvEater for FUploadEater {
 }
 
 pub(crate) fn upload_f<R: Read>(key: &[String], reader: BufReader<R>, neo: &Neo,
-                                row_eater: &mut UploadRowEater)
+                                row_eater: &mut UploadRowEater, provenance: &Provenance)
                                  -> Result<(), Error> {
     let eater_maker = FUploadEaterMaker {};
     let tsv_reader = crate::tsv::TsvReader::new(reader, '\t', eater_maker)?;
-    let query_builder = CreateFactorNodeQueryBuilder::new();
+    let query_builder = CreateFactorNodeQueryBuilder::new(provenance);
     for row in tsv_reader {
         let row = row?;
         upload_row(key, neo, &query_builder, row_eater, row)?;
@@ -61,7 +61,7 @@ fn upload_row(key: &[String], neo: &Neo, query_builder: &CreateFactorNodeQueryBu
               row_eater: &mut UploadRowEater, row: Row)
               -> Result<(), Error> {
     let node_id = factor_id(key, &row.subkey);
-    let query = query_builder.create_query(&node_id, &row.label);
+    let query = query_builder.create_query(&node_id.to_string(), &row.label);
     println!("{}, {}", &node_id, &row.label);
     neo.cypher(query, row_eater)?;
     Ok(())
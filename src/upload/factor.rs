@@ -1,7 +1,5 @@
-pub(crate) fn factor_id(key: &[String], subkey: &str) -> String {
-    if key.is_empty() {
-        subkey.to_string()
-    } else {
-        format!("{}_{}", key.join("_"), subkey)
-    }
-}
\ No newline at end of file
+use crate::ids::{FactorId, TraitId};
+
+pub(crate) fn factor_id(key: &[String], subkey: &str) -> FactorId {
+    FactorId::new(subkey.to_string(), TraitId::new(key.join("_")))
+}
@@ -1,9 +1,10 @@
 use crate::error::Error;
+use crate::hgnc::GeneNormalizer;
 use crate::neo::Neo;
 use crate::upload::entities::EntityUploadEaterMaker;
 use crate::upload::{entities, UploadRowEater};
 use std::io::{BufReader, Read};
-use crate::upload::cypher::{CreateEntityEdgeQueryBuilder, CreateGeneEdgeQueryBuilder};
+use crate::upload::cypher::{CreateEntityEdgeQueryBuilder, CreateGeneEdgeQueryBuilder, Provenance};
 
 mod fields {
     pub const GENE: &str = "Gene";
@@ -11,10 +12,12 @@ mod fields {
 
 const THRESHOLD: f64 = 0.01;
 pub(crate) fn upload_gc<R: Read>(key: &[String], reader: BufReader<R>, neo: &Neo,
-                                 row_eater: &mut UploadRowEater) -> Result<(), Error> {
-    let query_builder = CreateGeneEdgeQueryBuilder::new();
+                                 row_eater: &mut UploadRowEater, genes: &mut GeneNormalizer,
+                                 provenance: &Provenance) -> Result<(), Error> {
+    let query_builder = CreateGeneEdgeQueryBuilder::new(provenance);
     let eater_maker = EntityUploadEaterMaker::new(fields::GENE.to_string());
-    entities::upload_rows(key, reader, neo, &query_builder, row_eater, eater_maker, THRESHOLD)?;
+    entities::upload_rows(key, reader, neo, &query_builder, row_eater, eater_maker, THRESHOLD,
+                          Some(genes))?;
     Ok(())
 }
 
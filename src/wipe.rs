@@ -1,61 +1,255 @@
-use log::{error, info};
-use neo4rs::query;
-use crate::config::ClientConfig;
+use crate::config::{WipeConfig, WipeScope};
+use crate::context::AppContext;
 use crate::error::Error;
-use crate::neo::RowEater;
+use crate::ids::TraitId;
+use crate::neo::{Neo, TypedRowEater};
+use crate::schema::{labels, provenance, rel_types};
+use log::info;
+use neo4rs::{query, Query};
+use serde::Deserialize;
+use std::io::{BufRead, IsTerminal, Write};
 
-struct WipeRowEater {
+const ALL_COUNTS_QUERY: &str = "\
+MATCH (n)\n\
+UNWIND labels(n) AS label\n\
+RETURN label, count(*) AS count\n\
+ORDER BY label";
+const ALL_NODES_QUERY: &str = "MATCH (n) RETURN count(n) AS n";
+const ALL_RELATIONSHIPS_QUERY: &str = "MATCH ()-[r]->() RETURN count(r) AS n";
+const DELETE_ALL_RELATIONSHIPS_QUERY: &str = "\
+MATCH ()-[r]->()\n\
+CALL (r) { DELETE r } IN TRANSACTIONS OF $batch_size ROWS";
+const DELETE_ALL_NODES_QUERY: &str = "\
+MATCH (n)\n\
+CALL (n) { DELETE n } IN TRANSACTIONS OF $batch_size ROWS";
+// The scoped statements follow a MATCH of the nodes in scope, bound to n.
+const SCOPED_COUNTS_QUERY: &str = "\
+UNWIND labels(n) AS label\n\
+RETURN label, count(*) AS count\n\
+ORDER BY label";
+const SCOPED_NODES_QUERY: &str = "RETURN count(n) AS n";
+const SCOPED_RELATIONSHIPS_QUERY: &str = "\
+MATCH (n)-[r]-()\n\
+RETURN count(DISTINCT r) AS n";
+// Collects the nodes first, since whether a factor is in scope may depend on relationships
+// deleted along the way.
+const DELETE_SCOPED_QUERY: &str = "\
+WITH collect(n) AS nodes\n\
+UNWIND nodes AS n\n\
+CALL (n) { DETACH DELETE n } IN TRANSACTIONS OF $batch_size ROWS";
+const SHOW_CONSTRAINTS_QUERY: &str = "SHOW CONSTRAINTS YIELD name, labelsOrTypes";
+const SHOW_INDEXES_QUERY: &str =
+    "SHOW INDEXES YIELD name, type, labelsOrTypes WHERE type <> 'LOOKUP'";
 
+mod keys {
+    pub(crate) const BATCH_SIZE: &str = "batch_size";
+    pub(crate) const LABELS: &str = "labels";
+    pub(crate) const TRAITS: &str = "traits";
 }
 
-impl WipeRowEater {
-    pub(crate) fn new() -> WipeRowEater {
-        WipeRowEater {}
+#[derive(Deserialize)]
+struct LabelCount {
+    label: String,
+    count: i64,
+}
+
+#[derive(Deserialize)]
+struct Count {
+    n: i64,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SchemaItem {
+    name: String,
+    labels_or_types: Option<Vec<String>>,
+}
+
+/// The nodes to delete, either all of them or those matching the scope.
+enum Selection<'a> {
+    All,
+    Scoped { scope: &'a WipeScope, matching: String },
+}
+
+impl<'a> Selection<'a> {
+    fn new(scope: &'a WipeScope) -> Selection<'a> {
+        let conditions = scope_conditions(scope);
+        if conditions.is_empty() {
+            Selection::All
+        } else {
+            let matching = format!("MATCH (n) WHERE {}", conditions.join(" AND "));
+            Selection::Scoped { scope, matching }
+        }
+    }
+    fn query(&self, all: &str, scoped: &str) -> Query {
+        match self {
+            Selection::All => query(all),
+            Selection::Scoped { scope, matching } => {
+                scope_params(query(&format!("{matching}\n{scoped}")), scope)
+            }
+        }
+    }
+}
+
+fn count(neo: &Neo, query: Query) -> Result<i64, Error> {
+    let counts: Vec<Count> = neo.read(query, &mut TypedRowEater::new())?;
+    Ok(counts.first().map(|count| count.n).unwrap_or(0))
+}
+
+/// The conditions on node `n` for each given scope. A trait's own factors and trait gene sets
+/// are in scope too, unless they also belong to a trait that is not.
+fn scope_conditions(scope: &WipeScope) -> Vec<String> {
+    let mut conditions: Vec<String> = Vec::new();
+    if scope.labels.is_some() {
+        conditions.push(format!("any(label IN labels(n) WHERE label IN ${})", keys::LABELS));
+    }
+    if !scope.traits.is_empty() {
+        let owned = format!("(n)<-[:{}|{}]-(t:{})", rel_types::TRAIT_FACTOR,
+                            rel_types::TRAIT_GENE_SET, labels::TRAIT);
+        conditions.push(format!(
+            "((n:{trait_label} AND n.id IN ${traits}) \
+            OR (EXISTS {{ MATCH {owned} WHERE t.id IN ${traits} }} \
+            AND NOT EXISTS {{ MATCH {owned} WHERE NOT t.id IN ${traits} }}))",
+            trait_label = labels::TRAIT, traits = keys::TRAITS
+        ));
+    }
+    if scope.run_id.is_some() {
+        conditions.push(format!("n.{0} = ${0}", provenance::RUN_ID));
     }
+    if scope.sub_dir.is_some() {
+        conditions.push(format!("n.{0} = ${0}", provenance::SUB_DIR));
+    }
+    conditions
 }
 
-impl RowEater for WipeRowEater {
-    type Summary = ();
-    fn eat(&mut self, row: neo4rs::Row) -> Result<(), Error> {
-        info!("{row:?}");
-        Ok(())
+fn scope_params(mut query: Query, scope: &WipeScope) -> Query {
+    if let Some(labels) = &scope.labels {
+        query = query.param(keys::LABELS, labels.clone());
+    }
+    if !scope.traits.is_empty() {
+        let traits: Vec<String> = scope.traits.iter().map(TraitId::to_string).collect();
+        query = query.param(keys::TRAITS, traits);
+    }
+    if let Some(run_id) = &scope.run_id {
+        query = query.param(provenance::RUN_ID, run_id.as_str());
     }
-    fn finish(&mut self) -> Result<(), Error> {
-        Ok(())
+    if let Some(sub_dir) = &scope.sub_dir {
+        query = query.param(provenance::SUB_DIR, sub_dir.as_str());
     }
+    query
 }
 
-pub(crate) fn wipe(config: &ClientConfig) -> Result<(), Error> {
-    let neo = crate::neo::Neo::for_config(&config.neo4j)?;
-    let mut n_nodes: i64 = 1;
-    let mut n_retries: usize = 0;
-    const MAX_RETRIES: usize = 10;
-    loop {
-        let query_base = query("MATCH (n) LIMIT $n_nodes DETACH DELETE n");
-        let query = query_base.param("n_nodes", n_nodes);
-        let mut row_eater = WipeRowEater::new();
-        info!("Wiping {n_nodes} nodes");
-        match neo.cypher(query, &mut row_eater) {
-            Ok(_) => {
-                info!("Wiped {n_nodes} nodes");
-                n_retries = 0;
-                n_nodes += 1 + n_nodes / 10;
-                if n_nodes > i64::MAX / 10 {
-                    break;
-                }
-            }
-            Err(_) => {
-                error!("Failed to wipe {n_nodes} nodes");
-                n_nodes -= 1 + n_nodes / 10;
-                if n_nodes < 1 {
-                    n_nodes = 1;
-                    n_retries += 1;
-                    if n_retries >= MAX_RETRIES {
-                        break;
-                    }
-                }
-            }
-        };
+fn describe_scope(scope: &WipeScope) -> String {
+    let mut parts: Vec<String> = Vec::new();
+    if let Some(labels) = &scope.labels {
+        parts.push(format!("labels {}", labels.join(", ")));
+    }
+    if !scope.traits.is_empty() {
+        let traits: Vec<String> = scope.traits.iter().map(TraitId::to_string).collect();
+        parts.push(format!("traits {}", traits.join(", ")));
+    }
+    if let Some(run_id) = &scope.run_id {
+        parts.push(format!("run id {run_id}"));
+    }
+    if let Some(sub_dir) = &scope.sub_dir {
+        parts.push(format!("sub-directory {sub_dir}"));
+    }
+    if parts.is_empty() { "everything".to_string() } else { parts.join("; ") }
+}
+
+/// Asks on the terminal whether to go ahead with deleting data.
+pub(crate) fn confirm(prompt: &str) -> Result<bool, Error> {
+    if !std::io::stdin().is_terminal() {
+        Err(Error::from("Not asking for confirmation without a terminal. Pass --yes to proceed."))?
+    }
+    print!("{prompt} [y/N] ");
+    std::io::stdout().flush()?;
+    let mut answer = String::new();
+    std::io::stdin().lock().read_line(&mut answer)?;
+    Ok(matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"))
+}
+
+/// Drops constraints and indexes, only those on the given labels if any.
+fn drop_schema(neo: &Neo, labels: Option<&[String]>) -> Result<(), Error> {
+    let applies = |item: &SchemaItem| match (labels, &item.labels_or_types) {
+        (None, _) => true,
+        (Some(labels), Some(item_labels)) => item_labels.iter().any(|label| labels.contains(label)),
+        (Some(_), None) => false,
+    };
+    let constraints: Vec<SchemaItem> =
+        neo.read(query(SHOW_CONSTRAINTS_QUERY), &mut TypedRowEater::new())?;
+    for constraint in constraints.iter().filter(|constraint| applies(constraint)) {
+        info!("Dropping constraint {}", constraint.name);
+        let statement = format!("DROP CONSTRAINT `{}` IF EXISTS", constraint.name);
+        let _: Vec<Count> = neo.cypher(query(&statement), &mut TypedRowEater::new())?;
+    }
+    let indexes: Vec<SchemaItem> =
+        neo.read(query(SHOW_INDEXES_QUERY), &mut TypedRowEater::new())?;
+    for index in indexes.iter().filter(|index| applies(index)) {
+        info!("Dropping index {}", index.name);
+        let statement = format!("DROP INDEX `{}` IF EXISTS", index.name);
+        let _: Vec<Count> = neo.cypher(query(&statement), &mut TypedRowEater::new())?;
     }
     Ok(())
-}
\ No newline at end of file
+}
+
+pub(crate) fn wipe(context: &AppContext, config: &WipeConfig) -> Result<(), Error> {
+    let neo = context.neo(&config.neo4j)?;
+    let selection = Selection::new(&config.scope);
+    let label_counts: Vec<LabelCount> = neo.read(
+        selection.query(ALL_COUNTS_QUERY, SCOPED_COUNTS_QUERY), &mut TypedRowEater::new()
+    )?;
+    let n_nodes = count(&neo, selection.query(ALL_NODES_QUERY, SCOPED_NODES_QUERY))?;
+    let n_relationships =
+        count(&neo, selection.query(ALL_RELATIONSHIPS_QUERY, SCOPED_RELATIONSHIPS_QUERY))?;
+    println!("Scope: {}", describe_scope(&config.scope));
+    for label_count in &label_counts {
+        println!("  {}: {}", label_count.label, label_count.count);
+    }
+    println!("Nodes to delete: {n_nodes}");
+    println!("Relationships to delete: {n_relationships}");
+    if config.drop_schema {
+        println!("Constraints and indexes will be dropped.");
+    }
+    if n_nodes == 0 && n_relationships == 0 && !config.drop_schema {
+        info!("Nothing to wipe.");
+        return Ok(());
+    }
+    if !config.yes && !confirm("Proceed?")? {
+        Err(Error::from("Wipe cancelled."))?
+    }
+    let batch_size = config.batch_size as i64;
+    match &selection {
+        Selection::All => {
+            info!("Deleting {n_relationships} relationships in batches of {batch_size}.");
+            let _: Vec<Count> = neo.cypher(
+                query(DELETE_ALL_RELATIONSHIPS_QUERY).param(keys::BATCH_SIZE, batch_size),
+                &mut TypedRowEater::new()
+            )?;
+            info!("Deleting {n_nodes} nodes in batches of {batch_size}.");
+            let _: Vec<Count> = neo.cypher(
+                query(DELETE_ALL_NODES_QUERY).param(keys::BATCH_SIZE, batch_size),
+                &mut TypedRowEater::new()
+            )?;
+        }
+        Selection::Scoped { .. } => {
+            info!("Deleting {n_nodes} nodes with their relationships in batches of {batch_size}.");
+            let _: Vec<Count> = neo.cypher(
+                selection.query(DELETE_ALL_NODES_QUERY, DELETE_SCOPED_QUERY)
+                    .param(keys::BATCH_SIZE, batch_size),
+                &mut TypedRowEater::new()
+            )?;
+        }
+    }
+    let n_remaining = count(&neo, selection.query(ALL_NODES_QUERY, SCOPED_NODES_QUERY))?;
+    if n_remaining > 0 {
+        Err(Error::from(format!(
+            "Wipe incomplete: {n_remaining} of {n_nodes} nodes could not be deleted."
+        )))?
+    }
+    info!("Deleted {n_nodes} nodes and {n_relationships} relationships.");
+    if config.drop_schema {
+        drop_schema(&neo, config.scope.labels.as_deref())?;
+    }
+    Ok(())
+}